@@ -1,6 +1,21 @@
-use clap::{Parser, Subcommand};
-use misty_db::interpreter::Interpreter;
+use clap::{Parser, Subcommand, ValueEnum};
+use misty_db::interpreter::{Interpreter, RunOptions};
+use misty_db::interpreter::debugger::Debugger;
+use misty_db::interpreter::diagnostic::Severity;
+use misty_db::interpreter::evaluator::{Evaluator, Exit};
+use misty_db::interpreter::formatter::Formatter;
+use misty_db::interpreter::interrupt;
+use misty_db::interpreter::lsp::LanguageServer;
+use misty_db::interpreter::linter::{Lint, LintConfig, Linter, Rule};
+use misty_db::interpreter::modules::ModuleLoader;
+use misty_db::interpreter::parser::Parser as MuParser;
+use misty_db::interpreter::repl::{ReplHelper, Session, is_complete};
+use misty_db::interpreter::tokenizer::Tokenizer;
+use lsp_server::Connection;
+use serde::Serialize;
+use std::collections::HashSet;
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 
 #[derive(Parser)]
@@ -15,19 +30,155 @@ struct Cli {
 enum Commands {
     /// Run a Mu script from a file
     Run {
+        /// Path to the .mu source file, `-` reads it from stdin
+        path: String,
+        /// Arguments for the script, after `--`, readable as the `args` list
+        #[arg(last = true)]
+        args: Vec<String>,
+        /// Skip the static analysis and type checking passes before evaluation
+        #[arg(long)]
+        no_check: bool,
+        /// Compile to bytecode and run it on the stack VM
+        #[arg(long)]
+        vm: bool,
+        /// Skip constant folding and dead code elimination
+        #[arg(long)]
+        no_optimize: bool,
+        /// Print the AST after optimization
+        #[arg(long)]
+        dump_optimized: bool,
+        /// More directories to look for imported modules in
+        #[arg(short = 'L', long = "lib-path")]
+        lib_path: Vec<PathBuf>,
+    },
+    /// Run Mu code given on the command line
+    Eval {
+        /// The code to run
+        #[arg(short = 'e', long = "expr")]
+        code: String,
+        /// Arguments for the code, after `--`, readable as the `args` list
+        #[arg(last = true)]
+        args: Vec<String>,
+        /// More directories to look for imported modules in
+        #[arg(short = 'L', long = "lib-path")]
+        lib_path: Vec<PathBuf>,
+    },
+    /// Rewrite Mu scripts in the canonical layout
+    Fmt {
+        /// Paths to the .mu source files
+        #[arg(required = true)]
+        paths: Vec<String>,
+        /// Do not write anything, exit with an error if a file is not formatted
+        #[arg(long)]
+        check: bool,
+    },
+    /// Lint Mu scripts without running them
+    Check {
+        /// Paths to the .mu source files
+        #[arg(required = true)]
+        paths: Vec<String>,
+        /// Output format of the report
+        #[arg(long, value_enum, default_value = "human")]
+        format: ReportFormat,
+        /// Do not report a rule (unused-variable, unused-function, shadowed-parameter, bool-comparison, constant-condition, non-exhaustive-match, unreachable-arm)
+        #[arg(long, value_name = "RULE")]
+        allow: Vec<String>,
+        /// Report a rule as an error instead of a warning
+        #[arg(long, value_name = "RULE")]
+        deny: Vec<String>,
+    },
+    /// Print the tokens of a Mu script
+    Tokens {
         /// Path to the .mu source file
         path: String,
+        #[arg(long, value_enum, default_value = "json")]
+        format: DumpFormat,
     },
+    /// Print the syntax tree of a Mu script
+    Ast {
+        /// Path to the .mu source file
+        path: String,
+        #[arg(long, value_enum, default_value = "json")]
+        format: DumpFormat,
+    },
+    /// Run a Mu script under the step debugger
+    Debug {
+        /// Path to the .mu source file
+        path: String,
+    },
+    /// Start a language server for editors, speaking LSP over stdio
+    Lsp,
     /// Start an interactive REPL
     Repl,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum DumpFormat {
+    Json,
+    Debug,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    Human,
+    Json,
+}
+
+// One entry of `mu check --format json`
+#[derive(Serialize)]
+struct JsonLint<'a> {
+    file: &'a str,
+    rule: Option<Rule>,
+    severity: Severity,
+    message: &'a str,
+    line: usize,
+    column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
 fn main() {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Run { path } => {
-            run_file(&path);
+        Commands::Run { path, args, no_check, vm, no_optimize, dump_optimized, lib_path } => {
+            let options = RunOptions {
+                check: !no_check,
+                vm,
+                optimize: !no_optimize,
+                dump_optimized,
+                args,
+                // a script read from stdin imports from the current directory
+                path: (path != "-").then(|| PathBuf::from(&path)),
+                search_path: lib_path,
+            };
+            run_file(&path, &options);
+        }
+        Commands::Eval { code, args, lib_path } => {
+            let options = RunOptions { args, search_path: lib_path, ..RunOptions::default() };
+            run_source(code, &options);
+        }
+        Commands::Fmt { paths, check } => {
+            format_files(&paths, check);
+        }
+        Commands::Check { paths, format, allow, deny } => {
+            let config = LintConfig {
+                allowed: parse_rules(&allow),
+                denied: parse_rules(&deny),
+            };
+            check_files(&paths, format, &config);
+        }
+        Commands::Tokens { path, format } => {
+            dump_tokens(&path, format);
+        }
+        Commands::Ast { path, format } => {
+            dump_ast(&path, format);
+        }
+        Commands::Debug { path } => {
+            debug_file(&path);
+        }
+        Commands::Lsp => {
+            run_language_server();
         }
         Commands::Repl => {
             run_repl();
@@ -35,60 +186,349 @@ fn main() {
     }
 }
 
-fn run_file(path: &str) {
-    println!("-- Mu Interpreter --");
-    println!("Running file: {}", path);
-    
-    let source = match fs::read_to_string(path) {
+fn debug_file(path: &str) {
+    use std::io::{BufReader, stdin, stdout};
+
+    let source = read_source(path);
+    let ast = Tokenizer::tokenize(source.clone())
+        .map_err(|e| format!("Failed to tokenize input: {}", e))
+        .and_then(|tokens| MuParser::new(tokens).parse().map_err(|e| format!("Failed to parse tokens: {}", e)))
+        .and_then(|ast| {
+            let file = (path != "-").then(|| Path::new(path));
+            ModuleLoader::new(Vec::new()).link(ast, file).map_err(|e| e.to_string())
+        });
+
+    let ast = match ast {
+        Ok(ast) => ast,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
+
+    println!("-- Mu Debugger -- type 'help' for the commands");
+
+    let mut evaluator = Evaluator::new();
+    evaluator.set_debug_hook(Box::new(Debugger::new(&source, Box::new(BufReader::new(stdin())), Box::new(stdout()))));
+
+    match evaluator.evaluate(ast) {
+        Ok(_) => println!("Program finished."),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit(1);
+        }
+    }
+}
+
+fn run_language_server() {
+    let (connection, io_threads) = Connection::stdio();
+
+    let result = LanguageServer::new(connection).run();
+    if let Err(e) = result.and_then(|_| io_threads.join().map_err(Into::into)) {
+        eprintln!("Language server error: {}", e);
+        exit(1);
+    }
+}
+
+// `-` is the standard input
+fn read_source(path: &str) -> String {
+    let content = if path == "-" {
+        std::io::read_to_string(std::io::stdin())
+    } else {
+        fs::read_to_string(path)
+    };
+
+    match content {
         Ok(content) => content,
         Err(e) => {
             eprintln!("Error reading file '{}': {}", path, e);
             exit(1);
         }
+    }
+}
+
+fn dump_tokens(path: &str, format: DumpFormat) {
+    let tokens = match Tokenizer::tokenize(read_source(path)) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            eprintln!("Failed to tokenize input: {}", e);
+            exit(1);
+        }
+    };
+
+    match format {
+        DumpFormat::Json => println!("{}", serde_json::to_string_pretty(&tokens).expect("tokens are always serializable")),
+        DumpFormat::Debug => println!("{:#?}", tokens),
+    }
+}
+
+fn dump_ast(path: &str, format: DumpFormat) {
+    let ast = Tokenizer::tokenize(read_source(path))
+        .map_err(|e| format!("Failed to tokenize input: {}", e))
+        .and_then(|tokens| MuParser::new(tokens).parse().map_err(|e| format!("Failed to parse tokens: {}", e)))
+        .and_then(|ast| {
+            let file = (path != "-").then(|| Path::new(path));
+            ModuleLoader::new(Vec::new()).link(ast, file).map_err(|e| e.to_string())
+        });
+
+    let ast = match ast {
+        Ok(ast) => ast,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
     };
 
-    match Interpreter::execute_full_pipeline(source) {
+    match format {
+        DumpFormat::Json => println!("{}", serde_json::to_string_pretty(&ast).expect("the AST is always serializable")),
+        DumpFormat::Debug => println!("{:#?}", ast),
+    }
+}
+
+fn parse_rules(names: &[String]) -> HashSet<Rule> {
+    names
+        .iter()
+        .map(|name| match Rule::from_name(name) {
+            Some(rule) => rule,
+            None => {
+                let known: Vec<&str> = Rule::ALL.iter().map(|rule| rule.name()).collect();
+                eprintln!("Unknown rule '{}', expected one of: {}", name, known.join(", "));
+                exit(2);
+            }
+        })
+        .collect()
+}
+
+fn check_files(paths: &[String], format: ReportFormat, config: &LintConfig) {
+    let mut reports: Vec<(&str, Vec<Lint>)> = Vec::new();
+
+    for path in paths {
+        match fs::read_to_string(path) {
+            Ok(source) => reports.push((path, Linter::check_source(&source, config))),
+            Err(e) => {
+                eprintln!("Error reading file '{}': {}", path, e);
+                exit(1);
+            }
+        }
+    }
+
+    let lints = reports.iter().flat_map(|(path, lints)| lints.iter().map(move |lint| (*path, lint)));
+    let errors = lints.clone().filter(|(_, lint)| lint.diagnostic.is_error()).count();
+
+    match format {
+        ReportFormat::Human => {
+            let mut total = 0;
+            for (path, lint) in lints {
+                let d = &lint.diagnostic;
+                match lint.rule {
+                    Some(rule) => println!("{}:{}:{}: {}[{}]: {}", path, d.span.line, d.span.col, d.severity, rule, d.message),
+                    None => println!("{}:{}:{}: {}: {}", path, d.span.line, d.span.col, d.severity, d.message),
+                }
+                total += 1;
+            }
+            println!("{} errors, {} warnings", errors, total - errors);
+        }
+        ReportFormat::Json => {
+            let entries: Vec<JsonLint> = lints
+                .map(|(file, lint)| JsonLint {
+                    file,
+                    rule: lint.rule,
+                    severity: lint.diagnostic.severity,
+                    message: &lint.diagnostic.message,
+                    line: lint.diagnostic.span.line,
+                    column: lint.diagnostic.span.col,
+                    end_line: lint.diagnostic.span.end_line,
+                    end_column: lint.diagnostic.span.end_col,
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&entries).expect("lints are always serializable"));
+        }
+    }
+
+    if errors > 0 {
+        exit(1);
+    }
+}
+
+fn format_files(paths: &[String], check: bool) {
+    let mut unformatted = 0;
+    let mut failed = false;
+
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Error reading file '{}': {}", path, e);
+                failed = true;
+                continue;
+            }
+        };
+
+        let formatted = match Formatter::format(&source) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                eprintln!("Error formatting '{}': {}", path, e);
+                failed = true;
+                continue;
+            }
+        };
+
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            println!("Would reformat: {}", path);
+            unformatted += 1;
+        } else if let Err(e) = fs::write(path, formatted) {
+            eprintln!("Error writing file '{}': {}", path, e);
+            failed = true;
+        } else {
+            println!("Formatted: {}", path);
+        }
+    }
+
+    if check && unformatted > 0 {
+        eprintln!("{} of {} files would be reformatted", unformatted, paths.len());
+        exit(1);
+    }
+
+    if failed {
+        exit(1);
+    }
+}
+
+fn run_file(path: &str, options: &RunOptions) {
+    println!("-- Mu Interpreter --");
+    println!("Running file: {}", path);
+    
+    run_source(read_source(path), options);
+}
+
+fn run_source(source: String, options: &RunOptions) {
+    match Interpreter::execute_with_options(source, options) {
         Ok(_) => {
             println!("Execution completed successfully.");
         }
         Err(e) => {
+            // the script chose its exit code
+            if let Some(request) = e.downcast_ref::<Exit>() {
+                exit(request.code);
+            }
             eprintln!("Error executing script: {}", e);
             exit(1);
         }
     }
 }
 
+// History of the REPL entries, kept across sessions in the home directory
+fn history_path() -> Option<std::path::PathBuf> {
+    std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".mu_history"))
+}
+
 fn run_repl() {
-    use std::io::{stdin, stdout, Write};
+    use rustyline::Editor;
+    use rustyline::error::ReadlineError;
+    use rustyline::history::DefaultHistory;
 
     println!("-- Mu Interpreter REPL --");
-    println!("Type 'exit', 'quit', or 'q' to exit.");
+    println!("Type 'exit', 'quit', or 'q' to exit, ':help' for the meta-commands.");
+
+    let mut editor: Editor<ReplHelper, DefaultHistory> = match Editor::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("Error: Failed to start the line editor: {}", e);
+            exit(1);
+        }
+    };
+
+    editor.set_helper(Some(ReplHelper::new()));
+
+    let history = history_path();
+    if let Some(path) = &history {
+        // there is no history yet on the first run
+        let _ = editor.load_history(path);
+    }
+
+    // Ctrl-C while code runs stops it and keeps the session
+    if let Err(e) = interrupt::install_handler() {
+        eprintln!("Warning: {}", e);
+    }
+
+    let mut session = Session::new();
+    let mut entry = String::new();
+    let mut exit_code = None;
 
     loop {
-        print!("mu> ");
-        stdout().flush().unwrap();
+        let prompt = if entry.is_empty() { "mu> " } else { "... " };
 
-        let mut input = String::new();
-        stdin()
-            .read_line(&mut input)
-            .expect("Failed to read line");
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            // Ctrl-C drops the entry being typed
+            Err(ReadlineError::Interrupted) => {
+                entry.clear();
+                continue;
+            }
+            // Ctrl-D
+            Err(ReadlineError::Eof) => {
+                println!("Goodbye!");
+                break;
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                break;
+            }
+        };
 
-        let input = input.trim().to_string();
+        if !entry.is_empty() {
+            entry.push('\n');
+        }
+        entry.push_str(&line);
 
-        if input == "exit" || input == "quit" || input == "q" {
-            println!("Goodbye!");
-            break;
+        // keep reading while a bracket, string or comment is open
+        if !is_complete(&entry) {
+            continue;
         }
 
+        let input = std::mem::take(&mut entry);
+        let input = input.trim();
+
         if input.is_empty() {
             continue;
         }
+        let _ = editor.add_history_entry(input);
+
+        if input == "exit" || input == "quit" || input == "q" {
+            println!("Goodbye!");
+            break;
+        }
 
-        match Interpreter::execute_full_pipeline(input) {
+        interrupt::clear();
+        match session.execute(input) {
+            Ok(message) if !message.is_empty() => println!("{}", message),
             Ok(_) => {}
-            Err(e) => {
-                eprintln!("Error: {}", e);
-            }
+            Err(e) => match e.downcast_ref::<Exit>() {
+                Some(request) => {
+                    exit_code = Some(request.code);
+                    break;
+                }
+                None => eprintln!("Error: {}", e),
+            },
+        }
+
+        // complete what this entry defined
+        if let Some(helper) = editor.helper_mut() {
+            helper.set_names(session.names());
         }
     }
+
+    if let Some(path) = &history
+        && let Err(e) = editor.save_history(path)
+    {
+        eprintln!("Warning: Failed to save history to {}: {}", path.display(), e);
+    }
+
+    if let Some(code) = exit_code {
+        exit(code);
+    }
 }
@@ -2,19 +2,82 @@ pub mod tokenizer;
 pub mod parser;
 pub mod evaluator;
 pub mod ast;
+pub mod span;
+pub mod diagnostic;
+pub mod analyzer;
+pub mod typechecker;
+pub mod bytecode;
+pub mod compiler;
+pub mod vm;
+pub mod optimizer;
+pub mod formatter;
+pub mod linter;
+pub mod document;
+pub mod lsp;
+pub mod debugger;
+pub mod repl;
+pub mod interrupt;
+pub mod builtins;
+pub mod modules;
 
 use anyhow::{Error, Result, anyhow};
 use tokenizer::Tokenizer;
 use parser::Parser;
-use evaluator::Evaluator;
+use evaluator::{Evaluator, Exit, Value};
+use analyzer::{Analyzer, AnalysisErrors};
+use typechecker::TypeChecker;
+use compiler::Compiler;
+use vm::Vm;
+use optimizer::Optimizer;
+use modules::ModuleLoader;
+use std::path::PathBuf;
+
+// Knobs for a single run of the pipeline
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    // run the static analysis and type checking passes before evaluating
+    pub check: bool,
+    // compile to bytecode and run it on the VM instead of walking the AST
+    pub vm: bool,
+    // fold constants and remove unreachable statements before evaluating
+    pub optimize: bool,
+    // print the AST produced by the optimizer
+    pub dump_optimized: bool,
+    // the script arguments, readable as the `args` list
+    pub args: Vec<String>,
+    // the file being run, imports are resolved from its directory
+    // (the current directory when None)
+    pub path: Option<PathBuf>,
+    // more directories to look for imported modules in
+    pub search_path: Vec<PathBuf>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions { check: true, vm: false, optimize: true, dump_optimized: false, args: Vec::new(), path: None, search_path: Vec::new() }
+    }
+}
+
+// `exit(code)` is not a failure, it reaches the caller untouched
+// so the binaries can find the exit code
+pub(crate) fn evaluation_error(err: Error) -> Error {
+    if err.is::<Exit>() {
+        return err;
+    }
+    anyhow!("Failed to evaluate AST: {}", err)
+}
 
 pub struct Interpreter {
 }
 
 impl Interpreter{
     // Execute the full pipeline:
-    // source -> tokenization -> parsing -> evaluation
+    // source -> tokenization -> parsing -> analysis/type checking -> optimization -> evaluation
     pub fn execute_full_pipeline(source: String) -> Result<(), Error>{
+        Self::execute_with_options(source, &RunOptions::default())
+    }
+
+    pub fn execute_with_options(source: String, options: &RunOptions) -> Result<(), Error>{
         let tokens = Tokenizer::tokenize(source)
             .map_err(|e| anyhow!("Failed to tokenize input: {}", e))?;
 
@@ -25,14 +88,52 @@ impl Interpreter{
         let ast = parser.parse()
             .map_err(|e| anyhow!("Failed to parse tokens: {}", e))?;
 
-        println!("AST: {:#?}", ast);
+        let ast = ModuleLoader::new(options.search_path.clone()).link(ast, options.path.as_deref())?;
 
-        let evaluator = Evaluator::new();
-        evaluator.evaluate(ast)
-            .map_err(|e| anyhow!("Failed to evaluate AST: {}", e))?;
+        if options.check {
+            let mut diagnostics = Analyzer::analyze(&ast);
+            diagnostics.extend(TypeChecker::check(&ast));
+
+            let (errors, warnings): (Vec<_>, Vec<_>) = diagnostics
+                .into_iter()
+                .partition(|d| d.is_error());
+
+            for warning in &warnings {
+                eprintln!("warning: {}", warning);
+            }
+
+            if !errors.is_empty() {
+                return Err(anyhow!("Failed semantic analysis: {}", AnalysisErrors(errors)));
+            }
+        }
+
+        let ast = if options.optimize {
+            Optimizer::optimize(ast)
+        } else {
+            ast
+        };
+
+        if options.dump_optimized {
+            println!("Optimized AST: {:#?}", ast);
+        }
+
+        let args = Value::List(options.args.iter().cloned().map(Value::String).collect());
+
+        if options.vm {
+            let program = Compiler::compile(&ast)
+                .map_err(|e| anyhow!("Failed to compile AST: {}", e))?;
+
+            let mut vm = Vm::new();
+            vm.define("args", args);
+            vm.run(program).map_err(evaluation_error)?;
+        } else {
+            let mut evaluator = Evaluator::new();
+            evaluator.define("args", args);
+            evaluator.evaluate(ast).map_err(evaluation_error)?;
+        }
 
         println!("Execution completed successfully.");
 
         Ok(())
     }
-}
\ No newline at end of file
+}
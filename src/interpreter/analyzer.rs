@@ -0,0 +1,453 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::interpreter::ast::{AST, Statement, StatementKind, Expr, ExprKind, LambdaBody, MatchArm, PatternKind, Param};
+use crate::interpreter::builtins::{self, GLOBALS};
+use crate::interpreter::diagnostic::Diagnostic;
+use crate::interpreter::span::Span;
+
+// All the semantic errors found by the analyzer
+#[derive(Debug)]
+pub struct AnalysisErrors(pub Vec<Diagnostic>);
+
+impl Display for AnalysisErrors {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if let [single] = self.0.as_slice() {
+            return write!(f, "{}", single);
+        }
+
+        write!(f, "{} semantic errors:", self.0.len())?;
+        for diagnostic in &self.0 {
+            write!(f, "\n  {}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AnalysisErrors {}
+
+pub(crate) const NON_EXHAUSTIVE_MATCH: &str = "Non-exhaustive match: no arm matches every value, add a '_' arm";
+pub(crate) const UNREACHABLE_ARM: &str = "Unreachable match arm: an earlier arm matches every value";
+
+// A name introduced by `let`, `const`, a function parameter or a match pattern
+struct Binding {
+    name: String,
+    span: Span,
+    is_param: bool,
+    constant: bool,
+    used: bool,
+}
+
+// Static checks that run between parsing and evaluation:
+// undefined variables, unknown functions, wrong argument counts,
+// assignments to undeclared variables or to constants, redeclared constants,
+// duplicate parameters and unused variables.
+//
+// Scoping follows the evaluator: top level `let`s are globals,
+// function bodies see their parameters, their own `let`s and the globals.
+pub struct Analyzer {
+    // function name -> number of parameters, collected before the walk
+    functions: HashMap<String, usize>,
+    // every top level `let` name, functions may use globals declared after them
+    globals: HashSet<String>,
+    // the top level `const` names
+    global_constants: HashSet<String>,
+    // functions defined so far by top level statements
+    defined_functions: HashSet<String>,
+    // aliases of the imported modules, their functions are only known once linked
+    modules: HashSet<String>,
+    scopes: Vec<Vec<Binding>>,
+    in_function: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Analyzer {
+    pub fn analyze(ast: &AST) -> Vec<Diagnostic> {
+        let mut analyzer = Analyzer {
+            functions: HashMap::new(),
+            globals: HashSet::new(),
+            global_constants: HashSet::new(),
+            defined_functions: HashSet::new(),
+            modules: HashSet::new(),
+            scopes: vec![Vec::new()],
+            in_function: false,
+            diagnostics: Vec::new(),
+        };
+
+        for name in GLOBALS {
+            analyzer.globals.insert(name.to_string());
+            analyzer.declare(name, Span::default(), false, false);
+        }
+        analyzer.collect_declarations(&ast.statements, true);
+
+        for statement in &ast.statements {
+            analyzer.check_statement(statement);
+        }
+
+        analyzer.diagnostics
+    }
+
+    // First pass: functions can be called from bodies defined before them
+    fn collect_declarations(&mut self, statements: &[Statement], top_level: bool) {
+        for statement in statements {
+            match &statement.kind {
+                StatementKind::FuncDef { name, params, body, .. } => {
+                    self.functions.insert(name.clone(), params.len());
+                    self.collect_declarations(body, false);
+                }
+                StatementKind::Let { name, constant, .. } if top_level => {
+                    self.globals.insert(name.clone());
+                    if *constant {
+                        self.global_constants.insert(name.clone());
+                    }
+                }
+                StatementKind::Try { body, catch, finally } => {
+                    self.collect_declarations(&body.statements, top_level);
+                    if let Some(catch) = catch {
+                        if top_level {
+                            self.globals.insert(catch.name.clone());
+                        }
+                        self.collect_declarations(&catch.body.statements, top_level);
+                    }
+                    if let Some(finally) = finally {
+                        self.collect_declarations(&finally.statements, top_level);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_statement(&mut self, stmt: &Statement) {
+        match &stmt.kind {
+            StatementKind::Let { name, value, constant, .. } => {
+                self.check_expr(value);
+                self.check_redeclared(name, stmt.span);
+                self.declare(name, stmt.span, false, *constant);
+            }
+            // a catch variable is bound like a `let`
+            StatementKind::Try { body, catch, finally } => {
+                for stmt in &body.statements {
+                    self.check_statement(stmt);
+                }
+                if let Some(catch) = catch {
+                    self.check_redeclared(&catch.name, catch.span);
+                    self.declare(&catch.name, catch.span, false, false);
+                    for stmt in &catch.body.statements {
+                        self.check_statement(stmt);
+                    }
+                }
+                if let Some(finally) = finally {
+                    for stmt in &finally.statements {
+                        self.check_statement(stmt);
+                    }
+                }
+            }
+            StatementKind::Throw { value } => self.check_expr(value),
+            StatementKind::Assign { target, value, .. } => {
+                self.check_assign_target(target);
+                self.check_expr(value);
+            }
+            StatementKind::FuncDef { name, params, body, .. } => {
+                self.check_function(name, params, body);
+            }
+            StatementKind::Expr(expr) => self.check_expr(expr),
+            StatementKind::Where { condition } => self.check_filter(condition),
+            StatementKind::Get { field, filter } => {
+                self.check_expr(field);
+                if let Some(filter) = filter {
+                    self.check_filter(filter);
+                }
+            }
+            StatementKind::Set { field, value, .. } => {
+                self.check_expr(field);
+                self.check_expr(value);
+            }
+            StatementKind::Delete { field, .. } => self.check_expr(field),
+            StatementKind::Import { alias, .. } => {
+                self.modules.insert(alias.clone());
+            }
+        }
+    }
+
+    fn check_function(&mut self, name: &str, params: &[Param], body: &[Statement]) {
+        self.check_params(name, params);
+
+        // a function body does not see the locals of the code that defines it
+        let saved_scopes = std::mem::replace(&mut self.scopes, vec![Vec::new()]);
+        let was_in_function = self.in_function;
+        self.in_function = true;
+
+        for param in params {
+            self.declare(&param.name, param.span, true, false);
+        }
+        for stmt in body {
+            self.check_statement(stmt);
+        }
+
+        let scope = self.scopes.pop().unwrap_or_default();
+        self.report_unused(scope);
+
+        self.scopes = saved_scopes;
+        self.in_function = was_in_function;
+
+        if !self.in_function {
+            self.defined_functions.insert(name.to_string());
+        }
+    }
+
+    // A lambda body sees its parameters on top of the bindings around it,
+    // the ones it uses are captured when it is created
+    fn check_lambda(&mut self, params: &[Param], body: &LambdaBody, require_defined: bool) {
+        self.check_params("fn", params);
+
+        self.scopes.push(Vec::new());
+        let was_in_function = self.in_function;
+        self.in_function = true;
+
+        for param in params {
+            self.declare(&param.name, param.span, true, false);
+        }
+        match body {
+            LambdaBody::Expr(value) => self.walk_expr(value, require_defined),
+            LambdaBody::Block(block) => {
+                for stmt in &block.statements {
+                    self.check_statement(stmt);
+                }
+            }
+        }
+
+        let scope = self.scopes.pop().unwrap_or_default();
+        self.report_unused(scope);
+        self.in_function = was_in_function;
+    }
+
+    fn check_params(&mut self, name: &str, params: &[Param]) {
+        let mut seen = HashSet::new();
+        for param in params {
+            if !seen.insert(&param.name) {
+                self.diagnostics.push(Diagnostic::error(
+                    format!("Duplicate parameter '{}' in function '{}'", param.name, name),
+                    param.span,
+                ));
+            }
+        }
+    }
+
+    // Conditions of `where`/`get` refer to record fields, so unknown names are fine
+    fn check_filter(&mut self, condition: &Expr) {
+        self.walk_expr(condition, false);
+    }
+
+    fn check_expr(&mut self, expr: &Expr) {
+        self.walk_expr(expr, true);
+    }
+
+    fn walk_expr(&mut self, expr: &Expr, require_defined: bool) {
+        match &expr.kind {
+            ExprKind::Number(_) | ExprKind::String(_) | ExprKind::Boolean(_) | ExprKind::Null => {}
+            // a function name is a function value
+            ExprKind::Identifier(name) => {
+                let is_function = self.functions.contains_key(name) || builtins::lookup(name).is_some();
+                if !self.resolve(name) && !is_function && require_defined {
+                    self.diagnostics.push(Diagnostic::error(format!("Undefined variable: {}", name), expr.span));
+                }
+            }
+            ExprKind::Binary { left, right, .. } => {
+                self.walk_expr(left, require_defined);
+                self.walk_expr(right, require_defined);
+            }
+            ExprKind::Unary { operand, .. } => self.walk_expr(operand, require_defined),
+            // `util.greeting` reads a variable of an imported module
+            ExprKind::FieldAccess { object, .. } if matches!(&object.kind, ExprKind::Identifier(name) if self.modules.contains(name)) => {}
+            ExprKind::FieldAccess { object, .. } => self.walk_expr(object, require_defined),
+            ExprKind::Call { name, args } => {
+                // a variable holding a function is only checked when called
+                if !self.resolve(name) {
+                    self.check_call(name, args.len(), expr.span);
+                }
+                for arg in args {
+                    self.walk_expr(arg, require_defined);
+                }
+            }
+            ExprKind::Apply { callee, args } => {
+                self.walk_expr(callee, require_defined);
+                for arg in args {
+                    self.walk_expr(arg, require_defined);
+                }
+            }
+            ExprKind::Lambda { params, body, .. } => self.check_lambda(params, body, require_defined),
+            ExprKind::List(items) => {
+                for item in items {
+                    self.walk_expr(item, require_defined);
+                }
+            }
+            ExprKind::Map(fields) => {
+                for (_, value) in fields {
+                    self.walk_expr(value, require_defined);
+                }
+            }
+            ExprKind::Index { object, index } => {
+                self.walk_expr(object, require_defined);
+                self.walk_expr(index, require_defined);
+            }
+            ExprKind::Match { value, arms } => {
+                self.walk_expr(value, require_defined);
+
+                // the names a pattern binds only live in its arm
+                for arm in arms {
+                    self.scopes.push(Vec::new());
+                    for (name, span) in arm.pattern.bindings() {
+                        self.declare(name, span, false, false);
+                    }
+                    if let Some(guard) = &arm.guard {
+                        self.walk_expr(guard, require_defined);
+                    }
+                    self.walk_expr(&arm.body, require_defined);
+                    let scope = self.scopes.pop().unwrap_or_default();
+                    self.report_unused(scope);
+                }
+
+                for arm in Self::unreachable_arms(arms) {
+                    self.diagnostics.push(Diagnostic::warning(UNREACHABLE_ARM, arm.pattern.span));
+                }
+                if !Self::is_exhaustive(arms) {
+                    self.diagnostics.push(Diagnostic::warning(NON_EXHAUSTIVE_MATCH, expr.span));
+                }
+            }
+        }
+    }
+
+    // Arms after one that matches every value never run
+    pub(crate) fn unreachable_arms(arms: &[MatchArm]) -> &[MatchArm] {
+        match arms.iter().position(|arm| arm.guard.is_none() && arm.pattern.is_irrefutable()) {
+            Some(i) => &arms[i + 1..],
+            None => &[],
+        }
+    }
+
+    // Every value is matched by an arm without a guard: `_`, a binding, or both
+    // `true` and `false`. Lists and maps have unbounded shapes, only a catch-all covers them
+    pub(crate) fn is_exhaustive(arms: &[MatchArm]) -> bool {
+        let unguarded = || arms.iter().filter(|arm| arm.guard.is_none()).map(|arm| &arm.pattern);
+        unguarded().any(|pattern| pattern.is_irrefutable())
+            || (unguarded().any(|pattern| matches!(pattern.kind, PatternKind::Boolean(true)))
+                && unguarded().any(|pattern| matches!(pattern.kind, PatternKind::Boolean(false))))
+    }
+
+    // The variable at the root of the target must exist, the indexes are plain expressions
+    fn check_assign_target(&mut self, target: &Expr) {
+        match &target.kind {
+            ExprKind::Identifier(name) => {
+                if self.is_constant(name) {
+                    self.diagnostics.push(Diagnostic::error(format!("Cannot assign to constant: {}", name), target.span));
+                } else if !self.resolve(name) {
+                    self.diagnostics.push(Diagnostic::error(format!("Assignment to undeclared variable: {}", name), target.span));
+                }
+            }
+            ExprKind::Index { object, index } => {
+                self.check_assign_target(object);
+                self.check_expr(index);
+            }
+            // a variable of an imported module
+            ExprKind::FieldAccess { object, .. } if matches!(&object.kind, ExprKind::Identifier(name) if self.modules.contains(name)) => {}
+            ExprKind::FieldAccess { object, .. } => self.check_assign_target(object),
+            _ => self.check_expr(target),
+        }
+    }
+
+    fn check_call(&mut self, name: &str, arg_count: usize, span: Span) {
+        if let Some((module, _)) = name.split_once('.')
+            && self.modules.contains(module)
+        {
+            return;
+        }
+
+        match self.functions.get(name) {
+            None => match builtins::lookup(name) {
+                Some(builtin) if builtin.params.len() != arg_count => {
+                    self.diagnostics.push(Diagnostic::error(
+                        format!("Function '{}' expects {} arguments, got {}", name, builtin.params.len(), arg_count),
+                        span,
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    self.diagnostics.push(Diagnostic::error(format!("Undefined function: {}", name), span));
+                }
+            },
+            Some(&param_count) => {
+                if param_count != arg_count {
+                    self.diagnostics.push(Diagnostic::error(
+                        format!("Function '{}' expects {} arguments, got {}", name, param_count, arg_count),
+                        span,
+                    ));
+                }
+
+                // top level code runs in order, the definition has to come first
+                if !self.in_function && !self.defined_functions.contains(name) {
+                    self.diagnostics.push(Diagnostic::error(
+                        format!("Function '{}' is called before its definition", name),
+                        span,
+                    ));
+                }
+            }
+        }
+    }
+
+    // A constant cannot be declared again in its own scope
+    fn check_redeclared(&mut self, name: &str, span: Span) {
+        let redeclared = self.scopes
+            .last()
+            .and_then(|scope| scope.iter().rev().find(|b| b.name == name))
+            .is_some_and(|binding| binding.constant);
+        if redeclared {
+            self.diagnostics.push(Diagnostic::error(format!("Cannot redeclare constant: {}", name), span));
+        }
+    }
+
+    fn declare(&mut self, name: &str, span: Span, is_param: bool, constant: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Binding { name: name.to_string(), span, is_param, constant, used: false });
+        }
+    }
+
+    // Whether the closest binding of `name` is a constant
+    fn is_constant(&self, name: &str) -> bool {
+        for scope in self.scopes.iter().rev() {
+            if let Some(binding) = scope.iter().rev().find(|b| b.name == name) {
+                return binding.constant;
+            }
+        }
+
+        self.in_function && self.global_constants.contains(name)
+    }
+
+    // Mark the closest binding of `name` as used, returns false if there is none
+    fn resolve(&mut self, name: &str) -> bool {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(binding) = scope.iter_mut().rev().find(|b| b.name == name) {
+                binding.used = true;
+                return true;
+            }
+        }
+
+        self.in_function && self.globals.contains(name)
+    }
+
+    // Only function locals and match arm bindings are reported, top level
+    // bindings are globals that any function (or a later REPL line) may read
+    fn report_unused(&mut self, scope: Vec<Binding>) {
+        for binding in scope {
+            if binding.used || binding.name.starts_with('_') {
+                continue;
+            }
+
+            let what = if binding.is_param { "parameter" } else { "variable" };
+            self.diagnostics.push(Diagnostic::warning(
+                format!("Unused {}: {}", what, binding.name),
+                binding.span,
+            ));
+        }
+    }
+}
@@ -1,11 +1,91 @@
-#[derive(Debug, Clone)]
+use serde::Serialize;
+
+use crate::interpreter::span::Span;
+use crate::interpreter::tokenizer::NumberLiteral;
+
+#[derive(Debug, Clone, Serialize)]
 pub struct AST {
     pub statements: Vec<Statement>
 }
 
 //expr returns a value
-#[derive(Debug, Clone)]
-pub enum Expr {
+#[derive(Debug, Clone, Serialize)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Expr { kind, span }
+    }
+
+    // This expression, then every expression inside it, nested lambda bodies included
+    pub fn visit<'a>(&'a self, visit: &mut impl FnMut(&'a Expr)) {
+        visit(self);
+        match &self.kind {
+            ExprKind::Number(_) | ExprKind::String(_) | ExprKind::Boolean(_) | ExprKind::Null | ExprKind::Identifier(_) => {}
+            ExprKind::Binary { left, right, .. } => {
+                left.visit(visit);
+                right.visit(visit);
+            }
+            ExprKind::Unary { operand, .. } => operand.visit(visit),
+            ExprKind::FieldAccess { object, .. } => object.visit(visit),
+            ExprKind::Call { args, .. } | ExprKind::List(args) => args.iter().for_each(|arg| arg.visit(visit)),
+            ExprKind::Apply { callee, args } => {
+                callee.visit(visit);
+                args.iter().for_each(|arg| arg.visit(visit));
+            }
+            ExprKind::Lambda { body, .. } => match body {
+                LambdaBody::Expr(expr) => expr.visit(visit),
+                LambdaBody::Block(block) => block.statements.iter().for_each(|stmt| stmt.visit(visit)),
+            },
+            ExprKind::Map(fields) => fields.iter().for_each(|(_, value)| value.visit(visit)),
+            ExprKind::Index { object, index } => {
+                object.visit(visit);
+                index.visit(visit);
+            }
+            ExprKind::Match { value, arms } => {
+                value.visit(visit);
+                for arm in arms {
+                    if let Some(guard) = &arm.guard {
+                        guard.visit(visit);
+                    }
+                    arm.body.visit(visit);
+                }
+            }
+        }
+    }
+
+    // Every name the expression reads, assigns or calls, nested lambdas included
+    pub fn visit_names<'a>(&'a self, visit: &mut impl FnMut(&'a str)) {
+        self.visit(&mut |expr| match &expr.kind {
+            ExprKind::Identifier(name) | ExprKind::Call { name, .. } => visit(name),
+            _ => {}
+        });
+    }
+
+    // Names used by the body of a lambda that are not its parameters, in order of first use.
+    // The ones that are locals of the enclosing call are captured when the lambda is created
+    pub fn free_names<'a>(params: &[Param], body: &'a LambdaBody) -> Vec<&'a str> {
+        let mut names = Vec::new();
+        let mut visit = |name: &'a str| {
+            if !names.contains(&name) && !params.iter().any(|param| param.name == name) {
+                names.push(name);
+            }
+        };
+        match body {
+            LambdaBody::Expr(expr) => expr.visit_names(&mut visit),
+            LambdaBody::Block(block) => block.statements.iter().for_each(|stmt| stmt.visit_names(&mut visit)),
+        }
+        names
+    }
+}
+
+// Serialized like TokenKind: {"type": "Binary", "value": {...}}
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "value")]
+pub enum ExprKind {
     Binary {
         left: Box<Expr>,
         op: BinaryOp,
@@ -15,45 +95,282 @@ pub enum Expr {
         op: UnaryOp,
         operand: Box<Expr>,
     },
-    Number(f64),
+    Number(NumberLiteral),
     String(String),
     Boolean(bool),
+    Null,
     Identifier(String),
+    // `object.field`, or `object?.field` when optional: null if the object is null
     FieldAccess {
         field: String,
         object: Box<Expr>,
+        optional: bool,
     },
-    // Function call or command
+    // Function call or command, functions of an imported module are
+    // called by their qualified name: `util.greet`.
+    // A variable holding a function is called by its name too
     Call {
         name: String,
         args: Vec<Expr>
     },
+    // Call of any other expression: `make_adder(1)(2)`, `(fn(x) => x)(1)`
+    Apply {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    // fn(x) => x * 2, fn(x) { ... }
+    Lambda {
+        params: Vec<Param>,
+        return_type: Option<TypeAnnotation>,
+        body: LambdaBody,
+    },
+    // [a, b, c]
+    List(Vec<Expr>),
+    // {name: "Ada", "full name": x}
+    Map(Vec<(String, Expr)>),
+    // items[i]
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
+    },
+    // match value { 0 => "zero", [first, ..rest] => first, _ => "other" }
+    Match {
+        value: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+}
+
+// `=> expr` evaluates to the expression, a block runs like a function body
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "value")]
+pub enum LambdaBody {
+    Expr(Box<Expr>),
+    Block(Block),
+}
+
+// `pattern if guard => body`, the names the pattern binds only live in the arm
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "value")]
+pub enum PatternKind {
+    // `_`, matches anything
+    Wildcard,
+    // `x`, matches anything and binds it
+    Binding(String),
+    Number(NumberLiteral),
+    String(String),
+    Boolean(bool),
+    Null,
+    // `[a, b, ..rest]`, rest is a Binding or a Wildcard (`..`) and takes the remaining items
+    List { items: Vec<Pattern>, rest: Option<Box<Pattern>> },
+    // `{ name, age: 42 }`, the map needs every field, `name` alone binds the field
+    Map(Vec<(String, Pattern)>),
 }
 
-#[derive(Debug, Clone)]
+impl Pattern {
+    // Names bound by the pattern and their spans, in the order they are matched
+    pub fn bindings(&self) -> Vec<(&str, Span)> {
+        let mut names = Vec::new();
+        self.collect_bindings(&mut names);
+        names
+    }
+
+    fn collect_bindings<'a>(&'a self, names: &mut Vec<(&'a str, Span)>) {
+        match &self.kind {
+            PatternKind::Binding(name) => names.push((name, self.span)),
+            PatternKind::List { items, rest } => {
+                for item in items {
+                    item.collect_bindings(names);
+                }
+                if let Some(rest) = rest {
+                    rest.collect_bindings(names);
+                }
+            },
+            PatternKind::Map(fields) => {
+                for (_, pattern) in fields {
+                    pattern.collect_bindings(names);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // Matches every value: `_` or a binding
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(self.kind, PatternKind::Wildcard | PatternKind::Binding(_))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub enum UnaryOp {
     Neg,
     Not,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub enum BinaryOp {
     Add, Sub, Mul, Div,
     Eq, Neq, Gt, Lt, Gte, Lte,
     And, Or,
+    // `a ?? b`, b is only evaluated when a is null
+    Coalesce,
+}
+
+// Types that can be written in annotations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Type {
+    Number,
+    String,
+    Bool,
+    List,
+    Map,
+    Unit,
+}
+
+impl Type {
+    pub fn from_name(name: &str) -> Option<Type> {
+        match name {
+            "number" => Some(Type::Number),
+            "string" => Some(Type::String),
+            "bool" => Some(Type::Bool),
+            "list" => Some(Type::List),
+            "map" => Some(Type::Map),
+            "unit" => Some(Type::Unit),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Type::Number => write!(f, "number"),
+            Type::String => write!(f, "string"),
+            Type::Bool => write!(f, "bool"),
+            Type::List => write!(f, "list"),
+            Type::Map => write!(f, "map"),
+            Type::Unit => write!(f, "unit"),
+        }
+    }
+}
+
+// `: number` after a name, or `-> number` after a parameter list
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeAnnotation {
+    pub ty: Type,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Param {
+    pub name: String,
+    pub ty: Option<TypeAnnotation>,
+    pub span: Span,
+}
+
+// `{ ... }`, the span includes the braces
+#[derive(Debug, Clone, Serialize)]
+pub struct Block {
+    pub statements: Vec<Statement>,
+    pub span: Span,
+}
+
+// `catch (e) { ... }`, the span is the one of the name
+#[derive(Debug, Clone, Serialize)]
+pub struct Catch {
+    pub name: String,
+    pub span: Span,
+    pub body: Block,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Statement { kind, span }
+    }
+
+    // Like Expr::visit, for the expressions of the statement and of its blocks
+    pub fn visit<'a>(&'a self, visit: &mut impl FnMut(&'a Expr)) {
+        match &self.kind {
+            StatementKind::Get { field, filter } => {
+                field.visit(visit);
+                if let Some(filter) = filter {
+                    filter.visit(visit);
+                }
+            }
+            StatementKind::Set { field, value, .. } => {
+                field.visit(visit);
+                value.visit(visit);
+            }
+            StatementKind::Delete { field, .. } => field.visit(visit),
+            StatementKind::Where { condition } => condition.visit(visit),
+            StatementKind::Let { value, .. } | StatementKind::Throw { value } => value.visit(visit),
+            StatementKind::Assign { target, value, .. } => {
+                target.visit(visit);
+                value.visit(visit);
+            }
+            StatementKind::Import { .. } => {}
+            StatementKind::Try { body, catch, finally } => {
+                let catch_body = catch.as_ref().map(|catch| &catch.body);
+                for block in [Some(body), catch_body, finally.as_ref()].into_iter().flatten() {
+                    block.statements.iter().for_each(|stmt| stmt.visit(visit));
+                }
+            }
+            StatementKind::FuncDef { body, .. } => body.iter().for_each(|stmt| stmt.visit(visit)),
+            StatementKind::Expr(expr) => expr.visit(visit),
+        }
+    }
+
+    // Like Expr::visit_names, for the expressions of the statement and of its blocks
+    pub fn visit_names<'a>(&'a self, visit: &mut impl FnMut(&'a str)) {
+        self.visit(&mut |expr| match &expr.kind {
+            ExprKind::Identifier(name) | ExprKind::Call { name, .. } => visit(name),
+            _ => {}
+        });
+    }
 }
 
-#[derive(Debug, Clone)]
-pub enum Statement {
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "value")]
+pub enum StatementKind {
     Get { field: Expr, filter: Option<Expr> },
     Set { field: Expr, key: String, value: Expr },
     Delete { field: Expr, key: String },
     Where { condition: Expr },
-    Let { name: String, value: Expr },
+    // `const` when constant, the binding cannot be assigned or declared again in its scope
+    Let { name: String, ty: Option<TypeAnnotation>, value: Expr, constant: bool },
+    // `x = 1;`, `items[0] += 1;`, `user.age = 4;`, op is set for compound assignments
+    Assign { target: Expr, op: Option<BinaryOp>, value: Expr },
+    // import "lib/util.mu" as util;
+    Import { path: String, alias: String },
+    // try { ... } catch (e) { ... } finally { ... }, at least one of catch and finally
+    Try { body: Block, catch: Option<Catch>, finally: Option<Block> },
+    // throw value;
+    Throw { value: Expr },
     FuncDef {
         name: String,
-        params: Vec<String>,
+        params: Vec<Param>,
+        return_type: Option<TypeAnnotation>,
         body: Vec<Statement>,
     },
     Expr(Expr),
-}
\ No newline at end of file
+}
@@ -0,0 +1,85 @@
+use anyhow::{Result, anyhow};
+
+use crate::interpreter::ast::Type;
+use crate::interpreter::evaluator::{Callable, ErrorKind, Exit, MuError, Value};
+
+// Names every program can read without defining them.
+// `args` holds the script arguments, `mu run script.mu -- a b`
+pub const GLOBALS: &[&str] = &["args"];
+
+// A function provided by the interpreter.
+// A Mu function with the same name takes precedence
+#[derive(Debug)]
+pub struct Builtin {
+    pub name: &'static str,
+    // parameter names and types, None when several types are accepted
+    pub params: &'static [(&'static str, Option<Type>)],
+    pub ret: Option<Type>,
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin { name: "len", params: &[("value", None)], ret: Some(Type::Number) },
+    Builtin { name: "exit", params: &[("code", Some(Type::Number))], ret: Some(Type::Unit) },
+    Builtin { name: "map", params: &[("items", Some(Type::List)), ("f", None)], ret: Some(Type::List) },
+    Builtin { name: "filter", params: &[("items", Some(Type::List)), ("keep", None)], ret: Some(Type::List) },
+];
+
+impl Builtin {
+    // `len(value) -> number`, as shown by completions
+    pub fn signature(&self) -> String {
+        let params: Vec<String> = self.params
+            .iter()
+            .map(|(name, ty)| match ty {
+                Some(ty) => format!("{}: {}", name, ty),
+                None => name.to_string(),
+            })
+            .collect();
+
+        match self.ret {
+            Some(ret) => format!("{}({}) -> {}", self.name, params.join(", "), ret),
+            None => format!("{}({})", self.name, params.join(", ")),
+        }
+    }
+
+    // The argument count has already been checked by the caller.
+    // `apply` runs a Mu function, for the builtins that take one
+    pub fn call(&self, args: Vec<Value>, apply: &mut dyn FnMut(&Callable, Vec<Value>) -> Result<Value>) -> Result<Value> {
+        match (self.name, args.as_slice()) {
+            ("len", [Value::List(items)]) => Ok(Value::Number(items.len() as f64)),
+            ("len", [Value::String(s)]) => Ok(Value::Number(s.chars().count() as f64)),
+            ("len", [Value::Map(fields)]) => Ok(Value::Number(fields.len() as f64)),
+            ("len", [other]) => Err(anyhow!("len() expects a list, a string or a map, got {:?}", other)),
+            // a process exit status is a single byte
+            ("exit", [Value::Number(code)]) if code.fract() == 0.0 && (0.0..=255.0).contains(code) => {
+                Err(anyhow::Error::new(Exit { code: *code as i32 }))
+            }
+            ("exit", [other]) => Err(anyhow!("exit() expects an integer code from 0 to 255, got {:?}", other)),
+            ("map", [Value::List(items), Value::Function(f)]) => {
+                let mut mapped = Vec::with_capacity(items.len());
+                for item in items {
+                    mapped.push(apply(f, vec![item.clone()])?);
+                }
+                Ok(Value::List(mapped))
+            }
+            ("filter", [Value::List(items), Value::Function(keep)]) => {
+                let mut kept = Vec::new();
+                for item in items {
+                    match apply(keep, vec![item.clone()])? {
+                        Value::Boolean(true) => kept.push(item.clone()),
+                        Value::Boolean(false) => {}
+                        other => return Err(anyhow!("filter() expects the function to return a bool, got {:?}", other)),
+                    }
+                }
+                Ok(Value::List(kept))
+            }
+            ("map" | "filter", [items, f]) => {
+                Err(anyhow!("{}() expects a list and a function, got {:?} and {:?}", self.name, items, f))
+            }
+            _ => Err(MuError::new(ErrorKind::TypeError, format!("Invalid call to builtin {}", self.name)).into()),
+        }
+    }
+}
+
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use crate::interpreter::ast::{BinaryOp, Pattern, UnaryOp};
+use crate::interpreter::evaluator::Value;
+use crate::interpreter::span::Span;
+
+// Instructions of the stack VM.
+// Names are indexes into Program::names, shared by every chunk,
+// so globals and functions can be stored in plain vectors.
+#[derive(Debug, Clone, Copy)]
+pub enum Instr {
+    // push chunk.constants[i]
+    Constant(u32),
+    Unit,
+    Pop,
+    // function locals, resolved at compile time to a slot of the frame
+    LoadLocal(u32),
+    // store the top of the stack without popping it (a let is also a value),
+    // fails if the slot already holds a constant
+    StoreLocal { slot: u32, constant: bool },
+    // anything that is not a local: caller locals or globals, looked up at runtime
+    LoadName(u32),
+    // top level let or const, keeps the value on the stack
+    DefineGlobal { name: u32, constant: bool },
+    // pop the top `n` values into a list
+    MakeList(u32),
+    // pop `n` (field name, value) pairs into a map
+    MakeMap(u32),
+    // pop the index and the object, push the item
+    Index,
+    // pop the object, push its field named Program::names[name],
+    // an optional access on null pushes null
+    Field { name: u32, optional: bool },
+    // pop the value and the `keys` indexes under it, store the value at those
+    // indexes of the variable named Program::names[name], in the innermost frame
+    // that has it initialized or else in the globals. A compound assignment
+    // combines it with the current item first. Pushes the stored value
+    Assign { name: u32, keys: u32, op: Option<BinaryOp> },
+    Binary(BinaryOp),
+    Unary(UnaryOp),
+    Jump(u32),
+    // jump to code[target] if the top of the stack is not null, keeps it on the stack.
+    // The left side of `??`, the right side only runs when it is null
+    JumpIfNotNull(u32),
+    // start a `try`: an error raised before the matching PopHandler unwinds the
+    // frames and the stack to where they are now and jumps to code[target].
+    // A catch handler pushes the error value, a finally handler keeps the error
+    // for the Rethrow at the end of the finally block
+    PushHandler { target: u32, finally: bool },
+    PopHandler,
+    // pop the value and raise it, see Evaluator::throw
+    Throw,
+    // raise again the error kept by the last finally handler
+    Rethrow,
+    // test chunk.patterns[pattern] against the top of the stack, keeping it there.
+    // When it matches bind its names until the next EndArm, else jump to code[next]
+    MatchPattern { pattern: u32, next: u32 },
+    // pop the guard of a match arm, jump to code[fail] when it is false
+    MatchGuard(u32),
+    // restore what the names bound by the last MatchPattern were before it
+    EndArm,
+    // pop the top value and the one under it, push back the top one
+    PopUnder,
+    // pop the value no arm matched and raise the error
+    NoMatch,
+    // print the value of an expression statement, keeps it on the stack
+    PrintValue,
+    // print "Let <name> = <value>" or "Const <name> = <value>", keeps the value on the stack
+    PrintLet { name: u32, constant: bool },
+    // print "<target> = <value>" with the target text in chunk.constants[i],
+    // keeps the value on the stack
+    PrintAssign(u32),
+    // register Program::functions[i] under its name, pushes unit
+    DefineFunction(u32),
+    // resolve the function (or else the builtin) by name and check the argument count
+    // before the arguments are evaluated, like the tree-walker does
+    PrepareCall { name: u32, argc: u32 },
+    // pop the function to call, check it is one and takes `argc` arguments
+    PrepareApply(u32),
+    // call the prepared function with the top `argc` values
+    Call(u32),
+    // push the lambda Program::functions[i], with the values of the slots it captures
+    MakeClosure(u32),
+    Return,
+    // statements the evaluator does not support yet,
+    // prints the message in chunk.constants[i] and pushes unit
+    NotImplemented(u32),
+}
+
+// A sequence of instructions with its constant pool.
+// spans[i] is the source range instruction i was compiled from
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Instr>,
+    pub spans: Vec<Span>,
+    pub constants: Vec<Value>,
+    pub patterns: Vec<MatchPattern>,
+}
+
+impl Chunk {
+    pub fn emit(&mut self, instr: Instr, span: Span) -> usize {
+        self.code.push(instr);
+        self.spans.push(span);
+        self.code.len() - 1
+    }
+
+    pub fn add_constant(&mut self, value: Value) -> u32 {
+        self.constants.push(value);
+        (self.constants.len() - 1) as u32
+    }
+}
+
+// Where a name bound by a pattern goes, resolved like the target of a `let`
+#[derive(Debug, Clone, Copy)]
+pub enum Binding {
+    Local(u32),
+    Global(u32),
+}
+
+// Pattern of a match arm, with one binding per name in Pattern::bindings order
+#[derive(Debug)]
+pub struct MatchPattern {
+    pub pattern: Pattern,
+    pub bindings: Vec<Binding>,
+}
+
+#[derive(Debug)]
+pub struct Function {
+    pub name: u32,
+    pub arity: usize,
+    // name of each local slot, parameters come first, then the captured locals of a lambda
+    pub locals: Vec<u32>,
+    // slots of the enclosing function a lambda copies when it is created
+    pub captures: Vec<u32>,
+    pub chunk: Chunk,
+}
+
+#[derive(Debug)]
+pub struct Program {
+    // top level statements
+    pub main: Arc<Function>,
+    pub functions: Vec<Arc<Function>>,
+    pub names: Vec<String>,
+}
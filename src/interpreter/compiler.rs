@@ -0,0 +1,449 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+
+use crate::interpreter::ast::{AST, Statement, StatementKind, Expr, ExprKind, BinaryOp, LambdaBody, Param};
+use crate::interpreter::bytecode::{Binding, Chunk, Function, Instr, MatchPattern, Program};
+use crate::interpreter::evaluator::Value;
+use crate::interpreter::formatter::Formatter;
+use crate::interpreter::span::Span;
+
+// State of the function being compiled
+#[derive(Default)]
+struct FunctionState {
+    // name id of each slot
+    locals: Vec<u32>,
+    // slots of match arm bindings whose arm is over, names after the arm do not resolve to them
+    ended: Vec<u32>,
+    chunk: Chunk,
+}
+
+impl FunctionState {
+    fn resolve(&self, name: u32) -> Option<u32> {
+        self.locals
+            .iter()
+            .enumerate()
+            .rposition(|(slot, &n)| n == name && !self.ended.contains(&(slot as u32)))
+            .map(|slot| slot as u32)
+    }
+
+    // a second `let` of the same name reuses the slot
+    fn declare(&mut self, name: u32) -> u32 {
+        match self.resolve(name) {
+            Some(slot) => slot,
+            None => {
+                self.locals.push(name);
+                (self.locals.len() - 1) as u32
+            }
+        }
+    }
+}
+
+// Compiles the AST into bytecode for the VM.
+// Top level `let`s become globals, names inside function bodies
+// are resolved to local slots when they are declared before the use.
+pub struct Compiler {
+    names: Vec<String>,
+    name_ids: HashMap<String, u32>,
+    functions: Vec<Arc<Function>>,
+    // innermost function last, the first one is the top level code
+    states: Vec<FunctionState>,
+}
+
+impl Compiler {
+    pub fn compile(ast: &AST) -> Result<Program> {
+        let mut compiler = Compiler {
+            names: Vec::new(),
+            name_ids: HashMap::new(),
+            functions: Vec::new(),
+            states: vec![FunctionState::default()],
+        };
+
+        for statement in &ast.statements {
+            compiler.compile_statement(statement)?;
+            compiler.emit(Instr::Pop, statement.span);
+        }
+
+        let main_state = compiler.states.pop().unwrap_or_default();
+        let main = Function {
+            name: compiler.intern("<main>"),
+            arity: 0,
+            locals: main_state.locals,
+            captures: Vec::new(),
+            chunk: main_state.chunk,
+        };
+
+        Ok(Program {
+            main: Arc::new(main),
+            functions: compiler.functions,
+            names: compiler.names,
+        })
+    }
+
+    fn intern(&mut self, name: &str) -> u32 {
+        if let Some(&id) = self.name_ids.get(name) {
+            return id;
+        }
+
+        let id = self.names.len() as u32;
+        self.names.push(name.to_string());
+        self.name_ids.insert(name.to_string(), id);
+        id
+    }
+
+    fn state(&mut self) -> &mut FunctionState {
+        self.states.last_mut().expect("compiler always has a function state")
+    }
+
+    fn in_function(&self) -> bool {
+        self.states.len() > 1
+    }
+
+    fn emit(&mut self, instr: Instr, span: Span) -> usize {
+        self.state().chunk.emit(instr, span)
+    }
+
+    fn emit_constant(&mut self, value: Value, span: Span) {
+        let index = self.state().chunk.add_constant(value);
+        self.emit(Instr::Constant(index), span);
+    }
+
+    fn emit_not_implemented(&mut self, message: &str, span: Span) {
+        let index = self.state().chunk.add_constant(Value::String(message.to_string()));
+        self.emit(Instr::NotImplemented(index), span);
+    }
+
+    // Every statement leaves exactly one value on the stack
+    fn compile_statement(&mut self, stmt: &Statement) -> Result<()> {
+        match &stmt.kind {
+            StatementKind::FuncDef { name, params, body, .. } => {
+                let index = self.compile_function(name, params, Vec::new(), |compiler| {
+                    // the function returns the value of its last statement
+                    let end = body.last().map(|s| s.span).unwrap_or_default();
+                    compiler.compile_block(body, end)?;
+                    Ok(end)
+                })?;
+                self.emit(Instr::DefineFunction(index), stmt.span);
+            }
+            StatementKind::Expr(expr) => {
+                self.compile_expr(expr)?;
+                self.emit(Instr::PrintValue, stmt.span);
+            }
+            StatementKind::Let { name, value, constant, .. } => {
+                self.compile_expr(value)?;
+                let id = self.intern(name);
+
+                if self.in_function() {
+                    let slot = self.state().declare(id);
+                    self.emit(Instr::StoreLocal { slot, constant: *constant }, stmt.span);
+                } else {
+                    self.emit(Instr::DefineGlobal { name: id, constant: *constant }, stmt.span);
+                }
+                self.emit(Instr::PrintLet { name: id, constant: *constant }, stmt.span);
+            }
+            StatementKind::Assign { target, op, value } => {
+                let (name, keys) = self.compile_assign_target(target)?;
+                self.compile_expr(value)?;
+
+                let name = self.intern(&name);
+                self.emit(Instr::Assign { name, keys, op: *op }, stmt.span);
+                let text = self.state().chunk.add_constant(Value::String(Formatter::expr(target)));
+                self.emit(Instr::PrintAssign(text), stmt.span);
+            }
+            StatementKind::Get { .. } => {
+                self.emit_not_implemented("GET statement (not yet implemented)", stmt.span);
+            }
+            StatementKind::Set { .. } => {
+                self.emit_not_implemented("SET statement (not yet implemented)", stmt.span);
+            }
+            StatementKind::Delete { .. } => {
+                self.emit_not_implemented("DELETE statement (not yet implemented)", stmt.span);
+            }
+            StatementKind::Where { .. } => {
+                self.emit_not_implemented("WHERE statement (not yet implemented)", stmt.span);
+            }
+            StatementKind::Import { path, .. } => {
+                return Err(anyhow!("Unresolved import \"{}\" at {}, modules are linked before compiling", path, stmt.span));
+            }
+            StatementKind::Throw { value } => {
+                self.compile_expr(value)?;
+                self.emit(Instr::Throw, stmt.span);
+            }
+            StatementKind::Try { body, catch, finally } => {
+                // the finally handler is outside, it also sees the errors of the catch block
+                let finally_handler = finally
+                    .as_ref()
+                    .map(|_| self.emit(Instr::PushHandler { target: 0, finally: true }, stmt.span));
+                let catch_handler = catch
+                    .as_ref()
+                    .map(|_| self.emit(Instr::PushHandler { target: 0, finally: false }, stmt.span));
+
+                self.compile_block(&body.statements, body.span)?;
+
+                if let (Some(catch), Some(handler)) = (catch, catch_handler) {
+                    self.emit(Instr::PopHandler, stmt.span);
+                    let skip = self.emit(Instr::Jump(0), stmt.span);
+
+                    // the error value is on the stack, bind it like a `let`
+                    self.patch_jump(handler);
+                    let id = self.intern(&catch.name);
+                    if self.in_function() {
+                        let slot = self.state().declare(id);
+                        self.emit(Instr::StoreLocal { slot, constant: false }, catch.span);
+                    } else {
+                        self.emit(Instr::DefineGlobal { name: id, constant: false }, catch.span);
+                    }
+                    self.emit(Instr::Pop, catch.span);
+                    self.compile_block(&catch.body.statements, catch.body.span)?;
+                    self.patch_jump(skip);
+                }
+
+                if let (Some(finally), Some(handler)) = (finally, finally_handler) {
+                    // the value of the try statement stays under the one of the finally block
+                    self.emit(Instr::PopHandler, stmt.span);
+                    self.compile_block(&finally.statements, finally.span)?;
+                    self.emit(Instr::Pop, stmt.span);
+                    let skip = self.emit(Instr::Jump(0), stmt.span);
+
+                    self.patch_jump(handler);
+                    self.compile_block(&finally.statements, finally.span)?;
+                    self.emit(Instr::Pop, stmt.span);
+                    self.emit(Instr::Rethrow, stmt.span);
+                    self.patch_jump(skip);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Pushes the indexes of the target, fields are string keys like in
+    // Evaluator::assign_path. Returns the variable name and the number of keys
+    fn compile_assign_target(&mut self, target: &Expr) -> Result<(String, u32)> {
+        match &target.kind {
+            ExprKind::Identifier(name) => Ok((name.clone(), 0)),
+            ExprKind::Index { object, index } => {
+                let (name, keys) = self.compile_assign_target(object)?;
+                self.compile_expr(index)?;
+                Ok((name, keys + 1))
+            }
+            ExprKind::FieldAccess { field, object, .. } => {
+                let (name, keys) = self.compile_assign_target(object)?;
+                self.emit_constant(Value::String(field.clone()), target.span);
+                Ok((name, keys + 1))
+            }
+            _ => Err(anyhow!("Invalid assignment target at {}", target.span)),
+        }
+    }
+
+    // Statements in order, the value of the last one stays on the stack
+    fn compile_block(&mut self, statements: &[Statement], span: Span) -> Result<()> {
+        for (i, stmt) in statements.iter().enumerate() {
+            self.compile_statement(stmt)?;
+            if i + 1 < statements.len() {
+                self.emit(Instr::Pop, stmt.span);
+            }
+        }
+
+        if statements.is_empty() {
+            self.emit(Instr::Unit, span);
+        }
+        Ok(())
+    }
+
+    // Point the jump or handler at `at` to the next instruction
+    fn patch_jump(&mut self, at: usize) {
+        let next = self.state().chunk.code.len() as u32;
+        match &mut self.state().chunk.code[at] {
+            Instr::Jump(target)
+            | Instr::JumpIfNotNull(target)
+            | Instr::PushHandler { target, .. }
+            | Instr::MatchPattern { next: target, .. }
+            | Instr::MatchGuard(target) => *target = next,
+            other => unreachable!("not a jump: {:?}", other),
+        }
+    }
+
+    // `body` compiles the code leaving the result on the stack and returns the span of the Return.
+    // A lambda has a slot for each (name, enclosing slot) it captures after its parameters
+    fn compile_function(
+        &mut self,
+        name: &str,
+        params: &[Param],
+        captures: Vec<(u32, u32)>,
+        body: impl FnOnce(&mut Self) -> Result<Span>,
+    ) -> Result<u32> {
+        let name = self.intern(name);
+
+        let mut state = FunctionState::default();
+        for param in params {
+            let id = self.intern(&param.name);
+            state.locals.push(id);
+        }
+        state.locals.extend(captures.iter().map(|&(id, _)| id));
+        self.states.push(state);
+
+        let end = body(self)?;
+        self.emit(Instr::Return, end);
+
+        let state = self.states.pop().unwrap_or_default();
+        self.functions.push(Arc::new(Function {
+            name,
+            arity: params.len(),
+            locals: state.locals,
+            captures: captures.into_iter().map(|(_, slot)| slot).collect(),
+            chunk: state.chunk,
+        }));
+
+        Ok((self.functions.len() - 1) as u32)
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) -> Result<()> {
+        match &expr.kind {
+            ExprKind::Number(n) => self.emit_constant(Value::Number(n.value), expr.span),
+            ExprKind::String(s) => self.emit_constant(Value::String(s.clone()), expr.span),
+            ExprKind::Boolean(b) => self.emit_constant(Value::Boolean(*b), expr.span),
+            ExprKind::Null => self.emit_constant(Value::Null, expr.span),
+            ExprKind::Identifier(name) => {
+                let id = self.intern(name);
+                match self.state().resolve(id) {
+                    Some(slot) if self.in_function() => self.emit(Instr::LoadLocal(slot), expr.span),
+                    _ => self.emit(Instr::LoadName(id), expr.span),
+                };
+            }
+            ExprKind::Binary { left, op: BinaryOp::Coalesce, right } => {
+                // the right side only runs when the left one is null
+                self.compile_expr(left)?;
+                let jump = self.emit(Instr::JumpIfNotNull(0), expr.span);
+                self.emit(Instr::Pop, expr.span);
+                self.compile_expr(right)?;
+                self.patch_jump(jump);
+            }
+            ExprKind::Binary { left, op, right } => {
+                // both operands are always evaluated, like in the tree-walker
+                self.compile_expr(left)?;
+                self.compile_expr(right)?;
+                self.emit(Instr::Binary(*op), expr.span);
+            }
+            ExprKind::Unary { op, operand } => {
+                self.compile_expr(operand)?;
+                self.emit(Instr::Unary(*op), expr.span);
+            }
+            ExprKind::Call { name, args } => {
+                let name = self.intern(name);
+                self.emit(Instr::PrepareCall { name, argc: args.len() as u32 }, expr.span);
+                for arg in args {
+                    self.compile_expr(arg)?;
+                }
+                self.emit(Instr::Call(args.len() as u32), expr.span);
+            }
+            ExprKind::Apply { callee, args } => {
+                self.compile_expr(callee)?;
+                self.emit(Instr::PrepareApply(args.len() as u32), expr.span);
+                for arg in args {
+                    self.compile_expr(arg)?;
+                }
+                self.emit(Instr::Call(args.len() as u32), expr.span);
+            }
+            ExprKind::Lambda { params, body, .. } => {
+                // the free names that are locals of the enclosing function are captured
+                let mut captures = Vec::new();
+                if self.in_function() {
+                    for name in Expr::free_names(params, body) {
+                        let id = self.intern(name);
+                        if let Some(slot) = self.state().resolve(id) {
+                            captures.push((id, slot));
+                        }
+                    }
+                }
+
+                let index = self.compile_function("fn", params, captures, |compiler| match body {
+                    LambdaBody::Expr(value) => {
+                        compiler.compile_expr(value)?;
+                        Ok(value.span)
+                    }
+                    LambdaBody::Block(block) => {
+                        compiler.compile_block(&block.statements, block.span)?;
+                        Ok(block.statements.last().map_or(block.span, |s| s.span))
+                    }
+                })?;
+                self.emit(Instr::MakeClosure(index), expr.span);
+            }
+            ExprKind::FieldAccess { field, object, optional } => {
+                self.compile_expr(object)?;
+                let name = self.intern(field);
+                self.emit(Instr::Field { name, optional: *optional }, expr.span);
+            }
+            ExprKind::List(items) => {
+                for item in items {
+                    self.compile_expr(item)?;
+                }
+                self.emit(Instr::MakeList(items.len() as u32), expr.span);
+            }
+            ExprKind::Map(fields) => {
+                for (name, value) in fields {
+                    self.emit_constant(Value::String(name.clone()), expr.span);
+                    self.compile_expr(value)?;
+                }
+                self.emit(Instr::MakeMap(fields.len() as u32), expr.span);
+            }
+            ExprKind::Index { object, index } => {
+                self.compile_expr(object)?;
+                self.compile_expr(index)?;
+                self.emit(Instr::Index, expr.span);
+            }
+            ExprKind::Match { value, arms } => {
+                // the matched value stays on the stack until an arm is done with it
+                self.compile_expr(value)?;
+                let mut ends = Vec::new();
+                for arm in arms {
+                    // bindings are locals of the arm inside functions, globals at the top level
+                    let mut bindings = Vec::new();
+                    let mut new_slots = Vec::new();
+                    for (name, _) in arm.pattern.bindings() {
+                        let id = self.intern(name);
+                        if self.in_function() {
+                            let state = self.state();
+                            if state.resolve(id).is_none() {
+                                new_slots.push(state.locals.len() as u32);
+                            }
+                            bindings.push(Binding::Local(state.declare(id)));
+                        } else {
+                            bindings.push(Binding::Global(id));
+                        }
+                    }
+                    let chunk = &mut self.state().chunk;
+                    chunk.patterns.push(MatchPattern { pattern: arm.pattern.clone(), bindings });
+                    let pattern = (chunk.patterns.len() - 1) as u32;
+                    let test = self.emit(Instr::MatchPattern { pattern, next: 0 }, arm.pattern.span);
+
+                    let guard = match &arm.guard {
+                        Some(guard) => {
+                            self.compile_expr(guard)?;
+                            Some(self.emit(Instr::MatchGuard(0), guard.span))
+                        }
+                        None => None,
+                    };
+
+                    self.compile_expr(&arm.body)?;
+                    self.emit(Instr::EndArm, arm.span);
+                    self.emit(Instr::PopUnder, arm.span);
+                    ends.push(self.emit(Instr::Jump(0), arm.span));
+
+                    if let Some(guard) = guard {
+                        self.patch_jump(guard);
+                        self.emit(Instr::EndArm, arm.span);
+                    }
+                    self.patch_jump(test);
+                    self.state().ended.extend(new_slots);
+                }
+
+                self.emit(Instr::NoMatch, expr.span);
+                for end in ends {
+                    self.patch_jump(end);
+                }
+            }
+        }
+        Ok(())
+    }
+}
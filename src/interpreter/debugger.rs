@@ -0,0 +1,194 @@
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+
+use anyhow::{Result, anyhow};
+
+use crate::interpreter::ast::Statement;
+use crate::interpreter::evaluator::{DebugHook, Evaluator};
+use crate::interpreter::parser::Parser;
+use crate::interpreter::tokenizer::{TokenKind, Tokenizer};
+
+const HELP: &str = "\
+Commands:
+  break <line>, b <line>     set a breakpoint
+  delete <line>              remove a breakpoint
+  continue, c                run until the next breakpoint
+  step, s                    step into the next statement, entering calls
+  next, n                    step over calls
+  out, o                     run until the current function returns
+  locals, l                  show the variables of the paused frame
+  stack, bt                  show the call stack
+  print <expr>, p <expr>     evaluate an expression in the paused frame
+  quit, q                    stop the program";
+
+// How far to run before pausing again
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    // only breakpoints pause
+    Continue,
+    // pause at the next statement, at any depth
+    StepIn,
+    // pause at the next statement at this call depth or above
+    StepOver(usize),
+    // pause at the next statement above this call depth
+    StepOut(usize),
+}
+
+// Command line front-end of `mu debug`, driving the Evaluator through DebugHook.
+// It starts paused before the first statement so breakpoints can be set.
+// Commands are read from `input` and the debugger messages go to `output`,
+// the program output keeps going to the evaluator's own writer
+pub struct Debugger {
+    source_lines: Vec<String>,
+    breakpoints: BTreeSet<usize>,
+    mode: Mode,
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+}
+
+impl Debugger {
+    pub fn new(source: &str, input: Box<dyn BufRead>, output: Box<dyn Write>) -> Self {
+        Debugger {
+            source_lines: source.lines().map(str::to_string).collect(),
+            breakpoints: BTreeSet::new(),
+            mode: Mode::StepIn,
+            input,
+            output,
+        }
+    }
+
+    fn should_pause(&self, line: usize, depth: usize) -> bool {
+        if self.breakpoints.contains(&line) {
+            return true;
+        }
+
+        match self.mode {
+            Mode::Continue => false,
+            Mode::StepIn => true,
+            Mode::StepOver(from) => depth <= from,
+            Mode::StepOut(from) => depth < from,
+        }
+    }
+
+    // Read commands until one resumes the program
+    fn pause(&mut self, evaluator: &mut Evaluator, stmt: &Statement) -> Result<()> {
+        let line = stmt.span.line;
+        let depth = evaluator.call_stack().len();
+
+        let location = match evaluator.call_stack().last() {
+            Some(frame) => format!("in {}()", frame.name),
+            None => "at top level".to_string(),
+        };
+        let text = self.source_lines.get(line - 1).map(|l| l.trim()).unwrap_or_default();
+        writeln!(self.output, "Paused at line {} {}: {}", line, location, text)?;
+
+        loop {
+            write!(self.output, "(mu-debug) ")?;
+            self.output.flush()?;
+
+            let mut command = String::new();
+            if self.input.read_line(&mut command)? == 0 {
+                // no more commands, let the program finish
+                self.breakpoints.clear();
+                self.mode = Mode::Continue;
+                return Ok(());
+            }
+
+            let command = command.trim();
+            let (name, argument) = match command.split_once(char::is_whitespace) {
+                Some((name, argument)) => (name, argument.trim()),
+                None => (command, ""),
+            };
+
+            match name {
+                "" => {}
+                "continue" | "c" => {
+                    self.mode = Mode::Continue;
+                    return Ok(());
+                }
+                "step" | "s" => {
+                    self.mode = Mode::StepIn;
+                    return Ok(());
+                }
+                "next" | "n" => {
+                    self.mode = Mode::StepOver(depth);
+                    return Ok(());
+                }
+                "out" | "o" => {
+                    self.mode = Mode::StepOut(depth);
+                    return Ok(());
+                }
+                "break" | "b" => match argument.parse::<usize>() {
+                    Ok(line) => {
+                        self.breakpoints.insert(line);
+                        writeln!(self.output, "Breakpoint set at line {}", line)?;
+                    }
+                    Err(_) => writeln!(self.output, "Usage: break <line>")?,
+                },
+                "delete" => match argument.parse::<usize>() {
+                    Ok(line) if self.breakpoints.remove(&line) => {
+                        writeln!(self.output, "Breakpoint removed from line {}", line)?;
+                    }
+                    _ => writeln!(self.output, "No breakpoint at line '{}'", argument)?,
+                },
+                "locals" | "l" => {
+                    // inside a call only its own bindings, the globals are the top level frame
+                    let mut variables: Vec<_> = evaluator
+                        .variables()
+                        .iter()
+                        .filter(|(name, _)| evaluator.frame_locals().is_none_or(|locals| locals.contains(*name)))
+                        .collect();
+                    variables.sort_by(|a, b| a.0.cmp(b.0));
+
+                    if variables.is_empty() {
+                        writeln!(self.output, "No variables")?;
+                    }
+                    for (name, value) in variables {
+                        writeln!(self.output, "  {} = {}", name, value)?;
+                    }
+                }
+                "stack" | "bt" => {
+                    writeln!(self.output, "  line {} {}", line, location)?;
+                    for (i, frame) in evaluator.call_stack().iter().enumerate().rev() {
+                        let caller = match i.checked_sub(1).map(|i| &evaluator.call_stack()[i]) {
+                            Some(caller) => format!("in {}()", caller.name),
+                            None => "at top level".to_string(),
+                        };
+                        writeln!(self.output, "  {}() called at {} {}", frame.name, frame.call_span, caller)?;
+                        if frame.repeated > 0 {
+                            writeln!(self.output, "  ... repeated {} more times", frame.repeated)?;
+                        }
+                    }
+                }
+                "print" | "p" => match Self::eval(evaluator, argument) {
+                    Ok(value) => writeln!(self.output, "{}", value)?,
+                    Err(e) => writeln!(self.output, "Error: {}", e)?,
+                },
+                "quit" | "q" => return Err(anyhow!("Debugging session ended")),
+                "help" | "h" => writeln!(self.output, "{}", HELP)?,
+                _ => writeln!(self.output, "Unknown command '{}', type 'help' for the list", name)?,
+            }
+        }
+    }
+
+    fn eval(evaluator: &mut Evaluator, source: &str) -> Result<String> {
+        let tokens = Tokenizer::tokenize(source.to_string())?;
+        let (expr, end) = Parser::new(tokens.clone()).parse_expression(0)?;
+
+        // the whole input has to be one expression
+        if !matches!(tokens.get(end).map(|t| &t.kind), Some(TokenKind::EOF) | Some(TokenKind::Semicolon)) {
+            return Err(anyhow!("Expected a single expression"));
+        }
+
+        Ok(evaluator.eval_in_frame(&expr)?.to_string())
+    }
+}
+
+impl DebugHook for Debugger {
+    fn before_statement(&mut self, evaluator: &mut Evaluator, stmt: &Statement) -> Result<()> {
+        if self.should_pause(stmt.span.line, evaluator.call_stack().len()) {
+            self.pause(evaluator, stmt)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,328 @@
+use crate::interpreter::analyzer::Analyzer;
+use crate::interpreter::ast::{Statement, StatementKind, Param, TypeAnnotation};
+use crate::interpreter::builtins::{BUILTINS, GLOBALS};
+use crate::interpreter::diagnostic::Diagnostic;
+use crate::interpreter::parser::Parser;
+use crate::interpreter::span::Span;
+use crate::interpreter::tokenizer::{Token, TokenKind, Tokenizer};
+use crate::interpreter::typechecker::TypeChecker;
+
+pub const KEYWORDS: &[&str] = &["let", "const", "func", "get", "set", "where", "delete", "import", "as", "try", "catch", "finally", "throw", "match", "fn", "true", "false", "null"];
+pub const TYPE_NAMES: &[&str] = &["number", "string", "bool", "list", "map", "unit"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Variable,
+    Constant,
+    Parameter,
+}
+
+// A name introduced by `func`, `let`, `const`, `catch` or a parameter list
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub name: String,
+    pub kind: SymbolKind,
+    // the name itself
+    pub span: Span,
+    // what a hover shows: `func add(a: number, b) -> number`, `let x: number`
+    pub signature: String,
+    // the function the name is local to, None for the top level
+    pub scope: Option<Span>,
+}
+
+// Outline entry: functions with their parameters and locals, top level variables
+#[derive(Debug, Clone)]
+pub struct DocumentSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    // the whole statement
+    pub span: Span,
+    pub name_span: Span,
+    pub children: Vec<DocumentSymbol>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Keyword,
+    Type,
+    Function,
+    Variable,
+    Constant,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: Option<String>,
+}
+
+// What the editor tooling knows about one source file.
+// Built from the tokens and the partial AST the parser recovers,
+// so a file with syntax errors still has definitions and symbols.
+// Positions are the 1-based line and column used by Span
+pub struct Document {
+    tokens: Vec<Token>,
+    diagnostics: Vec<Diagnostic>,
+    definitions: Vec<Definition>,
+    symbols: Vec<DocumentSymbol>,
+}
+
+impl Document {
+    pub fn new(source: &str) -> Self {
+        let tokens = match Tokenizer::tokenize(source.to_string()) {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                // the tokenizer message already says where, the span is the start of the file
+                return Document {
+                    tokens: Vec::new(),
+                    diagnostics: vec![Diagnostic::error(e.to_string(), Span::new(1, 1, 1, 1))],
+                    definitions: Vec::new(),
+                    symbols: Vec::new(),
+                };
+            }
+        };
+
+        // the parser takes the names out of the tokens, it gets its own copy
+        let (ast, mut diagnostics) = Parser::new(tokens.clone()).parse_with_diagnostics();
+
+        // semantic errors on a partial AST would only be noise
+        if diagnostics.is_empty() {
+            diagnostics.extend(Analyzer::analyze(&ast));
+            diagnostics.extend(TypeChecker::check(&ast));
+        }
+
+        let mut document = Document {
+            tokens,
+            diagnostics,
+            definitions: Vec::new(),
+            symbols: Vec::new(),
+        };
+        document.symbols = document.collect(&ast.statements, None);
+        document
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn definitions(&self) -> &[Definition] {
+        &self.definitions
+    }
+
+    pub fn symbols(&self) -> &[DocumentSymbol] {
+        &self.symbols
+    }
+
+    fn collect(&mut self, statements: &[Statement], scope: Option<Span>) -> Vec<DocumentSymbol> {
+        let mut symbols = Vec::new();
+
+        for stmt in statements {
+            match &stmt.kind {
+                StatementKind::Let { name, ty, constant, .. } => {
+                    let name_span = self.name_span(stmt.span, name);
+                    let (kind, keyword) = if *constant {
+                        (SymbolKind::Constant, "const")
+                    } else {
+                        (SymbolKind::Variable, "let")
+                    };
+                    self.definitions.push(Definition {
+                        name: name.clone(),
+                        kind,
+                        span: name_span,
+                        signature: format!("{} {}{}", keyword, name, annotation(": ", ty.as_ref())),
+                        scope,
+                    });
+                    symbols.push(DocumentSymbol {
+                        name: name.clone(),
+                        kind,
+                        span: stmt.span,
+                        name_span,
+                        children: Vec::new(),
+                    });
+                }
+                StatementKind::FuncDef { name, params, return_type, body } => {
+                    let name_span = self.name_span(stmt.span, name);
+                    let params_text: Vec<String> = params.iter().map(param_text).collect();
+                    self.definitions.push(Definition {
+                        name: name.clone(),
+                        kind: SymbolKind::Function,
+                        span: name_span,
+                        signature: format!(
+                            "func {}({}){}",
+                            name,
+                            params_text.join(", "),
+                            annotation(" -> ", return_type.as_ref())
+                        ),
+                        scope,
+                    });
+
+                    let mut children = Vec::new();
+                    for param in params {
+                        let span = Span::new(param.span.line, param.span.col, param.span.line, param.span.col + param.name.chars().count());
+                        self.definitions.push(Definition {
+                            name: param.name.clone(),
+                            kind: SymbolKind::Parameter,
+                            span,
+                            signature: format!("(parameter) {}", param_text(param)),
+                            scope: Some(stmt.span),
+                        });
+                        children.push(DocumentSymbol {
+                            name: param.name.clone(),
+                            kind: SymbolKind::Parameter,
+                            span: param.span,
+                            name_span: span,
+                            children: Vec::new(),
+                        });
+                    }
+                    children.extend(self.collect(body, Some(stmt.span)));
+
+                    symbols.push(DocumentSymbol {
+                        name: name.clone(),
+                        kind: SymbolKind::Function,
+                        span: stmt.span,
+                        name_span,
+                        children,
+                    });
+                }
+                StatementKind::Try { body, catch, finally } => {
+                    symbols.extend(self.collect(&body.statements, scope));
+                    if let Some(catch) = catch {
+                        self.definitions.push(Definition {
+                            name: catch.name.clone(),
+                            kind: SymbolKind::Variable,
+                            span: catch.span,
+                            signature: format!("(catch) {}", catch.name),
+                            scope,
+                        });
+                        symbols.extend(self.collect(&catch.body.statements, scope));
+                    }
+                    if let Some(finally) = finally {
+                        symbols.extend(self.collect(&finally.statements, scope));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        symbols
+    }
+
+    // `let` and `func` are followed by the name
+    fn name_span(&self, statement: Span, name: &str) -> Span {
+        match self.token_index(statement.line, statement.col) {
+            Some(index) if index + 1 < self.tokens.len() => self.tokens[index + 1].span,
+            _ => Span::new(statement.line, statement.col, statement.line, statement.col + name.chars().count()),
+        }
+    }
+
+    fn token_index(&self, line: usize, col: usize) -> Option<usize> {
+        self.tokens
+            .binary_search_by_key(&(line, col), |t| (t.span.line, t.span.col))
+            .ok()
+    }
+
+    // Index of the identifier under the cursor, the column right after the name counts too
+    fn identifier_at(&self, line: usize, col: usize) -> Option<(usize, &str)> {
+        self.tokens.iter().enumerate().find_map(|(i, token)| match &token.kind {
+            TokenKind::Identifier(name)
+                if token.span.line == line && token.span.col <= col && col <= token.span.end_col =>
+            {
+                Some((i, name.as_str()))
+            }
+            _ => None,
+        })
+    }
+
+    // Definition of the name under the cursor.
+    // Functions and variables live in different namespaces, a name followed by '('
+    // is a function. Variables resolve to the closest earlier definition in the
+    // enclosing function, then to the top level ones
+    pub fn definition_at(&self, line: usize, col: usize) -> Option<&Definition> {
+        let (index, name) = self.identifier_at(line, col)?;
+        let position = (line, col);
+
+        let is_function = matches!(self.tokens.get(index + 1).map(|t| &t.kind), Some(TokenKind::LParen))
+            || matches!(index.checked_sub(1).map(|i| &self.tokens[i].kind), Some(TokenKind::Func));
+
+        let candidates = self.definitions
+            .iter()
+            .filter(|d| d.name == name && (d.kind == SymbolKind::Function) == is_function);
+
+        if is_function {
+            return candidates.clone().find(|d| d.scope.is_none()).or(candidates.clone().next());
+        }
+
+        // innermost function around the cursor
+        let enclosing = self.definitions
+            .iter()
+            .filter_map(|d| d.scope)
+            .filter(|scope| contains(scope, position))
+            .min_by_key(|scope| (scope.end_line - scope.line, scope.end_col));
+
+        let before = |d: &&Definition| (d.span.line, d.span.col) <= position;
+
+        if let Some(scope) = enclosing
+            && let Some(local) = candidates.clone().filter(|d| d.scope == Some(scope)).rfind(before)
+        {
+            return Some(local);
+        }
+
+        let globals = candidates.filter(|d| d.scope.is_none());
+        globals.clone().rfind(before).or(globals.clone().next())
+    }
+
+    pub fn hover_at(&self, line: usize, col: usize) -> Option<String> {
+        self.definition_at(line, col).map(|d| d.signature.clone())
+    }
+
+    // Keywords, type names, builtins, and the functions and top level variables of the document
+    pub fn completions(&self) -> Vec<Completion> {
+        let mut completions: Vec<Completion> = KEYWORDS
+            .iter()
+            .map(|k| Completion { label: k.to_string(), kind: CompletionKind::Keyword, detail: None })
+            .chain(TYPE_NAMES.iter().map(|t| Completion { label: t.to_string(), kind: CompletionKind::Type, detail: None }))
+            .chain(BUILTINS.iter().map(|b| Completion {
+                label: b.name.to_string(),
+                kind: CompletionKind::Function,
+                detail: Some(b.signature()),
+            }))
+            .chain(GLOBALS.iter().map(|g| Completion { label: g.to_string(), kind: CompletionKind::Variable, detail: None }))
+            .collect();
+
+        for definition in &self.definitions {
+            let kind = match definition.kind {
+                SymbolKind::Function => CompletionKind::Function,
+                SymbolKind::Variable if definition.scope.is_none() => CompletionKind::Variable,
+                SymbolKind::Constant if definition.scope.is_none() => CompletionKind::Constant,
+                _ => continue,
+            };
+
+            if !completions.iter().any(|c| c.label == definition.name && c.kind == kind) {
+                completions.push(Completion {
+                    label: definition.name.clone(),
+                    kind,
+                    detail: Some(definition.signature.clone()),
+                });
+            }
+        }
+
+        completions
+    }
+}
+
+fn contains(span: &Span, position: (usize, usize)) -> bool {
+    (span.line, span.col) <= position && position < (span.end_line, span.end_col)
+}
+
+fn annotation(prefix: &str, annotation: Option<&TypeAnnotation>) -> String {
+    match annotation {
+        Some(annotation) => format!("{}{}", prefix, annotation.ty),
+        None => String::new(),
+    }
+}
+
+fn param_text(param: &Param) -> String {
+    format!("{}{}", param.name, annotation(": ", param.ty.as_ref()))
+}
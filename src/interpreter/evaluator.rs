@@ -1,13 +1,93 @@
-use anyhow::{Result, anyhow};
-use std::collections::HashMap;
-use crate::interpreter::ast::{AST, Statement, Expr, BinaryOp, UnaryOp};
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Write, stdout};
+use std::sync::Arc;
+use unicode_ident::{is_xid_continue, is_xid_start};
+use crate::interpreter::ast::{AST, Block, Statement, StatementKind, Expr, ExprKind, BinaryOp, UnaryOp, LambdaBody, MatchArm, Pattern, PatternKind};
+use crate::interpreter::bytecode::Function;
+use crate::interpreter::span::Span;
+use crate::interpreter::interrupt;
+use crate::interpreter::builtins::{self, Builtin};
+use crate::interpreter::formatter::Formatter;
 
 #[derive(Debug, Clone)]
 pub enum Value {
     Number(f64),
     String(String),
     Boolean(bool),
+    List(Vec<Value>),
+    // fields sorted by name
+    Map(BTreeMap<String, Value>),
+    // `null`, also what a missing map field reads as
+    Null,
     Unit,
+    Function(Callable),
+}
+
+// A function used as a value: a lambda, or a `func` or builtin read by its name
+#[derive(Clone)]
+pub enum Callable {
+    // run by the tree-walker
+    Closure(Arc<Closure>),
+    // run by the VM, with the values of the slots the lambda captured
+    Compiled { function: Arc<Function>, name: Option<String>, captured: Arc<Vec<Option<Value>>> },
+    Builtin(&'static Builtin),
+}
+
+impl Callable {
+    // None for lambdas
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Callable::Closure(closure) => closure.name.as_deref(),
+            Callable::Compiled { name, .. } => name.as_deref(),
+            Callable::Builtin(builtin) => Some(builtin.name),
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        match self {
+            Callable::Closure(closure) => closure.params.len(),
+            Callable::Compiled { function, .. } => function.arity,
+            Callable::Builtin(builtin) => builtin.params.len(),
+        }
+    }
+}
+
+// `<fn double>`, or `<fn>` for a lambda. Debug is the same so that error
+// messages do not depend on the backend
+impl std::fmt::Display for Callable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "<fn {}>", name),
+            None => write!(f, "<fn>"),
+        }
+    }
+}
+
+impl std::fmt::Debug for Callable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+// A `func` definition or a lambda of the tree-walker
+#[derive(Debug)]
+pub struct Closure {
+    pub name: Option<String>,
+    pub params: Vec<String>,
+    pub body: LambdaBody,
+    // locals of the enclosing call the body uses, copied when the lambda is created
+    pub captured: Vec<(String, Value)>,
+}
+
+impl Value {
+    // Inside a list or a map strings are quoted, so ["a, b"] and ["a", "b"] look different
+    fn fmt_item(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{:?}", s),
+            other => write!(f, "{}", other),
+        }
+    }
 }
 
 impl std::fmt::Display for Value {
@@ -16,161 +96,1054 @@ impl std::fmt::Display for Value {
             Value::Number(n) => write!(f, "{}", n),
             Value::String(s) => write!(f, "{}", s),
             Value::Boolean(b) => write!(f, "{}", b),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item.fmt_item(f)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(fields) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    // quoted like in a map literal when it is not a plain name
+                    let plain = name.chars().next().is_some_and(|c| is_xid_start(c) || c == '_')
+                        && name.chars().all(is_xid_continue);
+                    if plain {
+                        write!(f, "{}: ", name)?;
+                    } else {
+                        write!(f, "{:?}: ", name)?;
+                    }
+                    value.fmt_item(f)?;
+                }
+                write!(f, "}}")
+            }
+            Value::Null => write!(f, "null"),
             Value::Unit => write!(f, "()"),
+            Value::Function(callable) => write!(f, "{}", callable),
+        }
+    }
+}
+
+// A Mu function call that is currently running
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub name: String,
+    pub call_span: Span,
+    // the same call made again right away, by recursion, folded into this frame
+    pub repeated: usize,
+}
+
+impl Frame {
+    pub fn new(name: String, call_span: Span) -> Self {
+        Frame { name, call_span, repeated: 0 }
+    }
+}
+
+// Folds runs of the same call into one frame, a failing recursion
+// millions of calls deep has a traceback of a few lines
+pub(crate) fn collapse_frames(frames: impl IntoIterator<Item = Frame>) -> Vec<Frame> {
+    let mut collapsed: Vec<Frame> = Vec::new();
+    for frame in frames {
+        match collapsed.last_mut() {
+            Some(last) if last.name == frame.name && last.call_span == frame.call_span => {
+                last.repeated += frame.repeated + 1;
+            }
+            _ => collapsed.push(frame),
+        }
+    }
+    collapsed
+}
+
+// Runtime error with the Mu call stack captured where it was raised
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub message: String,
+    // what a `catch` binds, see Evaluator::error_value
+    pub value: Value,
+    // outermost call first
+    pub trace: Vec<Frame>,
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+
+        if !self.trace.is_empty() {
+            write!(f, "\nTraceback (most recent call last):")?;
+            for frame in &self.trace {
+                write!(f, "\n  in {}() called at {}", frame.name, frame.call_span)?;
+                if frame.repeated > 0 {
+                    write!(f, "\n  ... repeated {} more times", frame.repeated)?;
+                }
+            }
         }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+// Raised by the `exit(code)` builtin, unwinds the whole program.
+// The binaries turn it into the process exit code
+#[derive(Debug)]
+pub struct Exit {
+    pub code: i32,
+}
+
+impl std::fmt::Display for Exit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "exit({})", self.code)
+    }
+}
+
+impl std::error::Error for Exit {}
+
+// Stops the program from the outside: Ctrl-C in the REPLs or a debugger quitting.
+// Like Exit, `try` cannot catch it
+#[derive(Debug)]
+pub struct Halt {
+    pub message: String,
+}
+
+impl std::fmt::Display for Halt {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)
     }
 }
 
+impl std::error::Error for Halt {}
+
+// Raised by `throw`, the value is already the error map a `catch` binds
+#[derive(Debug)]
+pub struct Thrown {
+    pub value: Value,
+    pub span: Span,
+}
+
+impl std::fmt::Display for Thrown {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let message = match &self.value {
+            Value::Map(fields) => fields.get("message").unwrap_or(&self.value),
+            value => value,
+        };
+        write!(f, "Uncaught error: {} at {}", message, self.span)
+    }
+}
+
+impl std::error::Error for Thrown {}
+
+// What went wrong, the `e.kind` a `catch` gets for an error of the interpreter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    DivisionByZero,
+    UndefinedVariable,
+    UndefinedFunction,
+    ArgumentError,
+    IndexError,
+    AssignmentError,
+    TypeError,
+    MatchError,
+    Error,
+}
+
+impl ErrorKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::DivisionByZero => "DivisionByZero",
+            ErrorKind::UndefinedVariable => "UndefinedVariable",
+            ErrorKind::UndefinedFunction => "UndefinedFunction",
+            ErrorKind::ArgumentError => "ArgumentError",
+            ErrorKind::IndexError => "IndexError",
+            ErrorKind::AssignmentError => "AssignmentError",
+            ErrorKind::TypeError => "TypeError",
+            ErrorKind::MatchError => "MatchError",
+            ErrorKind::Error => "Error",
+        }
+    }
+}
+
+// An error raised by the interpreter itself. The operations shared by both
+// backends do not know where they run, the expression that failed adds the span
+#[derive(Debug)]
+pub struct MuError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl MuError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        MuError { kind, message: message.into(), span: None }
+    }
+
+    pub fn at(self, span: Span) -> Self {
+        MuError { span: Some(span), ..self }
+    }
+}
+
+impl std::fmt::Display for MuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(span) = self.span {
+            write!(f, " at {}", span)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MuError {}
+
+// Result of an expression in tail position: its value, or the call that gives it,
+// which the caller runs in place of its own call
+enum Tail {
+    Value(Value),
+    Call { closure: Arc<Closure>, name: String, args: Vec<Value>, span: Span },
+}
+
+// What the calls replaced by tail calls leave to do once the last one returned
+#[derive(Default)]
+struct TailCalls {
+    // their locals, the caller does not get them back either
+    locals: HashSet<String>,
+    // what the names bound by the match arms around the tail calls held before, the outermost arm wins
+    bindings: HashMap<String, Option<Value>>,
+    // the result of their last statement still has to be printed
+    prints: usize,
+}
+
+// What a match arm binding hides until the end of the arm
+struct ArmBinding<'a> {
+    name: &'a str,
+    previous: Option<Value>,
+    constant: bool,
+    // the arm added the name to the locals of the running call
+    local: bool,
+}
+
+// Lets a debugger front-end drive the evaluation.
+// The evaluator calls it before running each statement, at the top level and
+// inside function bodies; the hook can inspect the paused evaluator, evaluate
+// expressions in the current frame, and return an error to stop the program.
+// The hook is detached while it runs, so what it evaluates is not reported back
+pub trait DebugHook {
+    fn before_statement(&mut self, evaluator: &mut Evaluator, stmt: &Statement) -> Result<()>;
+}
+
 pub struct Evaluator {
     // Store function definitions
-    functions: HashMap<String, (Vec<String>, Vec<Statement>)>,
+    functions: HashMap<String, Arc<Closure>>,
     // Store variables
     variables: HashMap<String, Value>,
+    // parameters and `let`s of each running call, innermost last.
+    // Assigning any other name changes the binding of the caller
+    locals: Vec<HashSet<String>>,
+    // visible bindings declared with `const`
+    constants: HashSet<String>,
+    // Mu calls being evaluated, innermost last
+    call_stack: Vec<Frame>,
+    // statement results and messages are written here (stdout by default)
+    output: Box<dyn Write>,
+    debug_hook: Option<Box<dyn DebugHook>>,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Evaluator {
     pub fn new() -> Self {
+        Self::with_output(Box::new(stdout()))
+    }
+
+    pub fn with_output(output: Box<dyn Write>) -> Self {
         Evaluator {
             functions: HashMap::new(),
             variables: HashMap::new(),
+            locals: Vec::new(),
+            constants: HashSet::new(),
+            call_stack: Vec::new(),
+            output,
+            debug_hook: None,
         }
     }
 
+    pub fn set_debug_hook(&mut self, hook: Box<dyn DebugHook>) {
+        self.debug_hook = Some(hook);
+    }
+
+    // Variables visible to the statement being evaluated
+    pub fn variables(&self) -> &HashMap<String, Value> {
+        &self.variables
+    }
+
+    // Parameters and `let`s of the innermost running call, None at the top level
+    pub fn frame_locals(&self) -> Option<&HashSet<String>> {
+        self.locals.last()
+    }
+
+    // Mu calls being evaluated, innermost last
+    pub fn call_stack(&self) -> &[Frame] {
+        &self.call_stack
+    }
+
+    // Evaluate an expression in the current frame, used by debuggers
+    pub fn eval_in_frame(&mut self, expr: &Expr) -> Result<Value> {
+        self.eval_expr(expr)
+    }
+
+    // Set a global before the program runs, like the script `args`
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.variables.insert(name.to_string(), value);
+    }
+
+    // Names and parameters of the defined functions
+    pub fn functions(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.functions.iter().map(|(name, function)| (name.as_str(), function.params.as_slice()))
+    }
+
+    // Forget every function and variable, used by the REPL `:reset`
+    pub fn reset(&mut self) {
+        self.functions.clear();
+        self.variables.clear();
+        self.locals.clear();
+        self.constants.clear();
+        self.call_stack.clear();
+    }
+
     pub fn evaluate(mut self, ast: AST) -> Result<()> {
+        self.run(ast)
+    }
+
+    // Like evaluate, but the definitions stay around for the next run
+    pub fn run(&mut self, ast: AST) -> Result<()> {
         for statement in ast.statements {
-            self.eval_statement(&statement)?;
+            self.eval_statement(&statement)
+                .map_err(|e| self.with_traceback(e))?;
+        }
+        Ok(())
+    }
+
+    // Snapshot the call stack into the error, only the innermost frame does it
+    // so outer calls leave an existing traceback untouched
+    fn with_traceback(&self, err: anyhow::Error) -> anyhow::Error {
+        if err.is::<RuntimeError>() || err.is::<Exit>() || err.is::<Halt>() {
+            return err;
+        }
+
+        let value = Self::error_value(&err).unwrap_or(Value::Unit);
+        anyhow::Error::new(RuntimeError {
+            message: err.to_string(),
+            value,
+            trace: collapse_frames(self.call_stack.iter().cloned()),
+        })
+    }
+
+    // The value a `catch` binds for an error, None for the ones it cannot catch.
+    // Errors of the interpreter become `{kind: "DivisionByZero", message: "..."}`.
+    // Shared with the bytecode VM
+    pub(crate) fn error_value(err: &anyhow::Error) -> Option<Value> {
+        if err.is::<Exit>() || err.is::<Halt>() {
+            return None;
+        }
+        if let Some(err) = err.downcast_ref::<RuntimeError>() {
+            return Some(err.value.clone());
+        }
+        if let Some(thrown) = err.downcast_ref::<Thrown>() {
+            return Some(thrown.value.clone());
+        }
+
+        // errors of the host, like a failed write, are plain errors
+        let (kind, message) = match err.downcast_ref::<MuError>() {
+            Some(err) => (err.kind, err.message.clone()),
+            None => (ErrorKind::Error, err.to_string()),
+        };
+        Some(Value::Map(BTreeMap::from([
+            ("kind".to_string(), Value::String(kind.name().to_string())),
+            ("message".to_string(), Value::String(message)),
+        ])))
+    }
+
+    // The error map of `throw value`: a map is thrown as it is,
+    // anything else becomes `{kind: "Error", message: "<value>", value: <value>}`.
+    // Shared with the bytecode VM
+    pub(crate) fn throw(value: Value, span: Span) -> anyhow::Error {
+        let value = match value {
+            Value::Map(fields) => Value::Map(fields),
+            value => Value::Map(BTreeMap::from([
+                ("kind".to_string(), Value::String("Error".to_string())),
+                ("message".to_string(), Value::String(value.to_string())),
+                ("value".to_string(), value),
+            ])),
+        };
+        anyhow::Error::new(Thrown { value, span })
+    }
+
+    // Statements of a block in order, the value of the last one
+    fn eval_block(&mut self, statements: &[Statement]) -> Result<Value> {
+        let mut value = Value::Unit;
+        for stmt in statements {
+            value = self.eval_statement(stmt)?;
+        }
+        Ok(value)
+    }
+
+    // Bind a `let`, `const` or `catch` variable in the current scope
+    fn declare(&mut self, name: &str, value: Value, constant: bool, span: Span) -> Result<()> {
+        // a function can shadow a constant of its caller, but not one of its own
+        let same_scope = self.locals.last().is_none_or(|locals| locals.contains(name));
+        if same_scope && self.constants.contains(name) {
+            return Err(MuError::new(ErrorKind::AssignmentError, format!("Cannot redeclare constant: {}", name)).at(span).into());
+        }
+
+        self.variables.insert(name.to_string(), value);
+        if let Some(locals) = self.locals.last_mut() {
+            locals.insert(name.to_string());
+        }
+
+        if constant {
+            self.constants.insert(name.to_string());
+        } else {
+            self.constants.remove(name);
+        }
+        Ok(())
+    }
+
+    fn before_statement(&mut self, stmt: &Statement) -> Result<()> {
+        // Ctrl-C in the REPL stops the program between statements
+        interrupt::check()?;
+
+        if let Some(mut hook) = self.debug_hook.take() {
+            let result = hook.before_statement(self, stmt);
+            self.debug_hook = Some(hook);
+            // stopping the program is not an error of the program, `try` must not catch it
+            result.map_err(|e| match e.downcast::<Halt>() {
+                Ok(halt) => halt,
+                Err(e) => Halt { message: e.to_string() },
+            })?;
         }
         Ok(())
     }
 
     fn eval_statement(&mut self, stmt: &Statement) -> Result<Value> {
-        match stmt {
-            Statement::FuncDef { name, params, body } => {
+        self.before_statement(stmt)?;
+
+        match &stmt.kind {
+            StatementKind::FuncDef { name, params, body, .. } => {
                 // Store the function definition
-                self.functions.insert(name.clone(), (params.clone(), body.clone()));
-                println!("Defined function: {}", name);
+                // type annotations are only checked statically, they are not needed at runtime
+                let function = Closure {
+                    name: Some(name.clone()),
+                    params: params.iter().map(|p| p.name.clone()).collect(),
+                    body: LambdaBody::Block(Block { statements: body.clone(), span: stmt.span }),
+                    captured: Vec::new(),
+                };
+                self.functions.insert(name.clone(), Arc::new(function));
+                writeln!(self.output, "Defined function: {}", name)?;
                 Ok(Value::Unit)
             }
-            Statement::Expr(expr) => {
+            StatementKind::Expr(expr) => {
                 let value = self.eval_expr(expr)?;
-                println!("{}", value);
+                writeln!(self.output, "{}", value)?;
                 Ok(value)
             }
-            Statement::Let { name, value } => {
+            StatementKind::Let { name, value, constant, .. } => {
                 let eval_value = self.eval_expr(value)?;
-                self.variables.insert(name.clone(), eval_value.clone());
-                println!("Let {} = {}", name, eval_value);
+                self.declare(name, eval_value.clone(), *constant, stmt.span)?;
+
+                let keyword = if *constant { "Const" } else { "Let" };
+                writeln!(self.output, "{} {} = {}", keyword, name, eval_value)?;
                 Ok(eval_value)
             }
-            Statement::Get { .. } => {
-                println!("GET statement (not yet implemented)");
+            StatementKind::Assign { target, op, value } => {
+                let (name, keys) = self.eval_assign_target(target)?;
+                let eval_value = self.eval_expr(value)?;
+
+                if self.constants.contains(&name) {
+                    return Err(MuError::new(ErrorKind::AssignmentError, format!("Cannot assign to constant: {}", name)).at(target.span).into());
+                }
+                let binding = self.variables
+                    .get_mut(&name)
+                    .ok_or_else(|| MuError::new(ErrorKind::AssignmentError, format!("Cannot assign to undeclared variable: {}", name)).at(target.span))?;
+                let assigned = Self::assign_path(binding, &keys, op.as_ref(), eval_value)
+                    .map_err(|e| Self::located(e, stmt.span))?;
+
+                writeln!(self.output, "{} = {}", Formatter::expr(target), assigned)?;
+                Ok(assigned)
+            }
+            StatementKind::Get { .. } => {
+                writeln!(self.output, "GET statement (not yet implemented)")?;
                 Ok(Value::Unit)
             }
-            Statement::Set { .. } => {
-                println!("SET statement (not yet implemented)");
+            StatementKind::Set { .. } => {
+                writeln!(self.output, "SET statement (not yet implemented)")?;
                 Ok(Value::Unit)
             }
-            Statement::Delete { .. } => {
-                println!("DELETE statement (not yet implemented)");
+            StatementKind::Delete { .. } => {
+                writeln!(self.output, "DELETE statement (not yet implemented)")?;
                 Ok(Value::Unit)
             }
-            Statement::Where { .. } => {
-                println!("WHERE statement (not yet implemented)");
+            StatementKind::Where { .. } => {
+                writeln!(self.output, "WHERE statement (not yet implemented)")?;
                 Ok(Value::Unit)
             }
+            StatementKind::Import { path, .. } => {
+                Err(MuError::new(ErrorKind::Error, format!("Unresolved import \"{}\", modules are linked before evaluation", path)).at(stmt.span).into())
+            }
+            StatementKind::Throw { value } => {
+                let value = self.eval_expr(value)?;
+                Err(Self::throw(value, stmt.span))
+            }
+            // the value of the try or the catch block that ran.
+            // exit() and Ctrl-C are not caught and skip the finally block
+            StatementKind::Try { body, catch, finally } => {
+                let mut result = self.eval_block(&body.statements);
+
+                if let (Err(err), Some(catch)) = (&result, catch)
+                    && let Some(error) = Self::error_value(err)
+                {
+                    result = self.declare(&catch.name, error, false, catch.span)
+                        .and_then(|_| self.eval_block(&catch.body.statements));
+                }
+
+                if let Some(finally) = finally
+                    && result.as_ref().err().is_none_or(|err| Self::error_value(err).is_some())
+                {
+                    self.eval_block(&finally.statements)?;
+                }
+                result
+            }
         }
     }
 
     fn eval_expr(&mut self, expr: &Expr) -> Result<Value> {
-        match expr {
-            Expr::Number(n) => Ok(Value::Number(*n)),
-            Expr::String(s) => Ok(Value::String(s.clone())),
-            Expr::Boolean(b) => Ok(Value::Boolean(*b)),
-            Expr::Identifier(name) => {
-                // Look up variable
-                self.variables
+        match &expr.kind {
+            ExprKind::Number(n) => Ok(Value::Number(n.value)),
+            ExprKind::String(s) => Ok(Value::String(s.clone())),
+            ExprKind::Boolean(b) => Ok(Value::Boolean(*b)),
+            ExprKind::Null => Ok(Value::Null),
+            ExprKind::Identifier(name) => {
+                // Look up variable, then a function of that name
+                if let Some(value) = self.variables.get(name) {
+                    return Ok(value.clone());
+                }
+                self.functions
                     .get(name)
-                    .cloned()
-                    .ok_or_else(|| anyhow!("Undefined variable: {}", name))
+                    .map(|function| Callable::Closure(function.clone()))
+                    .or_else(|| builtins::lookup(name).map(Callable::Builtin))
+                    .map(Value::Function)
+                    .ok_or_else(|| MuError::new(ErrorKind::UndefinedVariable, format!("Undefined variable: {}", name)).at(expr.span).into())
             }
-            Expr::Binary { left, op, right } => {
+            ExprKind::Binary { left, op: BinaryOp::Coalesce, right } => {
+                match self.eval_expr(left)? {
+                    Value::Null => self.eval_expr(right),
+                    value => Ok(value),
+                }
+            }
+            ExprKind::Binary { left, op, right } => {
                 let left_val = self.eval_expr(left)?;
                 let right_val = self.eval_expr(right)?;
-                self.eval_binary_op(&left_val, op, &right_val)
+                Self::eval_binary_op(&left_val, op, &right_val)
+                    .map_err(|e| Self::located(e, expr.span))
             }
-            Expr::Unary { op, operand } => {
+            ExprKind::Unary { op, operand } => {
                 let operand_val = self.eval_expr(operand)?;
-                self.eval_unary_op(op, &operand_val)
+                Self::eval_unary_op(op, &operand_val)
+                    .map_err(|e| Self::located(e, expr.span))
             }
-            Expr::Call { name, args } => {
-                // Look up function
-                let (params, body) = self.functions
-                    .get(name)
-                    .cloned()
-                    .ok_or_else(|| anyhow!("Undefined function: {}", name))?;
+            ExprKind::Call { name, args } => {
+                let callee = self.function_named(name, expr.span)?;
+                let args = self.eval_args(&callee, name, args, expr.span)?;
+                self.invoke(&callee, name, args, expr.span)
+            }
+            ExprKind::Apply { callee, args } => {
+                let callee = self.eval_callee(callee, expr.span)?;
+                let name = callee.name().unwrap_or("fn").to_string();
+                let args = self.eval_args(&callee, &name, args, expr.span)?;
+                self.invoke(&callee, &name, args, expr.span)
+            }
+            ExprKind::Lambda { params, body, .. } => {
+                // the locals of the running call the body uses are copied into the lambda
+                let captured = match self.locals.last() {
+                    Some(locals) => Expr::free_names(params, body)
+                        .into_iter()
+                        .filter(|name| locals.contains(*name))
+                        .filter_map(|name| self.variables.get(name).map(|value| (name.to_string(), value.clone())))
+                        .collect(),
+                    None => Vec::new(),
+                };
+                Ok(Value::Function(Callable::Closure(Arc::new(Closure {
+                    name: None,
+                    params: params.iter().map(|p| p.name.clone()).collect(),
+                    body: body.clone(),
+                    captured,
+                }))))
+            }
+            ExprKind::FieldAccess { field, object, optional } => {
+                let object_val = self.eval_expr(object)?;
+                Self::eval_field(&object_val, field, *optional)
+                    .map_err(|e| Self::located(e, expr.span))
+            }
+            ExprKind::List(items) => {
+                let mut values = Vec::with_capacity(items.len());
+                for item in items {
+                    values.push(self.eval_expr(item)?);
+                }
+                Ok(Value::List(values))
+            }
+            ExprKind::Map(fields) => {
+                let mut values = BTreeMap::new();
+                for (name, value) in fields {
+                    let value = self.eval_expr(value)?;
+                    values.insert(name.clone(), value);
+                }
+                Ok(Value::Map(values))
+            }
+            ExprKind::Index { object, index } => {
+                let object_val = self.eval_expr(object)?;
+                let index_val = self.eval_expr(index)?;
+                Self::eval_index(&object_val, &index_val)
+                    .map_err(|e| Self::located(e, expr.span))
+            }
+            ExprKind::Match { value, arms } => {
+                let value = self.eval_expr(value)?;
+                let (arm, bindings) = self.select_arm(&value, arms, expr.span)?;
+                let result = self.eval_expr(&arm.body);
+                self.unbind_arm(bindings);
+                result
+            }
+        }
+    }
+
+    // The first arm that matches the value and whose guard holds, with its bindings in place
+    fn select_arm<'a>(&mut self, value: &Value, arms: &'a [MatchArm], span: Span) -> Result<(&'a MatchArm, Vec<ArmBinding<'a>>)> {
+        for arm in arms {
+            let mut bound = Vec::new();
+            if !Self::match_pattern(&arm.pattern, value, &mut bound) {
+                continue;
+            }
+
+            // the bindings shadow variables and constants until the end of the arm
+            // inside a function they are locals, a lambda in the arm captures them
+            let mut bindings = Vec::new();
+            for ((name, _), value) in arm.pattern.bindings().into_iter().zip(bound) {
+                let previous = self.variables.insert(name.to_string(), value);
+                let constant = self.constants.remove(name);
+                let local = self.locals.last_mut().is_some_and(|locals| locals.insert(name.to_string()));
+                bindings.push(ArmBinding { name, previous, constant, local });
+            }
+
+            let Some(guard) = &arm.guard else {
+                return Ok((arm, bindings));
+            };
+            match self.eval_expr(guard) {
+                Ok(Value::Boolean(true)) => return Ok((arm, bindings)),
+                Ok(Value::Boolean(false)) => self.unbind_arm(bindings),
+                Ok(other) => {
+                    self.unbind_arm(bindings);
+                    return Err(Self::guard_error(&other).at(guard.span).into());
+                }
+                Err(e) => {
+                    self.unbind_arm(bindings);
+                    return Err(e);
+                }
+            }
+        }
+        Err(Self::no_match_error(value).at(span).into())
+    }
+
+    // Put back what the arm bindings hid, last bound first
+    fn unbind_arm(&mut self, bindings: Vec<ArmBinding>) {
+        for binding in bindings.into_iter().rev() {
+            match binding.previous {
+                Some(previous) => self.variables.insert(binding.name.to_string(), previous),
+                None => self.variables.remove(binding.name),
+            };
+            if binding.constant {
+                self.constants.insert(binding.name.to_string());
+            }
+            if binding.local
+                && let Some(locals) = self.locals.last_mut()
+            {
+                locals.remove(binding.name);
+            }
+        }
+    }
+
+    // Whether `value` matches `pattern`, pushing the bound values in the order
+    // of Pattern::bindings. Shared with the bytecode VM
+    pub(crate) fn match_pattern(pattern: &Pattern, value: &Value, bound: &mut Vec<Value>) -> bool {
+        match (&pattern.kind, value) {
+            (PatternKind::Wildcard, _) => true,
+            (PatternKind::Binding(_), value) => {
+                bound.push(value.clone());
+                true
+            }
+            (PatternKind::Number(p), Value::Number(n)) => p.value == *n,
+            (PatternKind::String(p), Value::String(s)) => p == s,
+            (PatternKind::Boolean(p), Value::Boolean(b)) => p == b,
+            (PatternKind::Null, Value::Null) => true,
+            (PatternKind::List { items: patterns, rest }, Value::List(items)) => {
+                let fits = match rest {
+                    Some(_) => items.len() >= patterns.len(),
+                    None => items.len() == patterns.len(),
+                };
+                if !fits || !patterns.iter().zip(items).all(|(pattern, item)| Self::match_pattern(pattern, item, bound)) {
+                    return false;
+                }
+                match rest {
+                    Some(rest) => Self::match_pattern(rest, &Value::List(items[patterns.len()..].to_vec()), bound),
+                    None => true,
+                }
+            }
+            (PatternKind::Map(patterns), Value::Map(fields)) => patterns.iter().all(|(name, pattern)| {
+                fields.get(name).is_some_and(|field| Self::match_pattern(pattern, field, bound))
+            }),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn guard_error(value: &Value) -> MuError {
+        MuError::new(ErrorKind::TypeError, format!("Match guard must be a bool, got {}", value))
+    }
+
+    pub(crate) fn no_match_error(value: &Value) -> MuError {
+        MuError::new(ErrorKind::MatchError, format!("No match arm for value: {}", value))
+    }
+
+    // A variable holding a function, then a `func`, then a builtin
+    fn function_named(&self, name: &str, span: Span) -> Result<Callable> {
+        let callee = match self.variables.get(name) {
+            Some(Value::Function(callee)) => Some(callee.clone()),
+            _ => None,
+        };
+        callee
+            .or_else(|| self.functions.get(name).map(|function| Callable::Closure(function.clone())))
+            .or_else(|| builtins::lookup(name).map(Callable::Builtin))
+            .ok_or_else(|| MuError::new(ErrorKind::UndefinedFunction, format!("Undefined function: {}", name)).at(span).into())
+    }
+
+    fn eval_callee(&mut self, callee: &Expr, span: Span) -> Result<Callable> {
+        match self.eval_expr(callee)? {
+            Value::Function(callee) => Ok(callee),
+            other => Err(Self::not_callable_error(&other).at(span).into()),
+        }
+    }
+
+    // The argument count is checked before the arguments are evaluated
+    fn eval_args(&mut self, callee: &Callable, name: &str, args: &[Expr], span: Span) -> Result<Vec<Value>> {
+        if args.len() != callee.arity() {
+            return Err(Self::arity_error(name, callee.arity(), args.len()).at(span).into());
+        }
+
+        let mut arg_values = Vec::with_capacity(args.len());
+        for arg in args {
+            arg_values.push(self.eval_expr(arg)?);
+        }
+        Ok(arg_values)
+    }
+
+    // Run a function with evaluated arguments, `name` is the one it was called by
+    fn invoke(&mut self, callee: &Callable, name: &str, args: Vec<Value>, span: Span) -> Result<Value> {
+        if args.len() != callee.arity() {
+            return Err(Self::arity_error(name, callee.arity(), args.len()).into());
+        }
+
+        match callee {
+            Callable::Builtin(builtin) => self.run_builtin(builtin, args, span).map_err(|e| Self::located(e, span)),
+            Callable::Closure(closure) => self.call_closure(closure, name, args, span),
+            Callable::Compiled { .. } => Err(MuError::new(ErrorKind::TypeError, "Invalid call to a function compiled for the VM").into()),
+        }
+    }
+
+    // The errors of a builtin only get the span at the outermost builtin call,
+    // a builtin given to `map` or `filter` is called back without it
+    fn run_builtin(&mut self, builtin: &'static Builtin, args: Vec<Value>, span: Span) -> Result<Value> {
+        // functions given to `map` and `filter` are called back from here
+        builtin.call(args, &mut |function: &Callable, args: Vec<Value>| match function {
+            Callable::Builtin(builtin) if args.len() == function.arity() => self.run_builtin(builtin, args, span),
+            _ => {
+                let name = function.name().unwrap_or("fn").to_string();
+                self.invoke(function, &name, args, span)
+            }
+        })
+    }
+
+    // A call of a Mu function in tail position (the last statement of a body, an
+    // arm of a match there, the body of a `=>` lambda) is run by the loop below in
+    // place of the call that made it, so recursion does not grow the native stack.
+    // The replaced calls keep their frame in the traceback and their locals stay
+    // visible to the callee, nothing a program can observe changes
+    fn call_closure(&mut self, closure: &Arc<Closure>, name: &str, args: Vec<Value>, span: Span) -> Result<Value> {
+        // Save current variables (simple scope handling)
+        let saved_vars = self.variables.clone();
+        let saved_constants = self.constants.clone();
+        let depth = self.call_stack.len();
+
+        let mut tail = TailCalls::default();
+        let mut call = (closure.clone(), name.to_string(), args, span);
+        let result = loop {
+            let (closure, name, args, span) = call;
+
+            // a `=>` body never reaches before_statement, Ctrl-C must still stop the loop
+            if let Err(e) = interrupt::check() {
+                break Err(e);
+            }
+
+            // Bind the captured locals, then the parameters to the arguments
+            let bindings = closure.captured.iter().cloned().chain(closure.params.iter().cloned().zip(args));
+            let mut locals = HashSet::new();
+            for (param, value) in bindings {
+                self.constants.remove(&param);
+                self.variables.insert(param.clone(), value);
+                locals.insert(param);
+            }
+            self.locals.push(locals);
 
-                // Check argument count
-                if args.len() != params.len() {
-                    return Err(anyhow!(
-                        "Function '{}' expects {} arguments, got {}",
-                        name,
-                        params.len(),
-                        args.len()
-                    ));
+            // a tail call repeating the call it replaces only counts it, the stack stays flat
+            let replaced = self.call_stack.len() > depth;
+            match self.call_stack.last_mut() {
+                Some(top) if replaced && top.name == name && top.call_span == span => {
+                    top.repeated += 1;
                 }
+                _ => self.call_stack.push(Frame::new(name, span)),
+            }
 
-                // Evaluate arguments
-                let mut arg_values = Vec::new();
-                for arg in args {
-                    arg_values.push(self.eval_expr(arg)?);
+            // Execute function body
+            match self.eval_body(&closure.body, &mut tail) {
+                Ok(Tail::Value(value)) => break Ok(value),
+                Ok(Tail::Call { closure, name, args, span }) => {
+                    tail.locals.extend(self.locals.pop().unwrap_or_default());
+                    call = (closure, name, args, span);
                 }
+                Err(e) => break Err(self.with_traceback(e)),
+            }
+        };
+
+        self.call_stack.truncate(depth);
+
+        // Restore variables, keeping what the call assigned to the caller's ones
+        for (name, previous) in std::mem::take(&mut tail.bindings) {
+            match previous {
+                Some(previous) => self.variables.insert(name, previous),
+                None => self.variables.remove(&name),
+            };
+        }
+        let mut locals = self.locals.pop().unwrap_or_default();
+        locals.extend(tail.locals);
+        let mut restored = saved_vars;
+        for (name, value) in restored.iter_mut() {
+            if !locals.contains(name)
+                && let Some(current) = self.variables.remove(name)
+            {
+                *value = current;
+            }
+        }
+        self.variables = restored;
+        self.constants = saved_constants;
+
+        let value = result?;
+        for _ in 0..tail.prints {
+            writeln!(self.output, "{}", value)?;
+        }
+        Ok(value)
+    }
+
+    // Statements of a function or lambda body, the last one in tail position
+    fn eval_body(&mut self, body: &LambdaBody, tail: &mut TailCalls) -> Result<Tail> {
+        let statements = match body {
+            LambdaBody::Expr(expr) => return self.eval_tail(expr, tail),
+            LambdaBody::Block(block) => &block.statements,
+        };
+        let Some((last, rest)) = statements.split_last() else {
+            return Ok(Tail::Value(Value::Unit));
+        };
 
-                // Save current variables (simple scope handling)
-                let saved_vars = self.variables.clone();
+        for stmt in rest {
+            self.eval_statement(stmt)?;
+        }
+        let StatementKind::Expr(expr) = &last.kind else {
+            return self.eval_statement(last).map(Tail::Value);
+        };
+
+        // an expression statement prints its value, a tail call's is printed once known
+        self.before_statement(last)?;
+        let result = self.eval_tail(expr, tail)?;
+        match &result {
+            Tail::Value(value) => writeln!(self.output, "{}", value)?,
+            Tail::Call { .. } => tail.prints += 1,
+        }
+        Ok(result)
+    }
 
-                // Bind parameters to arguments
-                for (param, value) in params.iter().zip(arg_values.iter()) {
-                    self.variables.insert(param.clone(), value.clone());
+    fn eval_tail(&mut self, expr: &Expr, tail: &mut TailCalls) -> Result<Tail> {
+        let (callee, name, args) = match &expr.kind {
+            ExprKind::Call { name, args } => (self.function_named(name, expr.span)?, name.clone(), args),
+            ExprKind::Apply { callee, args } => {
+                let callee = self.eval_callee(callee, expr.span)?;
+                let name = callee.name().unwrap_or("fn").to_string();
+                (callee, name, args)
+            }
+            // the arm bindings stay in place for the call, they are put back after it returned
+            ExprKind::Match { value, arms } => {
+                let value = self.eval_expr(value)?;
+                let (arm, bindings) = self.select_arm(&value, arms, expr.span)?;
+                let result = self.eval_tail(&arm.body, tail);
+                if !matches!(result, Ok(Tail::Call { .. })) {
+                    self.unbind_arm(bindings);
+                    return result;
                 }
 
-                // Execute function body
-                let mut result = Value::Unit;
-                for stmt in &body {
-                    result = self.eval_statement(stmt)?;
+                for binding in bindings {
+                    if binding.local
+                        && let Some(locals) = self.locals.last_mut()
+                    {
+                        locals.remove(binding.name);
+                        tail.bindings.entry(binding.name.to_string()).or_insert(binding.previous);
+                    }
                 }
+                return result;
+            }
+            _ => return self.eval_expr(expr).map(Tail::Value),
+        };
+
+        let args = self.eval_args(&callee, &name, args, expr.span)?;
+        match callee {
+            Callable::Closure(closure) => Ok(Tail::Call { closure, name, args, span: expr.span }),
+            callee => self.invoke(&callee, &name, args, expr.span).map(Tail::Value),
+        }
+    }
+
+    // Messages shared with the bytecode VM
+    pub(crate) fn arity_error(name: &str, expected: usize, got: usize) -> MuError {
+        MuError::new(ErrorKind::ArgumentError, format!("Function '{}' expects {} arguments, got {}", name, expected, got))
+    }
+
+    pub(crate) fn not_callable_error(value: &Value) -> MuError {
+        MuError::new(ErrorKind::TypeError, format!("Cannot call {}, it is not a function", value))
+    }
 
-                // Restore variables
-                self.variables = saved_vars;
+    // An error gets the span of the expression or the builtin call that failed,
+    // the ones raised by a function a builtin called back already have theirs.
+    // Errors of a builtin are plain errors, they are of the kind Error
+    pub(crate) fn located(err: anyhow::Error, span: Span) -> anyhow::Error {
+        if err.is::<Exit>() || err.is::<Halt>() || err.is::<RuntimeError>() || err.is::<Thrown>() {
+            return err;
+        }
+        match err.downcast::<MuError>() {
+            Ok(err) if err.span.is_some() => err.into(),
+            Ok(err) => err.at(span).into(),
+            Err(err) => MuError::new(ErrorKind::Error, err.to_string()).at(span).into(),
+        }
+    }
 
-                Ok(result)
+    // Variable changed by an assignment and the keys leading to the changed
+    // item: `users[0].age` is ("users", [0, "age"]), fields are string keys
+    fn eval_assign_target(&mut self, target: &Expr) -> Result<(String, Vec<Value>)> {
+        match &target.kind {
+            ExprKind::Identifier(name) => Ok((name.clone(), Vec::new())),
+            ExprKind::Index { object, index } => {
+                let (name, mut keys) = self.eval_assign_target(object)?;
+                keys.push(self.eval_expr(index)?);
+                Ok((name, keys))
             }
-            Expr::FieldAccess { .. } => {
-                println!("Field access (not yet implemented)");
-                Ok(Value::Unit)
+            ExprKind::FieldAccess { field, object, .. } => {
+                let (name, mut keys) = self.eval_assign_target(object)?;
+                keys.push(Value::String(field.clone()));
+                Ok((name, keys))
             }
+            // rejected by the parser
+            _ => Err(MuError::new(ErrorKind::TypeError, "Invalid assignment target").at(target.span).into()),
         }
     }
 
-    fn eval_unary_op(&self, op: &UnaryOp, operand: &Value) -> Result<Value> {
+    // Stores `value` at `keys` inside `target`, combined with the current item by
+    // a compound assignment. Returns the stored value. Shared with the bytecode VM
+    pub(crate) fn assign_path(target: &mut Value, keys: &[Value], op: Option<&BinaryOp>, value: Value) -> Result<Value> {
+        let Some((key, rest)) = keys.split_first() else {
+            let value = match op {
+                Some(op) => Self::eval_binary_op(target, op, &value)?,
+                None => value,
+            };
+            *target = value.clone();
+            return Ok(value);
+        };
+
+        match (target, key) {
+            (Value::List(items), Value::Number(i)) => {
+                let i = Self::list_index(*i, items.len())?;
+                Self::assign_path(&mut items[i], rest, op, value)
+            }
+            // a plain assignment can add a field
+            (Value::Map(fields), Value::String(name)) if rest.is_empty() && op.is_none() => {
+                fields.insert(name.clone(), value.clone());
+                Ok(value)
+            }
+            (Value::Map(fields), Value::String(name)) => {
+                let field = fields.get_mut(name).ok_or_else(|| MuError::new(ErrorKind::IndexError, format!("Map has no field '{}'", name)))?;
+                Self::assign_path(field, rest, op, value)
+            }
+            (target, key) => Err(MuError::new(ErrorKind::TypeError, format!("Invalid index assignment: {:?}[{:?}]", target, key)).into()),
+        }
+    }
+
+    fn list_index(i: f64, len: usize) -> Result<usize> {
+        if i.fract() != 0.0 || i < 0.0 {
+            return Err(MuError::new(ErrorKind::IndexError, format!("List index must be a non-negative integer, got {}", i)).into());
+        }
+        if i as usize >= len {
+            return Err(MuError::new(ErrorKind::IndexError, format!("List index out of bounds: {} for a list of length {}", i, len)).into());
+        }
+        Ok(i as usize)
+    }
+
+    // Shared with the bytecode VM
+    pub(crate) fn eval_index(object: &Value, index: &Value) -> Result<Value> {
+        match (object, index) {
+            (Value::List(items), Value::Number(i)) => Ok(items[Self::list_index(*i, items.len())?].clone()),
+            (Value::Map(_), Value::String(name)) => Self::eval_field(object, name, false),
+            _ => Err(MuError::new(ErrorKind::TypeError, format!("Invalid index operation: {:?}[{:?}]", object, index)).into()),
+        }
+    }
+
+    // Missing fields are null, `?.` on null is null. Shared with the bytecode VM
+    pub(crate) fn eval_field(object: &Value, field: &str, optional: bool) -> Result<Value> {
+        match object {
+            Value::Map(fields) => Ok(fields.get(field).cloned().unwrap_or(Value::Null)),
+            Value::Null if optional => Ok(Value::Null),
+            _ => Err(MuError::new(ErrorKind::TypeError, format!("Invalid field access: {:?}.{}", object, field)).into()),
+        }
+    }
+
+    // Shared with the bytecode VM, so both backends report the same errors
+    pub(crate) fn eval_unary_op(op: &UnaryOp, operand: &Value) -> Result<Value> {
         match (op, operand) {
             (UnaryOp::Neg, Value::Number(n)) => Ok(Value::Number(-n)),
             (UnaryOp::Not, Value::Boolean(b)) => Ok(Value::Boolean(!b)),
-            _ => Err(anyhow!(
+            _ => Err(MuError::new(ErrorKind::TypeError, format!(
                 "Invalid unary operation: {:?} {:?}",
                 op,
                 operand
-            )),
+            )).into()),
         }
     }
 
-    fn eval_binary_op(&self, left: &Value, op: &BinaryOp, right: &Value) -> Result<Value> {
+    pub(crate) fn eval_binary_op(left: &Value, op: &BinaryOp, right: &Value) -> Result<Value> {
         match (left, op, right) {
             (Value::Number(l), BinaryOp::Add, Value::Number(r)) => Ok(Value::Number(l + r)),
             (Value::Number(l), BinaryOp::Sub, Value::Number(r)) => Ok(Value::Number(l - r)),
             (Value::Number(l), BinaryOp::Mul, Value::Number(r)) => Ok(Value::Number(l * r)),
             (Value::Number(l), BinaryOp::Div, Value::Number(r)) => {
                 if *r == 0.0 {
-                    Err(anyhow!("Division by zero"))
+                    Err(MuError::new(ErrorKind::DivisionByZero, "Division by zero").into())
                 } else {
                     Ok(Value::Number(l / r))
                 }
@@ -192,13 +1165,25 @@ impl Evaluator {
             (Value::Boolean(l), BinaryOp::Or, Value::Boolean(r)) => Ok(Value::Boolean(*l || *r)),
             (Value::Boolean(l), BinaryOp::Eq, Value::Boolean(r)) => Ok(Value::Boolean(l == r)),
             (Value::Boolean(l), BinaryOp::Neq, Value::Boolean(r)) => Ok(Value::Boolean(l != r)),
+
+            // anything can be compared with null
+            (Value::Null, BinaryOp::Eq, other) | (other, BinaryOp::Eq, Value::Null) => {
+                Ok(Value::Boolean(matches!(other, Value::Null)))
+            }
+            (Value::Null, BinaryOp::Neq, other) | (other, BinaryOp::Neq, Value::Null) => {
+                Ok(Value::Boolean(!matches!(other, Value::Null)))
+            }
+
+            // the evaluators short-circuit it, this is used by constant folding
+            (Value::Null, BinaryOp::Coalesce, right) => Ok(right.clone()),
+            (left, BinaryOp::Coalesce, _) => Ok(left.clone()),
             
-            _ => Err(anyhow!(
+            _ => Err(MuError::new(ErrorKind::TypeError, format!(
                 "Invalid binary operation: {:?} {:?} {:?}",
                 left,
                 op,
                 right
-            )),
+            )).into()),
         }
     }
 }
\ No newline at end of file
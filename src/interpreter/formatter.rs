@@ -0,0 +1,401 @@
+use anyhow::Result;
+
+use crate::interpreter::ast::{AST, Statement, StatementKind, Expr, ExprKind, BinaryOp, UnaryOp, LambdaBody, Param, Pattern, PatternKind, TypeAnnotation};
+use crate::interpreter::parser::Parser;
+use crate::interpreter::span::Span;
+use crate::interpreter::tokenizer::{is_identifier, Comment, Tokenizer};
+
+const INDENT: &str = "    ";
+
+// Binding strength of each operator, mirrors the parser levels
+fn precedence(op: &BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Coalesce => 0,
+        BinaryOp::Or => 1,
+        BinaryOp::And => 2,
+        BinaryOp::Eq | BinaryOp::Neq | BinaryOp::Gt | BinaryOp::Lt | BinaryOp::Gte | BinaryOp::Lte => 3,
+        BinaryOp::Add | BinaryOp::Sub => 4,
+        BinaryOp::Mul | BinaryOp::Div => 5,
+    }
+}
+
+fn op_symbol(op: &BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Eq => "==",
+        BinaryOp::Neq => "!=",
+        BinaryOp::Gt => ">",
+        BinaryOp::Lt => "<",
+        BinaryOp::Gte => ">=",
+        BinaryOp::Lte => "<=",
+        BinaryOp::And => "&&",
+        BinaryOp::Or => "||",
+        BinaryOp::Coalesce => "??",
+    }
+}
+
+// (line, col) of the first character, to order comments and statements
+fn start(span: &Span) -> (usize, usize) {
+    (span.line, span.col)
+}
+
+fn end(span: &Span) -> (usize, usize) {
+    (span.end_line, span.end_col)
+}
+
+// Pretty-prints Mu source in the canonical layout:
+// one statement per line ending with ';', four spaces of indentation,
+// single spaces around binary operators and after commas, parentheses
+// only where the precedence needs them (and around `&&` inside `||`),
+// at most one blank line in a row.
+// Comments are kept: the ones between statements stay on their own line,
+// a comment after a statement on the same line stays there, and comments
+// inside an expression are moved right before its statement.
+pub struct Formatter {
+    comments: Vec<Comment>,
+    // next comment to print
+    next: usize,
+    out: String,
+}
+
+impl Formatter {
+    pub fn format(source: &str) -> Result<String> {
+        let (tokens, comments) = Tokenizer::tokenize_with_comments(source.to_string())?;
+        let ast = Parser::new(tokens).parse()?;
+
+        Ok(Self::format_ast(&ast, comments))
+    }
+
+    pub fn format_ast(ast: &AST, comments: Vec<Comment>) -> String {
+        let mut formatter = Formatter {
+            comments,
+            next: 0,
+            out: String::new(),
+        };
+
+        formatter.write_block(&ast.statements, 0, None);
+        formatter.out
+    }
+
+    fn peek_comment(&self) -> Option<&Comment> {
+        self.comments.get(self.next)
+    }
+
+    // Writes the statements of a block, one per line, and the comments
+    // that start before `until` (the closing '}'), or all of them at top level
+    fn write_block(&mut self, statements: &[Statement], depth: usize, until: Option<(usize, usize)>) {
+        // last source line written, None at the start of the block
+        let mut last_line: Option<usize> = None;
+
+        for stmt in statements {
+            // function bodies and try blocks print their own comments, for the
+            // other statements the ones inside the expression go before it
+            let leading_until = match stmt.kind {
+                StatementKind::FuncDef { .. } | StatementKind::Try { .. } => start(&stmt.span),
+                _ => end(&stmt.span),
+            };
+            self.write_comments_before(leading_until, depth, &mut last_line);
+
+            self.blank_line_if_needed(stmt.span.line, last_line);
+            self.write_indent(depth);
+            self.write_statement(stmt, depth);
+            last_line = Some(stmt.span.end_line);
+
+            // comment after the statement on the same line
+            if let Some(comment) = self.peek_comment()
+                && comment.span.line == stmt.span.end_line
+                && until.is_none_or(|until| start(&comment.span) < until)
+            {
+                let comment = comment.clone();
+                self.out.push(' ');
+                self.out.push_str(&comment.text);
+                self.next += 1;
+                last_line = Some(comment.span.end_line);
+            }
+            self.out.push('\n');
+        }
+
+        self.write_comments_before(until.unwrap_or((usize::MAX, usize::MAX)), depth, &mut last_line);
+    }
+
+    fn write_comments_before(&mut self, position: (usize, usize), depth: usize, last_line: &mut Option<usize>) {
+        while let Some(comment) = self.peek_comment()
+            && start(&comment.span) < position
+        {
+            let comment = comment.clone();
+            self.blank_line_if_needed(comment.span.line, *last_line);
+            self.write_indent(depth);
+            self.out.push_str(&comment.text);
+            self.out.push('\n');
+            self.next += 1;
+            *last_line = Some(comment.span.end_line);
+        }
+    }
+
+    // blank lines in the source are kept, several of them become one
+    fn blank_line_if_needed(&mut self, line: usize, last_line: Option<usize>) {
+        if let Some(last_line) = last_line
+            && line > last_line + 1
+        {
+            self.out.push('\n');
+        }
+    }
+
+    fn write_indent(&mut self, depth: usize) {
+        for _ in 0..depth {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    // Writes the statement without the final newline
+    fn write_statement(&mut self, stmt: &Statement, depth: usize) {
+        match &stmt.kind {
+            StatementKind::Let { name, ty, value, constant } => {
+                let value = Self::expr(value);
+                let keyword = if *constant { "const" } else { "let" };
+                self.out.push_str(&format!("{} {}{} = {};", keyword, name, Self::annotation(": ", ty.as_ref()), value));
+            }
+            StatementKind::Assign { target, op, value } => {
+                let op = op.as_ref().map(op_symbol).unwrap_or("");
+                self.out.push_str(&format!("{} {}= {};", Self::expr(target), op, Self::expr(value)));
+            }
+            StatementKind::FuncDef { name, params, return_type, body } => {
+                let params: Vec<String> = params.iter().map(Self::param).collect();
+                self.out.push_str(&format!(
+                    "func {}({}){} ",
+                    name,
+                    params.join(", "),
+                    Self::annotation(" -> ", return_type.as_ref())
+                ));
+                self.write_braces(body, depth, stmt.span);
+            }
+            StatementKind::Try { body, catch, finally } => {
+                self.out.push_str("try ");
+                self.write_braces(&body.statements, depth, body.span);
+                if let Some(catch) = catch {
+                    self.out.push_str(&format!(" catch ({}) ", catch.name));
+                    self.write_braces(&catch.body.statements, depth, catch.body.span);
+                }
+                if let Some(finally) = finally {
+                    self.out.push_str(" finally ");
+                    self.write_braces(&finally.statements, depth, finally.span);
+                }
+            }
+            StatementKind::Throw { value } => {
+                self.out.push_str(&format!("throw {};", Self::expr(value)));
+            }
+            StatementKind::Expr(expr) => {
+                self.out.push_str(&format!("{};", Self::expr(expr)));
+            }
+            StatementKind::Where { condition } => {
+                self.out.push_str(&format!("where {};", Self::expr(condition)));
+            }
+            StatementKind::Import { path, alias } => {
+                self.out.push_str(&format!("import \"{}\" as {};", path, alias));
+            }
+            // the parser does not produce these yet
+            StatementKind::Get { field, filter } => {
+                self.out.push_str(&format!("get {}", Self::expr(field)));
+                if let Some(filter) = filter {
+                    self.out.push_str(&format!(" where {}", Self::expr(filter)));
+                }
+                self.out.push(';');
+            }
+            StatementKind::Set { field, key, value } => {
+                self.out.push_str(&format!("set {} {} = {};", Self::expr(field), key, Self::expr(value)));
+            }
+            StatementKind::Delete { field, key } => {
+                self.out.push_str(&format!("delete {} {};", Self::expr(field), key));
+            }
+        }
+    }
+
+    // `{ ... }` ending at the end of `span`, with the comments before the closing '}'
+    fn write_braces(&mut self, body: &[Statement], depth: usize, span: Span) {
+        let close = (span.end_line, span.end_col.saturating_sub(1));
+        let has_comments = self.peek_comment().is_some_and(|c| start(&c.span) < close);
+
+        if body.is_empty() && !has_comments {
+            self.out.push_str("{}");
+            return;
+        }
+
+        self.out.push_str("{\n");
+        self.write_block(body, depth + 1, Some(close));
+        self.write_indent(depth);
+        self.out.push('}');
+    }
+
+    fn annotation(prefix: &str, annotation: Option<&TypeAnnotation>) -> String {
+        match annotation {
+            Some(annotation) => format!("{}{}", prefix, annotation.ty),
+            None => String::new(),
+        }
+    }
+
+    fn param(param: &Param) -> String {
+        format!("{}{}", param.name, Self::annotation(": ", param.ty.as_ref()))
+    }
+
+    pub(crate) fn expr(expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Number(n) => n.to_string(),
+            // the literal keeps its escapes as written
+            ExprKind::String(s) => format!("\"{}\"", s),
+            ExprKind::Boolean(b) => b.to_string(),
+            ExprKind::Null => "null".to_string(),
+            ExprKind::Identifier(name) => name.clone(),
+            ExprKind::FieldAccess { field, object, optional } => {
+                let dot = if *optional { "?." } else { "." };
+                format!("{}{}{}", Self::postfix_object(object), dot, field)
+            }
+            ExprKind::Call { name, args } => {
+                let args: Vec<String> = args.iter().map(Self::expr).collect();
+                format!("{}({})", name, args.join(", "))
+            }
+            ExprKind::Apply { callee, args } => {
+                let args: Vec<String> = args.iter().map(Self::expr).collect();
+                format!("{}({})", Self::postfix_object(callee), args.join(", "))
+            }
+            ExprKind::Lambda { params, return_type, body } => {
+                let params: Vec<String> = params.iter().map(Self::param).collect();
+                let signature = format!("fn({}){}", params.join(", "), Self::annotation(" -> ", return_type.as_ref()));
+                match body {
+                    LambdaBody::Expr(value) => format!("{} => {}", signature, Self::expr(value)),
+                    LambdaBody::Block(block) => format!("{} {}", signature, Self::inline_block(&block.statements)),
+                }
+            }
+            ExprKind::List(items) => {
+                let items: Vec<String> = items.iter().map(Self::expr).collect();
+                format!("[{}]", items.join(", "))
+            }
+            ExprKind::Map(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", Self::field_name(name), Self::expr(value)))
+                    .collect();
+                format!("{{{}}}", fields.join(", "))
+            }
+            ExprKind::Index { object, index } => {
+                format!("{}[{}]", Self::postfix_object(object), Self::expr(index))
+            }
+            ExprKind::Unary { op, operand } => {
+                let symbol = match op {
+                    UnaryOp::Neg => "-",
+                    UnaryOp::Not => "!",
+                };
+                format!("{}{}", symbol, Self::operand(operand, None, false))
+            }
+            ExprKind::Binary { left, op, right } => format!(
+                "{} {} {}",
+                Self::operand(left, Some(op), false),
+                op_symbol(op),
+                Self::operand(right, Some(op), true)
+            ),
+            ExprKind::Match { value, arms } => {
+                let arms: Vec<String> = arms
+                    .iter()
+                    .map(|arm| match &arm.guard {
+                        Some(guard) => format!("{} if {} => {}", Self::pattern(&arm.pattern), Self::expr(guard), Self::expr(&arm.body)),
+                        None => format!("{} => {}", Self::pattern(&arm.pattern), Self::expr(&arm.body)),
+                    })
+                    .collect();
+                format!("match {} {{ {} }}", Self::expr(value), arms.join(", "))
+            }
+        }
+    }
+
+    // A lambda body stays on the line of its expression: `{ let y = x; y; }`
+    fn inline_block(statements: &[Statement]) -> String {
+        if statements.is_empty() {
+            return "{}".to_string();
+        }
+
+        let mut formatter = Formatter {
+            comments: Vec::new(),
+            next: 0,
+            out: String::new(),
+        };
+        formatter.write_block(statements, 0, None);
+
+        let lines: Vec<&str> = formatter.out.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+        format!("{{ {} }}", lines.join(" "))
+    }
+
+    fn pattern(pattern: &Pattern) -> String {
+        match &pattern.kind {
+            PatternKind::Wildcard => "_".to_string(),
+            PatternKind::Binding(name) => name.clone(),
+            PatternKind::Number(n) => n.to_string(),
+            PatternKind::String(s) => format!("\"{}\"", s),
+            PatternKind::Boolean(b) => b.to_string(),
+            PatternKind::Null => "null".to_string(),
+            PatternKind::List { items, rest } => {
+                let mut items: Vec<String> = items.iter().map(Self::pattern).collect();
+                if let Some(rest) = rest {
+                    match &rest.kind {
+                        PatternKind::Binding(name) => items.push(format!("..{}", name)),
+                        _ => items.push("..".to_string()),
+                    }
+                }
+                format!("[{}]", items.join(", "))
+            }
+            // `{name}` when the field binds its own name
+            PatternKind::Map(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(name, field)| match &field.kind {
+                        PatternKind::Binding(binding) if binding == name => name.clone(),
+                        _ => format!("{}: {}", Self::field_name(name), Self::pattern(field)),
+                    })
+                    .collect();
+                format!("{{{}}}", fields.join(", "))
+            }
+        }
+    }
+
+    // Map keys that are not identifiers keep their quotes
+    fn field_name(name: &str) -> String {
+        if is_identifier(name) {
+            name.to_string()
+        } else {
+            format!("\"{}\"", name)
+        }
+    }
+
+    // Indexing and field access bind tighter than the unary operators too
+    fn postfix_object(object: &Expr) -> String {
+        match object.kind {
+            ExprKind::Unary { .. } => format!("({})", Self::expr(object)),
+            _ => Self::operand(object, None, false),
+        }
+    }
+
+    // Operand of `parent`, None for unary operators and field access which bind
+    // tighter than any binary operator. Operators are left associative, so a right
+    // operand at the same level keeps its parentheses: a - (b - c).
+    // `&&` inside `||` keeps them too, they are easier to read.
+    // A lambda always has them, its body would take what follows
+    fn operand(expr: &Expr, parent: Option<&BinaryOp>, right: bool) -> String {
+        let needs_parens = match (&expr.kind, parent) {
+            (ExprKind::Lambda { .. }, _) => true,
+            (ExprKind::Binary { .. }, None) => true,
+            (ExprKind::Binary { op, .. }, Some(parent)) => {
+                let (inner, outer) = (precedence(op), precedence(parent));
+                inner < outer
+                    || (right && inner == outer)
+                    || (*op == BinaryOp::And && *parent == BinaryOp::Or)
+            }
+            _ => false,
+        };
+
+        if needs_parens {
+            format!("({})", Self::expr(expr))
+        } else {
+            Self::expr(expr)
+        }
+    }
+}
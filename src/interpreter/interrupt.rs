@@ -0,0 +1,34 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{Result, anyhow};
+
+use crate::interpreter::evaluator::Halt;
+
+// Set by Ctrl-C, cleared by whoever reports it
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Make Ctrl-C set the interrupt flag instead of killing the process.
+// Only the REPLs install it, `mu run` keeps the default behaviour
+pub fn install_handler() -> Result<()> {
+    ctrlc::set_handler(interrupt)
+        .map_err(|e| anyhow!("Failed to install the Ctrl-C handler: {}", e))
+}
+
+// Ask the running code to stop at the next check
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+// Drop an interrupt that arrived while nothing was running
+pub fn clear() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+// Polled by long running code, fails once per interrupt.
+// The error is a Halt, a `try` in the running code cannot catch it
+pub fn check() -> Result<()> {
+    if INTERRUPTED.swap(false, Ordering::SeqCst) {
+        return Err(Halt { message: "Interrupted".to_string() }.into());
+    }
+    Ok(())
+}
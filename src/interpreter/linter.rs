@@ -0,0 +1,526 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter as FmtFormatter, Result as FmtResult};
+
+use serde::Serialize;
+
+use crate::interpreter::analyzer::{Analyzer, NON_EXHAUSTIVE_MATCH, UNREACHABLE_ARM};
+use crate::interpreter::ast::{AST, Statement, StatementKind, Expr, ExprKind, BinaryOp, LambdaBody, Param};
+use crate::interpreter::diagnostic::{Diagnostic, Severity};
+use crate::interpreter::formatter::Formatter;
+use crate::interpreter::optimizer::Optimizer;
+use crate::interpreter::parser::Parser;
+use crate::interpreter::span::Span;
+use crate::interpreter::tokenizer::Tokenizer;
+use crate::interpreter::typechecker::TypeChecker;
+
+// Style and correctness rules of `mu check`, each one can be allowed or denied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rule {
+    // a variable or parameter that is never read
+    UnusedVariable,
+    // a function that is never called, or only calls itself
+    UnusedFunction,
+    // a `let` in a function body with the name of one of its parameters
+    ShadowedParameter,
+    // `premium == true`, `active != false` ...
+    BoolComparison,
+    // a `where` or `get` condition that does not depend on the record
+    ConstantCondition,
+    // a `match` that no arm without a guard covers completely
+    NonExhaustiveMatch,
+    // a match arm after one that matches every value
+    UnreachableArm,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 7] = [
+        Rule::UnusedVariable,
+        Rule::UnusedFunction,
+        Rule::ShadowedParameter,
+        Rule::BoolComparison,
+        Rule::ConstantCondition,
+        Rule::NonExhaustiveMatch,
+        Rule::UnreachableArm,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused-variable",
+            Rule::UnusedFunction => "unused-function",
+            Rule::ShadowedParameter => "shadowed-parameter",
+            Rule::BoolComparison => "bool-comparison",
+            Rule::ConstantCondition => "constant-condition",
+            Rule::NonExhaustiveMatch => "non-exhaustive-match",
+            Rule::UnreachableArm => "unreachable-arm",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Self::ALL.into_iter().find(|rule| rule.name() == name)
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut FmtFormatter) -> FmtResult {
+        write!(f, "{}", self.name())
+    }
+}
+
+// Every rule is a warning unless it is allowed (not reported) or denied (an error)
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    pub allowed: HashSet<Rule>,
+    pub denied: HashSet<Rule>,
+}
+
+impl LintConfig {
+    fn severity(&self, rule: Rule) -> Option<Severity> {
+        if self.allowed.contains(&rule) {
+            None
+        } else if self.denied.contains(&rule) {
+            Some(Severity::Error)
+        } else {
+            Some(Severity::Warning)
+        }
+    }
+}
+
+// A problem reported by `mu check`.
+// Syntax and semantic errors have no rule, they cannot be allowed
+#[derive(Debug, Clone)]
+pub struct Lint {
+    pub rule: Option<Rule>,
+    pub diagnostic: Diagnostic,
+}
+
+// A name introduced by `let`, a function parameter or a match pattern
+struct Binding {
+    name: String,
+    span: Span,
+    is_param: bool,
+    used: bool,
+}
+
+// Runs the lint rules on a parsed program.
+// Variables follow the scoping of the Analyzer: function locals are
+// reported when the body never reads them, top level variables when
+// neither the top level code nor any function body reads them.
+pub struct Linter {
+    config: LintConfig,
+    scopes: Vec<Vec<Binding>>,
+    // parameters of the function being linted
+    params: Vec<String>,
+    // names read anywhere inside a function body, functions may use any global
+    function_reads: HashSet<String>,
+    // function name -> calls from outside its own body
+    calls: HashMap<String, usize>,
+    lints: Vec<Lint>,
+}
+
+impl Linter {
+    // Syntax errors, semantic errors and lints of a whole source file, in source order
+    pub fn check_source(source: &str, config: &LintConfig) -> Vec<Lint> {
+        let tokens = match Tokenizer::tokenize(source.to_string()) {
+            Ok(tokens) => tokens,
+            // the tokenizer stops at the first error and has no span for it
+            Err(e) => return vec![Lint { rule: None, diagnostic: Diagnostic::error(e.to_string(), Span::default()) }],
+        };
+
+        let (ast, syntax_errors) = Parser::new(tokens).parse_with_diagnostics();
+        let mut lints: Vec<Lint> = syntax_errors
+            .into_iter()
+            .map(|diagnostic| Lint { rule: None, diagnostic })
+            .collect();
+
+        // a program with syntax errors is incomplete, only those are reported
+        if !lints.is_empty() {
+            return lints;
+        }
+
+        // the analyzer warnings are covered by the unused-variable and match rules
+        let mut diagnostics = Analyzer::analyze(&ast);
+        diagnostics.extend(TypeChecker::check(&ast));
+        lints.extend(diagnostics
+            .into_iter()
+            .filter(|d| d.is_error())
+            .map(|diagnostic| Lint { rule: None, diagnostic }));
+
+        lints.extend(Self::lint(&ast, config));
+        lints.sort_by_key(|lint| (lint.diagnostic.span.line, lint.diagnostic.span.col));
+        lints
+    }
+
+    pub fn lint(ast: &AST, config: &LintConfig) -> Vec<Lint> {
+        let mut linter = Linter {
+            config: config.clone(),
+            scopes: vec![Vec::new()],
+            params: Vec::new(),
+            function_reads: HashSet::new(),
+            calls: HashMap::new(),
+            lints: Vec::new(),
+        };
+
+        linter.collect_usage(&ast.statements, None);
+
+        for statement in &ast.statements {
+            linter.lint_statement(statement);
+        }
+
+        let globals = linter.scopes.pop().unwrap_or_default();
+        let globals = globals
+            .into_iter()
+            .filter(|binding| !linter.function_reads.contains(&binding.name))
+            .collect();
+        linter.report_unused(globals);
+
+        linter.report_unused_functions(&ast.statements);
+        linter.lints.sort_by_key(|lint| (lint.diagnostic.span.line, lint.diagnostic.span.col));
+        linter.lints
+    }
+
+    fn report(&mut self, rule: Rule, message: String, span: Span) {
+        if let Some(severity) = self.config.severity(rule) {
+            self.lints.push(Lint {
+                rule: Some(rule),
+                diagnostic: Diagnostic { severity, message, span },
+            });
+        }
+    }
+
+    // First pass: calls to each function and names read by function bodies
+    fn collect_usage(&mut self, statements: &[Statement], function: Option<&str>) {
+        for statement in statements {
+            match &statement.kind {
+                StatementKind::FuncDef { name, body, .. } => self.collect_usage(body, Some(name)),
+                StatementKind::Let { value, .. } => self.collect_expr_usage(value, function),
+                StatementKind::Assign { target, value, .. } => {
+                    self.collect_expr_usage(target, function);
+                    self.collect_expr_usage(value, function);
+                }
+                StatementKind::Expr(expr) => self.collect_expr_usage(expr, function),
+                StatementKind::Where { condition } => self.collect_expr_usage(condition, function),
+                StatementKind::Get { field, filter } => {
+                    self.collect_expr_usage(field, function);
+                    if let Some(filter) = filter {
+                        self.collect_expr_usage(filter, function);
+                    }
+                }
+                StatementKind::Set { field, value, .. } => {
+                    self.collect_expr_usage(field, function);
+                    self.collect_expr_usage(value, function);
+                }
+                StatementKind::Delete { field, .. } => self.collect_expr_usage(field, function),
+                StatementKind::Import { .. } => {}
+                StatementKind::Try { body, catch, finally } => {
+                    self.collect_usage(&body.statements, function);
+                    if let Some(catch) = catch {
+                        self.collect_usage(&catch.body.statements, function);
+                    }
+                    if let Some(finally) = finally {
+                        self.collect_usage(&finally.statements, function);
+                    }
+                }
+                StatementKind::Throw { value } => self.collect_expr_usage(value, function),
+            }
+        }
+    }
+
+    fn collect_expr_usage(&mut self, expr: &Expr, function: Option<&str>) {
+        match &expr.kind {
+            ExprKind::Number(_) | ExprKind::String(_) | ExprKind::Boolean(_) | ExprKind::Null => {}
+            ExprKind::Identifier(name) => {
+                if function.is_some() {
+                    self.function_reads.insert(name.clone());
+                }
+                // a function passed as a value is used too
+                if function != Some(name.as_str()) {
+                    *self.calls.entry(name.clone()).or_insert(0) += 1;
+                }
+            }
+            ExprKind::Binary { left, right, .. } => {
+                self.collect_expr_usage(left, function);
+                self.collect_expr_usage(right, function);
+            }
+            ExprKind::Unary { operand, .. } => self.collect_expr_usage(operand, function),
+            ExprKind::FieldAccess { object, .. } => self.collect_expr_usage(object, function),
+            ExprKind::Call { name, args } => {
+                // recursion alone does not make a function used
+                if function != Some(name.as_str()) {
+                    *self.calls.entry(name.clone()).or_insert(0) += 1;
+                }
+                for arg in args {
+                    self.collect_expr_usage(arg, function);
+                }
+            }
+            ExprKind::List(items) => {
+                for item in items {
+                    self.collect_expr_usage(item, function);
+                }
+            }
+            ExprKind::Map(fields) => {
+                for (_, value) in fields {
+                    self.collect_expr_usage(value, function);
+                }
+            }
+            ExprKind::Index { object, index } => {
+                self.collect_expr_usage(object, function);
+                self.collect_expr_usage(index, function);
+            }
+            ExprKind::Apply { callee, args } => {
+                self.collect_expr_usage(callee, function);
+                for arg in args {
+                    self.collect_expr_usage(arg, function);
+                }
+            }
+            ExprKind::Lambda { body, .. } => match body {
+                LambdaBody::Expr(value) => self.collect_expr_usage(value, function),
+                LambdaBody::Block(block) => self.collect_usage(&block.statements, function),
+            },
+            ExprKind::Match { value, arms } => {
+                self.collect_expr_usage(value, function);
+                for arm in arms {
+                    if let Some(guard) = &arm.guard {
+                        self.collect_expr_usage(guard, function);
+                    }
+                    self.collect_expr_usage(&arm.body, function);
+                }
+            }
+        }
+    }
+
+    fn lint_statement(&mut self, stmt: &Statement) {
+        match &stmt.kind {
+            StatementKind::Let { name, value, .. } => {
+                self.lint_expr(value);
+
+                if self.params.contains(name) {
+                    self.report(
+                        Rule::ShadowedParameter,
+                        format!("Variable '{}' shadows a parameter with the same name", name),
+                        stmt.span,
+                    );
+                }
+                self.declare(name, stmt.span, false);
+            }
+            StatementKind::Assign { target, value, .. } => {
+                self.lint_expr(target);
+                self.lint_expr(value);
+            }
+            StatementKind::FuncDef { params, body, .. } => self.lint_function(params, body),
+            StatementKind::Expr(expr) => self.lint_expr(expr),
+            StatementKind::Where { condition } => self.lint_condition("where", condition),
+            StatementKind::Get { field, filter } => {
+                self.lint_expr(field);
+                if let Some(filter) = filter {
+                    self.lint_condition("get", filter);
+                }
+            }
+            StatementKind::Set { field, value, .. } => {
+                self.lint_expr(field);
+                self.lint_expr(value);
+            }
+            StatementKind::Delete { field, .. } => self.lint_expr(field),
+            StatementKind::Import { .. } => {}
+            StatementKind::Try { body, catch, finally } => {
+                for stmt in &body.statements {
+                    self.lint_statement(stmt);
+                }
+                if let Some(catch) = catch {
+                    self.declare(&catch.name, catch.span, false);
+                    for stmt in &catch.body.statements {
+                        self.lint_statement(stmt);
+                    }
+                }
+                if let Some(finally) = finally {
+                    for stmt in &finally.statements {
+                        self.lint_statement(stmt);
+                    }
+                }
+            }
+            StatementKind::Throw { value } => self.lint_expr(value),
+        }
+    }
+
+    fn lint_function(&mut self, params: &[Param], body: &[Statement]) {
+        // a function body does not see the locals of the code that defines it
+        let saved_scopes = std::mem::replace(&mut self.scopes, vec![Vec::new()]);
+        let saved_params = std::mem::replace(&mut self.params, params.iter().map(|p| p.name.clone()).collect());
+
+        for param in params {
+            self.declare(&param.name, param.span, true);
+        }
+        for stmt in body {
+            self.lint_statement(stmt);
+        }
+
+        let scope = self.scopes.pop().unwrap_or_default();
+        self.report_unused(scope);
+
+        self.scopes = saved_scopes;
+        self.params = saved_params;
+    }
+
+    // Unlike a function body, a lambda body sees the bindings around it
+    fn lint_lambda(&mut self, params: &[Param], body: &LambdaBody) {
+        self.scopes.push(Vec::new());
+        let saved_params = std::mem::replace(&mut self.params, params.iter().map(|p| p.name.clone()).collect());
+
+        for param in params {
+            self.declare(&param.name, param.span, true);
+        }
+        match body {
+            LambdaBody::Expr(value) => self.lint_expr(value),
+            LambdaBody::Block(block) => {
+                for stmt in &block.statements {
+                    self.lint_statement(stmt);
+                }
+            }
+        }
+
+        let scope = self.scopes.pop().unwrap_or_default();
+        self.report_unused(scope);
+        self.params = saved_params;
+    }
+
+    fn lint_condition(&mut self, keyword: &str, condition: &Expr) {
+        self.lint_expr(condition);
+
+        if let ExprKind::Boolean(value) = Optimizer::fold(condition.clone()).kind {
+            self.report(
+                Rule::ConstantCondition,
+                format!("Condition of '{}' is always {}", keyword, value),
+                condition.span,
+            );
+        }
+    }
+
+    fn lint_expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Number(_) | ExprKind::String(_) | ExprKind::Boolean(_) | ExprKind::Null => {}
+            ExprKind::Identifier(name) => self.resolve(name),
+            ExprKind::Binary { left, op, right } => {
+                self.lint_bool_comparison(expr, left, op, right);
+                self.lint_expr(left);
+                self.lint_expr(right);
+            }
+            ExprKind::Unary { operand, .. } => self.lint_expr(operand),
+            ExprKind::FieldAccess { object, .. } => self.lint_expr(object),
+            // the name can be a variable holding a function
+            ExprKind::Call { name, args } => {
+                self.resolve(name);
+                for arg in args {
+                    self.lint_expr(arg);
+                }
+            }
+            ExprKind::List(items) => {
+                for item in items {
+                    self.lint_expr(item);
+                }
+            }
+            ExprKind::Apply { callee, args } => {
+                self.lint_expr(callee);
+                for arg in args {
+                    self.lint_expr(arg);
+                }
+            }
+            ExprKind::Lambda { params, body, .. } => self.lint_lambda(params, body),
+            ExprKind::Map(fields) => {
+                for (_, value) in fields {
+                    self.lint_expr(value);
+                }
+            }
+            ExprKind::Index { object, index } => {
+                self.lint_expr(object);
+                self.lint_expr(index);
+            }
+            ExprKind::Match { value, arms } => {
+                self.lint_expr(value);
+
+                for arm in arms {
+                    self.scopes.push(Vec::new());
+                    for (name, span) in arm.pattern.bindings() {
+                        self.declare(name, span, false);
+                    }
+                    if let Some(guard) = &arm.guard {
+                        self.lint_expr(guard);
+                    }
+                    self.lint_expr(&arm.body);
+                    let scope = self.scopes.pop().unwrap_or_default();
+                    self.report_unused(scope);
+                }
+
+                for arm in Analyzer::unreachable_arms(arms) {
+                    self.report(Rule::UnreachableArm, UNREACHABLE_ARM.to_string(), arm.pattern.span);
+                }
+                if !Analyzer::is_exhaustive(arms) {
+                    self.report(Rule::NonExhaustiveMatch, NON_EXHAUSTIVE_MATCH.to_string(), expr.span);
+                }
+            }
+        }
+    }
+
+    fn lint_bool_comparison(&mut self, expr: &Expr, left: &Expr, op: &BinaryOp, right: &Expr) {
+        if !matches!(op, BinaryOp::Eq | BinaryOp::Neq) {
+            return;
+        }
+
+        let (literal, other) = match (&left.kind, &right.kind) {
+            // both literals is a constant, not a style problem
+            (ExprKind::Boolean(_), ExprKind::Boolean(_)) => return,
+            (ExprKind::Boolean(b), _) => (*b, right),
+            (_, ExprKind::Boolean(b)) => (*b, left),
+            _ => return,
+        };
+
+        // `x == true` and `x != false` are just `x`
+        let message = if literal == (*op == BinaryOp::Eq) {
+            format!(
+                "Comparison with boolean literal: '{}' can be written as '{}'",
+                Formatter::expr(expr),
+                Formatter::expr(other)
+            )
+        } else {
+            format!("Comparison with boolean literal: '{}'", Formatter::expr(expr))
+        };
+        self.report(Rule::BoolComparison, message, expr.span);
+    }
+
+    fn declare(&mut self, name: &str, span: Span, is_param: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Binding { name: name.to_string(), span, is_param, used: false });
+        }
+    }
+
+    fn resolve(&mut self, name: &str) {
+        if let Some(binding) = self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.iter_mut().rev().find(|b| b.name == name))
+        {
+            binding.used = true;
+        }
+    }
+
+    fn report_unused(&mut self, scope: Vec<Binding>) {
+        for binding in scope {
+            if binding.used || binding.name.starts_with('_') {
+                continue;
+            }
+
+            let what = if binding.is_param { "parameter" } else { "variable" };
+            self.report(Rule::UnusedVariable, format!("Unused {}: {}", what, binding.name), binding.span);
+        }
+    }
+
+    fn report_unused_functions(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            if let StatementKind::FuncDef { name, body, .. } = &statement.kind {
+                if !name.starts_with('_') && !self.calls.contains_key(name) {
+                    self.report(Rule::UnusedFunction, format!("Function '{}' is never called", name), statement.span);
+                }
+                self.report_unused_functions(body);
+            }
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+    LogMessage, Notification as _, PublishDiagnostics,
+};
+use lsp_types::request::{Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionResponse, DiagnosticSeverity,
+    DocumentSymbolResponse, GotoDefinitionResponse, Hover, HoverContents, HoverProviderCapability,
+    Location, LogMessageParams, MarkupContent, MarkupKind, MessageType, OneOf, Position, PublishDiagnosticsParams, Range,
+    SaveOptions, ServerCapabilities, TextDocumentPositionParams, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextDocumentSyncOptions, TextDocumentSyncSaveOptions, Url,
+};
+use serde::de::DeserializeOwned;
+
+use crate::interpreter::diagnostic::{Diagnostic, Severity};
+use crate::interpreter::document::{CompletionKind, Document, DocumentSymbol, SymbolKind};
+use crate::interpreter::span::{self, Span};
+
+// Span lines and columns are 1-based and columns count chars,
+// LSP positions are 0-based and count the UTF-16 code units of the line
+fn to_range(text: &str, span: Span) -> Range {
+    Range::new(to_position(text, span.line, span.col), to_position(text, span.end_line, span.end_col))
+}
+
+fn to_position(text: &str, line: usize, col: usize) -> Position {
+    let character = span::utf16_offset(line_text(text, line), col);
+    Position::new(line.saturating_sub(1) as u32, character as u32)
+}
+
+fn from_position(text: &str, position: Position) -> (usize, usize) {
+    let line = position.line as usize + 1;
+    (line, span::col_from_utf16(line_text(text, line), position.character as usize))
+}
+
+fn line_text(text: &str, line: usize) -> &str {
+    text.split('\n').nth(line.saturating_sub(1)).unwrap_or_default()
+}
+
+fn to_lsp_diagnostic(text: &str, diagnostic: &Diagnostic) -> lsp_types::Diagnostic {
+    let severity = match diagnostic.severity {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+    };
+
+    lsp_types::Diagnostic {
+        range: to_range(text, diagnostic.span),
+        severity: Some(severity),
+        source: Some("mu".to_string()),
+        message: diagnostic.message.clone(),
+        ..Default::default()
+    }
+}
+
+#[allow(deprecated)] // `deprecated` is a required field of the lsp_types struct
+fn to_lsp_symbol(text: &str, symbol: &DocumentSymbol) -> lsp_types::DocumentSymbol {
+    let kind = match symbol.kind {
+        SymbolKind::Function => lsp_types::SymbolKind::FUNCTION,
+        SymbolKind::Variable | SymbolKind::Parameter => lsp_types::SymbolKind::VARIABLE,
+        SymbolKind::Constant => lsp_types::SymbolKind::CONSTANT,
+    };
+
+    lsp_types::DocumentSymbol {
+        name: symbol.name.clone(),
+        detail: None,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: to_range(text, symbol.span),
+        selection_range: to_range(text, symbol.name_span),
+        children: Some(symbol.children.iter().map(|child| to_lsp_symbol(text, child)).collect()),
+    }
+}
+
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        // the whole text is sent on every change, diagnostics are published on open and save
+        text_document_sync: Some(TextDocumentSyncCapability::Options(TextDocumentSyncOptions {
+            open_close: Some(true),
+            change: Some(TextDocumentSyncKind::FULL),
+            save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions { include_text: Some(false) })),
+            ..Default::default()
+        })),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions::default()),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+// Language server over an lsp_server connection (stdio for `mu lsp`).
+// Every open file is kept as text and analyzed again on each request,
+// Mu scripts are small enough for that.
+pub struct LanguageServer {
+    connection: Connection,
+    documents: HashMap<Url, String>,
+}
+
+impl LanguageServer {
+    pub fn new(connection: Connection) -> Self {
+        LanguageServer { connection, documents: HashMap::new() }
+    }
+
+    // Initialize handshake, then serve until the client asks to shut down
+    pub fn run(mut self) -> Result<()> {
+        let capabilities = serde_json::to_value(capabilities())?;
+        self.connection.initialize(capabilities)?;
+
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(request);
+                    self.connection.sender.send(Message::Response(response))?;
+                }
+                Message::Notification(notification) => {
+                    // notifications have no response, a bad one is only logged
+                    if let Err(e) = self.handle_notification(notification) {
+                        self.log_error(e)?;
+                    }
+                }
+                Message::Response(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_request(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            GotoDefinition::METHOD => self.definition(request.params),
+            HoverRequest::METHOD => self.hover(request.params),
+            Completion::METHOD => self.completion(request.params),
+            DocumentSymbolRequest::METHOD => self.document_symbols(request.params),
+            method => return Response::new_err(id, ErrorCode::MethodNotFound as i32, format!("Unknown method: {}", method)),
+        };
+
+        match result {
+            Ok(value) => Response::new_ok(id, value),
+            Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: lsp_types::DidOpenTextDocumentParams = parse_params(notification.params)?;
+                let uri = params.text_document.uri;
+                self.documents.insert(uri.clone(), params.text_document.text);
+                self.publish_diagnostics(uri)?;
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: lsp_types::DidChangeTextDocumentParams = parse_params(notification.params)?;
+                // full sync, the last change is the whole text
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents.insert(params.text_document.uri, change.text);
+                }
+            }
+            DidSaveTextDocument::METHOD => {
+                let params: lsp_types::DidSaveTextDocumentParams = parse_params(notification.params)?;
+                if let Some(text) = params.text {
+                    self.documents.insert(params.text_document.uri.clone(), text);
+                }
+                self.publish_diagnostics(params.text_document.uri)?;
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: lsp_types::DidCloseTextDocumentParams = parse_params(notification.params)?;
+                self.documents.remove(&params.text_document.uri);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn log_error(&self, err: anyhow::Error) -> Result<()> {
+        let params = LogMessageParams { typ: MessageType::ERROR, message: err.to_string() };
+        self.connection.sender.send(Message::Notification(Notification::new(LogMessage::METHOD.to_string(), params)))?;
+        Ok(())
+    }
+
+    fn publish_diagnostics(&self, uri: Url) -> Result<()> {
+        let diagnostics = match self.documents.get(&uri) {
+            Some(text) => Document::new(text).diagnostics().iter().map(|d| to_lsp_diagnostic(text, d)).collect(),
+            None => Vec::new(),
+        };
+
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        self.connection.sender.send(Message::Notification(Notification::new(
+            PublishDiagnostics::METHOD.to_string(),
+            params,
+        )))?;
+        Ok(())
+    }
+
+    fn text(&self, uri: &Url) -> &str {
+        self.documents.get(uri).map(String::as_str).unwrap_or_default()
+    }
+
+    fn definition(&self, params: serde_json::Value) -> Result<serde_json::Value> {
+        let params: TextDocumentPositionParams = parse_params(params)?;
+        let uri = params.text_document.uri;
+        let text = self.text(&uri);
+        let (line, col) = from_position(text, params.position);
+
+        let response = Document::new(text)
+            .definition_at(line, col)
+            .map(|definition| GotoDefinitionResponse::Scalar(Location::new(uri.clone(), to_range(text, definition.span))));
+        Ok(serde_json::to_value(response)?)
+    }
+
+    fn hover(&self, params: serde_json::Value) -> Result<serde_json::Value> {
+        let params: TextDocumentPositionParams = parse_params(params)?;
+        let text = self.text(&params.text_document.uri);
+        let (line, col) = from_position(text, params.position);
+
+        let response = Document::new(text)
+            .hover_at(line, col)
+            .map(|signature| Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: format!("```mu\n{}\n```", signature),
+                }),
+                range: None,
+            });
+        Ok(serde_json::to_value(response)?)
+    }
+
+    fn completion(&self, params: serde_json::Value) -> Result<serde_json::Value> {
+        let params: lsp_types::CompletionParams = parse_params(params)?;
+
+        let items = Document::new(self.text(&params.text_document_position.text_document.uri))
+            .completions()
+            .into_iter()
+            .map(|completion| CompletionItem {
+                label: completion.label,
+                kind: Some(match completion.kind {
+                    CompletionKind::Keyword => CompletionItemKind::KEYWORD,
+                    CompletionKind::Type => CompletionItemKind::TYPE_PARAMETER,
+                    CompletionKind::Function => CompletionItemKind::FUNCTION,
+                    CompletionKind::Variable => CompletionItemKind::VARIABLE,
+                    CompletionKind::Constant => CompletionItemKind::CONSTANT,
+                }),
+                detail: completion.detail,
+                ..Default::default()
+            })
+            .collect();
+        Ok(serde_json::to_value(CompletionResponse::Array(items))?)
+    }
+
+    fn document_symbols(&self, params: serde_json::Value) -> Result<serde_json::Value> {
+        let params: lsp_types::DocumentSymbolParams = parse_params(params)?;
+
+        let text = self.text(&params.text_document.uri);
+        let symbols = Document::new(text)
+            .symbols()
+            .iter()
+            .map(|symbol| to_lsp_symbol(text, symbol))
+            .collect();
+        Ok(serde_json::to_value(DocumentSymbolResponse::Nested(symbols))?)
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: serde_json::Value) -> Result<T> {
+    Ok(serde_json::from_value(params)?)
+}
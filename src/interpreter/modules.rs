@@ -0,0 +1,376 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow};
+
+use crate::interpreter::ast::{AST, Block, Catch, Expr, ExprKind, LambdaBody, MatchArm, Statement, StatementKind};
+use crate::interpreter::parser::Parser;
+use crate::interpreter::tokenizer::Tokenizer;
+
+// Resolves `import "path" as alias;` statements before a program runs.
+//
+// Imports are linked statically: the statements of a module are placed
+// where it is first imported, with its functions and top level variables
+// renamed to `namespace.name`. The namespace is the chain of aliases that
+// first reached the module, `a.c` when the program imports `a` which imports `c`.
+// Each module is linked once, later imports of the same file under any alias
+// only refer to that namespace, so its top level code runs once and every
+// importer shares its variables. The rest of the pipeline only sees one
+// program where `util.greet()` is a call to a function named "util.greet".
+//
+// A path is looked up next to the importing file first, then in the
+// search path directories, then in the directories of MU_PATH.
+pub struct ModuleLoader {
+    search_path: Vec<PathBuf>,
+    // namespace of each linked module by canonical path
+    linked: HashMap<PathBuf, String>,
+    // namespace of the modules each module imported by alias, "" is the program
+    imports: HashMap<String, HashMap<String, String>>,
+    // modules being linked, innermost last, to report import cycles
+    loading: Vec<PathBuf>,
+}
+
+impl ModuleLoader {
+    pub fn new(mut search_path: Vec<PathBuf>) -> Self {
+        if let Some(paths) = env::var_os("MU_PATH") {
+            search_path.extend(env::split_paths(&paths));
+        }
+
+        ModuleLoader {
+            search_path,
+            linked: HashMap::new(),
+            imports: HashMap::new(),
+            loading: Vec::new(),
+        }
+    }
+
+    // Replace the imports of the program in `file`, or of code that is not
+    // in a file (stdin, the REPL) whose imports start from the current directory
+    pub fn link(&mut self, ast: AST, file: Option<&Path>) -> Result<AST> {
+        let mut statements = Vec::with_capacity(ast.statements.len());
+        let Some(file) = file else {
+            self.link_module(ast, Path::new("."), None, &mut statements)?;
+            return Ok(AST { statements });
+        };
+
+        // the program itself can be part of a cycle
+        let root = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+        self.loading.push(root);
+        let linked = self.link_module(ast, file.parent().unwrap_or(Path::new(".")), None, &mut statements);
+        self.loading.pop();
+        linked?;
+        Ok(AST { statements })
+    }
+
+    // Appends the statements of a module, and of the modules it imports
+    // for the first time, to `statements`
+    fn link_module(&mut self, ast: AST, base_dir: &Path, namespace: Option<&str>, statements: &mut Vec<Statement>) -> Result<()> {
+        let names = Names::collect(&ast.statements);
+        let key = namespace.unwrap_or_default().to_string();
+        self.imports.entry(key.clone()).or_default();
+
+        for stmt in ast.statements {
+            let StatementKind::Import { path, alias } = &stmt.kind else {
+                let qualifier = Qualifier { namespace, names: &names, imports: &self.imports };
+                statements.push(qualifier.statement(stmt, &HashSet::new(), true));
+                continue;
+            };
+
+            let imported = self.import(path, alias, base_dir, namespace, statements)
+                .map_err(|e| anyhow!("Failed to import \"{}\" at {}: {}", path, stmt.span, e))?;
+            self.imports.entry(key.clone()).or_default().insert(alias.clone(), imported);
+        }
+
+        Ok(())
+    }
+
+    // Links the module at `path` unless it already is, returns its namespace
+    fn import(&mut self, path: &str, alias: &str, base_dir: &Path, namespace: Option<&str>, statements: &mut Vec<Statement>) -> Result<String> {
+        let file = self.resolve(path, base_dir)?;
+
+        if let Some(start) = self.loading.iter().position(|loading| *loading == file) {
+            let cycle: Vec<String> = self.loading[start..]
+                .iter()
+                .chain(std::iter::once(&file))
+                .map(|path| path.display().to_string())
+                .collect();
+            return Err(anyhow!("Import cycle: {}", cycle.join(" -> ")));
+        }
+
+        if let Some(linked) = self.linked.get(&file) {
+            return Ok(linked.clone());
+        }
+
+        let source = fs::read_to_string(&file)
+            .map_err(|e| anyhow!("Failed to read {}: {}", file.display(), e))?;
+        let tokens = Tokenizer::tokenize(source)
+            .map_err(|e| anyhow!("Failed to tokenize {}: {}", file.display(), e))?;
+        let ast = Parser::new(tokens).parse()
+            .map_err(|e| anyhow!("Failed to parse {}: {}", file.display(), e))?;
+
+        let module_namespace = match namespace {
+            Some(namespace) => format!("{}.{}", namespace, alias),
+            None => alias.to_string(),
+        };
+        self.loading.push(file.clone());
+        let base_dir = file.parent().unwrap_or(Path::new("."));
+        let linked = self.link_module(ast, base_dir, Some(&module_namespace), statements);
+        self.loading.pop();
+        linked?;
+
+        self.linked.insert(file, module_namespace.clone());
+        Ok(module_namespace)
+    }
+
+    fn resolve(&self, path: &str, base_dir: &Path) -> Result<PathBuf> {
+        let candidates = std::iter::once(base_dir).chain(self.search_path.iter().map(PathBuf::as_path));
+
+        for dir in candidates {
+            let file = dir.join(path);
+            if file.is_file() {
+                return Ok(file.canonicalize()?);
+            }
+        }
+
+        Err(anyhow!("Module not found"))
+    }
+}
+
+// Functions and top level variables a module defines
+#[derive(Default)]
+struct Names {
+    functions: HashSet<String>,
+    variables: HashSet<String>,
+}
+
+impl Names {
+    fn collect(statements: &[Statement]) -> Self {
+        let mut names = Names::default();
+        names.add(statements, true);
+        names
+    }
+
+    fn add(&mut self, statements: &[Statement], top_level: bool) {
+        for stmt in statements {
+            match &stmt.kind {
+                StatementKind::FuncDef { name, body, .. } => {
+                    self.functions.insert(name.clone());
+                    self.add(body, false);
+                }
+                StatementKind::Let { name, .. } if top_level => {
+                    self.variables.insert(name.clone());
+                }
+                StatementKind::Try { body, catch, finally } => {
+                    self.add(&body.statements, top_level);
+                    if let Some(catch) = catch {
+                        if top_level {
+                            self.variables.insert(catch.name.clone());
+                        }
+                        self.add(&catch.body.statements, top_level);
+                    }
+                    if let Some(finally) = finally {
+                        self.add(&finally.statements, top_level);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// Renames what a module defines to `namespace.name`.
+// Functions live in their own namespace like in the evaluator; variables are
+// only renamed at the top level and where no parameter or local of the
+// enclosing function has the same name.
+// `util.greeting` and `util.inner.greet()` become the names the imported
+// modules were given. The program itself has no namespace
+struct Qualifier<'a> {
+    namespace: Option<&'a str>,
+    names: &'a Names,
+    imports: &'a HashMap<String, HashMap<String, String>>,
+}
+
+impl<'a> Qualifier<'a> {
+    // Namespace of the module `expr` names, `util` or `util.inner`
+    fn module(&self, expr: &Expr) -> Option<&'a str> {
+        let (namespace, alias) = match &expr.kind {
+            ExprKind::Identifier(name) => (self.namespace.unwrap_or_default(), name.as_str()),
+            ExprKind::FieldAccess { object, field, optional: false } => (self.module(object)?, field.as_str()),
+            _ => return None,
+        };
+        self.imports.get(namespace)?.get(alias).map(String::as_str)
+    }
+
+    // The parser joins `util.inner.greet(...)` into one call name
+    fn module_function(&self, name: &str) -> Option<String> {
+        let (path, function) = name.rsplit_once('.')?;
+        let mut namespace = self.namespace.unwrap_or_default();
+        for alias in path.split('.') {
+            namespace = self.imports.get(namespace)?.get(alias)?;
+        }
+        Some(format!("{}.{}", namespace, function))
+    }
+
+    fn qualified(&self, name: &str) -> String {
+        match self.namespace {
+            Some(namespace) => format!("{}.{}", namespace, name),
+            None => name.to_string(),
+        }
+    }
+
+    // `locals` are the names of the enclosing function that hide module variables
+    fn statement(&self, stmt: Statement, locals: &HashSet<String>, top_level: bool) -> Statement {
+        let kind = match stmt.kind {
+            StatementKind::Let { name, ty, value, constant } => {
+                let value = self.expr(value, locals);
+                let name = if top_level { self.qualified(&name) } else { name };
+                StatementKind::Let { name, ty, value, constant }
+            }
+            StatementKind::FuncDef { name, params, return_type, body } => {
+                let mut function_locals: HashSet<String> = params.iter().map(|p| p.name.clone()).collect();
+                declared_names(&body, &mut function_locals);
+
+                StatementKind::FuncDef {
+                    name: self.qualified(&name),
+                    params,
+                    return_type,
+                    body: body.into_iter().map(|stmt| self.statement(stmt, &function_locals, false)).collect(),
+                }
+            }
+            StatementKind::Assign { target, op, value } => StatementKind::Assign {
+                target: self.expr(target, locals),
+                op,
+                value: self.expr(value, locals),
+            },
+            StatementKind::Expr(expr) => StatementKind::Expr(self.expr(expr, locals)),
+            StatementKind::Where { condition } => StatementKind::Where { condition: self.expr(condition, locals) },
+            StatementKind::Get { field, filter } => StatementKind::Get {
+                field: self.expr(field, locals),
+                filter: filter.map(|filter| self.expr(filter, locals)),
+            },
+            StatementKind::Set { field, key, value } => StatementKind::Set {
+                field: self.expr(field, locals),
+                key,
+                value: self.expr(value, locals),
+            },
+            StatementKind::Delete { field, key } => StatementKind::Delete { field: self.expr(field, locals), key },
+            // imports are linked before the statements are renamed
+            kind @ StatementKind::Import { .. } => kind,
+            StatementKind::Try { body, catch, finally } => StatementKind::Try {
+                body: self.block(body, locals, top_level),
+                catch: catch.map(|catch| Catch {
+                    name: if top_level { self.qualified(&catch.name) } else { catch.name },
+                    span: catch.span,
+                    body: self.block(catch.body, locals, top_level),
+                }),
+                finally: finally.map(|finally| self.block(finally, locals, top_level)),
+            },
+            StatementKind::Throw { value } => StatementKind::Throw { value: self.expr(value, locals) },
+        };
+
+        Statement::new(kind, stmt.span)
+    }
+
+    fn block(&self, block: Block, locals: &HashSet<String>, top_level: bool) -> Block {
+        Block {
+            statements: block.statements.into_iter().map(|stmt| self.statement(stmt, locals, top_level)).collect(),
+            span: block.span,
+        }
+    }
+
+    fn expr(&self, expr: Expr, locals: &HashSet<String>) -> Expr {
+        let boxed = |e: Box<Expr>| Box::new(self.expr(*e, locals));
+
+        if let ExprKind::FieldAccess { object, field, optional: false } = &expr.kind
+            && let Some(namespace) = self.module(object)
+            && self.module(&expr).is_none()
+        {
+            return Expr::new(ExprKind::Identifier(format!("{}.{}", namespace, field)), expr.span);
+        }
+
+        let kind = match expr.kind {
+            ExprKind::Identifier(name) if self.names.variables.contains(&name) && !locals.contains(&name) => {
+                ExprKind::Identifier(self.qualified(&name))
+            }
+            // a function passed as a value
+            ExprKind::Identifier(name) if self.names.functions.contains(&name) && !locals.contains(&name) => {
+                ExprKind::Identifier(self.qualified(&name))
+            }
+            // the name can be a variable holding a function
+            ExprKind::Call { name, args } => {
+                let is_module_name = self.names.functions.contains(&name) || self.names.variables.contains(&name);
+                let name = match self.module_function(&name) {
+                    Some(qualified) => qualified,
+                    None if is_module_name && !locals.contains(&name) => self.qualified(&name),
+                    None => name,
+                };
+                ExprKind::Call { name, args: args.into_iter().map(|arg| self.expr(arg, locals)).collect() }
+            }
+            ExprKind::Binary { left, op, right } => ExprKind::Binary { left: boxed(left), op, right: boxed(right) },
+            ExprKind::Unary { op, operand } => ExprKind::Unary { op, operand: boxed(operand) },
+            ExprKind::FieldAccess { field, object, optional } => ExprKind::FieldAccess { field, object: boxed(object), optional },
+            ExprKind::List(items) => ExprKind::List(items.into_iter().map(|item| self.expr(item, locals)).collect()),
+            ExprKind::Map(fields) => ExprKind::Map(
+                fields.into_iter().map(|(name, value)| (name, self.expr(value, locals))).collect()
+            ),
+            ExprKind::Index { object, index } => ExprKind::Index { object: boxed(object), index: boxed(index) },
+            ExprKind::Apply { callee, args } => ExprKind::Apply {
+                callee: boxed(callee),
+                args: args.into_iter().map(|arg| self.expr(arg, locals)).collect(),
+            },
+            // the parameters and `let`s of a lambda hide the module names in its body
+            ExprKind::Lambda { params, return_type, body } => {
+                let mut locals = locals.clone();
+                locals.extend(params.iter().map(|p| p.name.clone()));
+                let body = match body {
+                    LambdaBody::Expr(value) => LambdaBody::Expr(Box::new(self.expr(*value, &locals))),
+                    LambdaBody::Block(block) => {
+                        declared_names(&block.statements, &mut locals);
+                        LambdaBody::Block(self.block(block, &locals, false))
+                    }
+                };
+                ExprKind::Lambda { params, return_type, body }
+            }
+            // the names of a pattern are never qualified, they only live in their arm
+            ExprKind::Match { value, arms } => ExprKind::Match {
+                value: boxed(value),
+                arms: arms.into_iter().map(|arm| {
+                    let mut locals = locals.clone();
+                    locals.extend(arm.pattern.bindings().into_iter().map(|(name, _)| name.to_string()));
+                    MatchArm {
+                        guard: arm.guard.map(|guard| self.expr(guard, &locals)),
+                        body: self.expr(arm.body, &locals),
+                        ..arm
+                    }
+                }).collect(),
+            },
+            kind => kind,
+        };
+
+        Expr::new(kind, expr.span)
+    }
+}
+
+// Names a function body binds with `let` or `catch`, in the try blocks too
+fn declared_names(statements: &[Statement], names: &mut HashSet<String>) {
+    for stmt in statements {
+        match &stmt.kind {
+            StatementKind::Let { name, .. } => {
+                names.insert(name.clone());
+            }
+            StatementKind::Try { body, catch, finally } => {
+                declared_names(&body.statements, names);
+                if let Some(catch) = catch {
+                    names.insert(catch.name.clone());
+                    declared_names(&catch.body.statements, names);
+                }
+                if let Some(finally) = finally {
+                    declared_names(&finally.statements, names);
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::interpreter::ast::{AST, Statement, StatementKind, Expr, ExprKind, BinaryOp, UnaryOp, Block, Catch, LambdaBody, MatchArm};
+use crate::interpreter::evaluator::{Evaluator, Value};
+
+// AST to AST optimizations that keep the observable behaviour:
+// - constant folding of arithmetic, comparison, boolean and string expressions,
+//   using the evaluator's own operators. An expression that would fail at runtime
+//   (division by zero, type mismatch) is left as it is, so it still fails there
+// - `true && x`, `x || false` ... become `x` when x is known to produce a bool
+// - statements after one that always fails are unreachable and removed
+// - a top level `const` whose value folds to a literal is evaluated once, its
+//   uses in the statements after it are replaced by the value. Names that some
+//   function binds are left alone, with dynamic scoping a use could see that binding
+pub struct Optimizer {
+    // top level constants known so far and their literal value
+    constants: HashMap<String, ExprKind>,
+    // names that cannot be propagated, see above
+    rebound: HashSet<String>,
+}
+
+impl Optimizer {
+    pub fn optimize(ast: AST) -> AST {
+        let mut optimizer = Optimizer { constants: HashMap::new(), rebound: HashSet::new() };
+        optimizer.collect_rebound(&ast.statements, true);
+
+        AST { statements: optimizer.optimize_block(ast.statements, true) }
+    }
+
+    // Function and lambda parameters, `let`s and `catch` variables anywhere, and top level `let`s
+    fn collect_rebound(&mut self, statements: &[Statement], top_level: bool) {
+        for statement in statements {
+            let mut lambdas = Vec::new();
+            statement.visit(&mut |expr| {
+                if let ExprKind::Lambda { params, body, .. } = &expr.kind {
+                    lambdas.push((params, body));
+                }
+            });
+            for (params, body) in lambdas {
+                self.rebound.extend(params.iter().map(|p| p.name.clone()));
+                if let LambdaBody::Block(block) = body {
+                    self.collect_rebound(&block.statements, false);
+                }
+            }
+
+            match &statement.kind {
+                StatementKind::Let { name, constant, .. } if !top_level || !constant => {
+                    self.rebound.insert(name.clone());
+                }
+                StatementKind::FuncDef { params, body, .. } => {
+                    self.rebound.extend(params.iter().map(|p| p.name.clone()));
+                    self.collect_rebound(body, false);
+                }
+                StatementKind::Try { body, catch, finally } => {
+                    self.collect_rebound(&body.statements, top_level);
+                    if let Some(catch) = catch {
+                        self.rebound.insert(catch.name.clone());
+                        self.collect_rebound(&catch.body.statements, top_level);
+                    }
+                    if let Some(finally) = finally {
+                        self.collect_rebound(&finally.statements, top_level);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn optimize_block(&mut self, statements: Vec<Statement>, top_level: bool) -> Vec<Statement> {
+        let mut optimized = Vec::with_capacity(statements.len());
+
+        for statement in statements {
+            let statement = self.optimize_statement(statement);
+
+            if top_level
+                && let StatementKind::Let { name, value, constant: true, .. } = &statement.kind
+                && !self.rebound.contains(name)
+                && Self::literal(value).is_some()
+            {
+                self.constants.insert(name.clone(), value.kind.clone());
+            }
+
+            let diverges = Self::always_fails(&statement);
+            optimized.push(statement);
+
+            if diverges {
+                break;
+            }
+        }
+
+        optimized
+    }
+
+    // Statements after a failing one are only unreachable inside the block,
+    // the ones after the try statement run when the error is caught
+    fn optimize_try_block(&mut self, block: Block) -> Block {
+        Block { statements: self.optimize_block(block.statements, false), span: block.span }
+    }
+
+    fn optimize_statement(&mut self, stmt: Statement) -> Statement {
+        let kind = match stmt.kind {
+            StatementKind::Let { name, ty, value, constant } => StatementKind::Let {
+                name,
+                ty,
+                value: Self::fold(self.propagate(value)),
+                constant,
+            },
+            // the target keeps its shape, only its indexes are folded
+            StatementKind::Assign { target, op, value } => StatementKind::Assign {
+                target: Self::fold(target),
+                op,
+                value: Self::fold(self.propagate(value)),
+            },
+            StatementKind::FuncDef { name, params, return_type, body } => StatementKind::FuncDef {
+                name,
+                params,
+                return_type,
+                body: self.optimize_block(body, false),
+            },
+            StatementKind::Expr(expr) => StatementKind::Expr(Self::fold(self.propagate(expr))),
+            // the names in conditions can be record fields
+            StatementKind::Where { condition } => StatementKind::Where { condition: Self::fold(condition) },
+            StatementKind::Get { field, filter } => StatementKind::Get {
+                field: Self::fold(field),
+                filter: filter.map(Self::fold),
+            },
+            StatementKind::Set { field, key, value } => StatementKind::Set {
+                field: Self::fold(field),
+                key,
+                value: Self::fold(value),
+            },
+            StatementKind::Delete { field, key } => StatementKind::Delete { field: Self::fold(field), key },
+            kind @ StatementKind::Import { .. } => kind,
+            StatementKind::Try { body, catch, finally } => StatementKind::Try {
+                body: self.optimize_try_block(body),
+                catch: catch.map(|catch| Catch { body: self.optimize_try_block(catch.body), ..catch }),
+                finally: finally.map(|finally| self.optimize_try_block(finally)),
+            },
+            StatementKind::Throw { value } => StatementKind::Throw { value: Self::fold(self.propagate(value)) },
+        };
+
+        Statement::new(kind, stmt.span)
+    }
+
+    // Replace the uses of known constants by their value
+    fn propagate(&self, expr: Expr) -> Expr {
+        if self.constants.is_empty() {
+            return expr;
+        }
+
+        let span = expr.span;
+        let boxed = |e: Box<Expr>| Box::new(self.propagate(*e));
+
+        let kind = match expr.kind {
+            ExprKind::Identifier(name) => match self.constants.get(&name) {
+                Some(value) => value.clone(),
+                None => ExprKind::Identifier(name),
+            },
+            ExprKind::Binary { left, op, right } => ExprKind::Binary { left: boxed(left), op, right: boxed(right) },
+            ExprKind::Unary { op, operand } => ExprKind::Unary { op, operand: boxed(operand) },
+            ExprKind::Call { name, args } => ExprKind::Call {
+                name,
+                args: args.into_iter().map(|arg| self.propagate(arg)).collect(),
+            },
+            ExprKind::FieldAccess { field, object, optional } => ExprKind::FieldAccess { field, object: boxed(object), optional },
+            ExprKind::List(items) => ExprKind::List(items.into_iter().map(|item| self.propagate(item)).collect()),
+            ExprKind::Map(fields) => ExprKind::Map(
+                fields.into_iter().map(|(name, value)| (name, self.propagate(value))).collect()
+            ),
+            ExprKind::Index { object, index } => ExprKind::Index { object: boxed(object), index: boxed(index) },
+            ExprKind::Apply { callee, args } => ExprKind::Apply {
+                callee: boxed(callee),
+                args: args.into_iter().map(|arg| self.propagate(arg)).collect(),
+            },
+            // the parameters are rebound names, block bodies are left as written
+            ExprKind::Lambda { params, return_type, body: LambdaBody::Expr(value) } => ExprKind::Lambda {
+                params,
+                return_type,
+                body: LambdaBody::Expr(boxed(value)),
+            },
+            ExprKind::Match { value, arms } => ExprKind::Match {
+                value: boxed(value),
+                arms: arms.into_iter().map(|arm| {
+                    // a binding with the name of a constant hides it in the arm
+                    if arm.pattern.bindings().iter().any(|(name, _)| self.constants.contains_key(*name)) {
+                        return arm;
+                    }
+                    MatchArm {
+                        guard: arm.guard.map(|guard| self.propagate(guard)),
+                        body: self.propagate(arm.body),
+                        ..arm
+                    }
+                }).collect(),
+            },
+            kind => kind,
+        };
+
+        Expr::new(kind, span)
+    }
+
+    pub(crate) fn fold(expr: Expr) -> Expr {
+        let span = expr.span;
+
+        match expr.kind {
+            ExprKind::Binary { left, op, right } => {
+                let left = Self::fold(*left);
+                let right = Self::fold(*right);
+
+                // the right side of `??` only runs when the left one is null
+                if op == BinaryOp::Coalesce
+                    && let Some(l) = Self::literal(&left)
+                {
+                    return if matches!(l, Value::Null) { right } else { left };
+                }
+
+                if let (Some(l), Some(r)) = (Self::literal(&left), Self::literal(&right))
+                    && let Ok(value) = Evaluator::eval_binary_op(&l, &op, &r)
+                    && let Some(kind) = Self::from_value(value)
+                {
+                    return Expr::new(kind, span);
+                }
+
+                if let Some(simplified) = Self::simplify_logic(&left, op, &right) {
+                    return if simplified { right } else { left };
+                }
+
+                Expr::new(ExprKind::Binary { left: Box::new(left), op, right: Box::new(right) }, span)
+            }
+            ExprKind::Unary { op, operand } => {
+                let operand = Self::fold(*operand);
+
+                if let Some(value) = Self::literal(&operand)
+                    && let Ok(value) = Evaluator::eval_unary_op(&op, &value)
+                    && let Some(kind) = Self::from_value(value)
+                {
+                    return Expr::new(kind, span);
+                }
+
+                Expr::new(ExprKind::Unary { op, operand: Box::new(operand) }, span)
+            }
+            ExprKind::Call { name, args } => Expr::new(ExprKind::Call {
+                name,
+                args: args.into_iter().map(Self::fold).collect(),
+            }, span),
+            ExprKind::FieldAccess { field, object, optional } => Expr::new(ExprKind::FieldAccess {
+                field,
+                object: Box::new(Self::fold(*object)),
+                optional,
+            }, span),
+            ExprKind::List(items) => Expr::new(ExprKind::List(items.into_iter().map(Self::fold).collect()), span),
+            ExprKind::Map(fields) => Expr::new(ExprKind::Map(
+                fields.into_iter().map(|(name, value)| (name, Self::fold(value))).collect()
+            ), span),
+            ExprKind::Index { object, index } => Expr::new(ExprKind::Index {
+                object: Box::new(Self::fold(*object)),
+                index: Box::new(Self::fold(*index)),
+            }, span),
+            ExprKind::Apply { callee, args } => Expr::new(ExprKind::Apply {
+                callee: Box::new(Self::fold(*callee)),
+                args: args.into_iter().map(Self::fold).collect(),
+            }, span),
+            ExprKind::Lambda { params, return_type, body: LambdaBody::Expr(value) } => Expr::new(ExprKind::Lambda {
+                params,
+                return_type,
+                body: LambdaBody::Expr(Box::new(Self::fold(*value))),
+            }, span),
+            ExprKind::Match { value, arms } => Expr::new(ExprKind::Match {
+                value: Box::new(Self::fold(*value)),
+                arms: arms.into_iter().map(|arm| MatchArm {
+                    guard: arm.guard.map(Self::fold),
+                    body: Self::fold(arm.body),
+                    ..arm
+                }).collect(),
+            }, span),
+            kind => Expr::new(kind, span),
+        }
+    }
+
+    // Identities of && and || with a literal operand.
+    // Some(true) keeps the right operand, Some(false) keeps the left one.
+    // Both sides are always evaluated and must be bools, so the other
+    // operand has to be known to produce a bool for the result to be the same
+    fn simplify_logic(left: &Expr, op: BinaryOp, right: &Expr) -> Option<bool> {
+        let identity = match op {
+            BinaryOp::And => true,
+            BinaryOp::Or => false,
+            _ => return None,
+        };
+
+        match (&left.kind, &right.kind) {
+            (ExprKind::Boolean(b), _) if *b == identity && Self::is_bool(right) => Some(true),
+            (_, ExprKind::Boolean(b)) if *b == identity && Self::is_bool(left) => Some(false),
+            _ => None,
+        }
+    }
+
+    fn is_bool(expr: &Expr) -> bool {
+        match &expr.kind {
+            ExprKind::Boolean(_) => true,
+            ExprKind::Unary { op: UnaryOp::Not, .. } => true,
+            ExprKind::Binary { op, .. } => {
+                !matches!(op, BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Coalesce)
+            }
+            _ => false,
+        }
+    }
+
+    fn literal(expr: &Expr) -> Option<Value> {
+        match &expr.kind {
+            ExprKind::Number(n) => Some(Value::Number(n.value)),
+            ExprKind::String(s) => Some(Value::String(s.clone())),
+            ExprKind::Boolean(b) => Some(Value::Boolean(*b)),
+            ExprKind::Null => Some(Value::Null),
+            _ => None,
+        }
+    }
+
+    fn from_value(value: Value) -> Option<ExprKind> {
+        match value {
+            Value::Number(n) => Some(ExprKind::Number(n.into())),
+            Value::String(s) => Some(ExprKind::String(s)),
+            Value::Boolean(b) => Some(ExprKind::Boolean(b)),
+            Value::Null => Some(ExprKind::Null),
+            Value::List(_) | Value::Map(_) | Value::Unit | Value::Function(_) => None,
+        }
+    }
+
+    // A statement fails for sure when, after folding, it still evaluates
+    // an operator applied to literals only
+    fn always_fails(stmt: &Statement) -> bool {
+        match &stmt.kind {
+            StatementKind::Let { value, .. } => Self::has_failing_constant(value),
+            StatementKind::Expr(expr) => Self::has_failing_constant(expr),
+            StatementKind::Assign { target, value, .. } => {
+                Self::has_failing_constant(target) || Self::has_failing_constant(value)
+            }
+            StatementKind::Throw { .. } => true,
+            // function bodies only run when called, the other statements
+            // do not evaluate their expressions yet
+            _ => false,
+        }
+    }
+
+    fn has_failing_constant(expr: &Expr) -> bool {
+        match &expr.kind {
+            // the right side of `??` only runs when the left one is null
+            ExprKind::Binary { op: BinaryOp::Coalesce, left, .. } => Self::has_failing_constant(left),
+            ExprKind::Binary { left, right, .. } => {
+                (Self::literal(left).is_some() && Self::literal(right).is_some())
+                    || Self::has_failing_constant(left)
+                    || Self::has_failing_constant(right)
+            }
+            ExprKind::Unary { operand, .. } => {
+                Self::literal(operand).is_some() || Self::has_failing_constant(operand)
+            }
+            ExprKind::Call { args, .. } | ExprKind::List(args) => args.iter().any(Self::has_failing_constant),
+            ExprKind::Map(fields) => fields.iter().any(|(_, value)| Self::has_failing_constant(value)),
+            ExprKind::Index { object, index } => {
+                Self::has_failing_constant(object) || Self::has_failing_constant(index)
+            }
+            ExprKind::FieldAccess { object, .. } => Self::has_failing_constant(object),
+            ExprKind::Apply { callee, args } => {
+                Self::has_failing_constant(callee) || args.iter().any(Self::has_failing_constant)
+            }
+            // the body only runs when the lambda is called, the arms when they match
+            ExprKind::Match { value, .. } => Self::has_failing_constant(value),
+            _ => false,
+        }
+    }
+}
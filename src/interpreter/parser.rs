@@ -1,13 +1,40 @@
+use std::collections::HashSet;
 use std::mem::take;
 
-use anyhow::{Result, anyhow};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use anyhow::Result;
 
 use crate::interpreter::tokenizer::{Token, TokenKind};
-use crate::interpreter::ast::{AST, Statement, Expr, BinaryOp, UnaryOp};
+use crate::interpreter::ast::{AST, Statement, StatementKind, Expr, ExprKind, BinaryOp, UnaryOp, Param, Block, Catch, LambdaBody, MatchArm, Pattern, PatternKind, Type, TypeAnnotation};
+use crate::interpreter::span::Span;
+use crate::interpreter::diagnostic::Diagnostic;
+
+// All the syntax errors found in a single pass
+#[derive(Debug)]
+pub struct ParseErrors(pub Vec<Diagnostic>);
+
+impl Display for ParseErrors {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if let [single] = self.0.as_slice() {
+            return write!(f, "{}", single);
+        }
+
+        write!(f, "{} syntax errors:", self.0.len())?;
+        for diagnostic in &self.0 {
+            write!(f, "\n  {}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseErrors {}
 
 pub struct Parser{
     tokens: Vec<Token>,
-    current: usize,
+    diagnostics: Vec<Diagnostic>,
+    // aliases of the modules imported so far
+    modules: HashSet<String>,
 }
 
 impl Parser {
@@ -15,20 +42,84 @@ impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
         Parser {
             tokens,
-            current: 0,
+            diagnostics: Vec::new(),
+            modules: HashSet::new(),
         }
     }
 
     // Helper method to create error messages with position information
+    // past the end we point at the EOF token, which is always the last one
     fn error_at(&self, pos: usize, message: &str) -> anyhow::Error {
-        if pos < self.tokens.len() {
-            anyhow!("{} at line {}, column {}", message, self.tokens[pos].line, self.tokens[pos].col)
+        let pos = pos.min(self.tokens.len() - 1);
+        anyhow::Error::new(Diagnostic::error(message, self.tokens[pos].span))
+    }
+
+    // Span covering the tokens in [start, end)
+    fn span_between(&self, start: usize, end: usize) -> Span {
+        let last = end.saturating_sub(1).max(start).min(self.tokens.len() - 1);
+        self.tokens[start].span.to(self.tokens[last].span)
+    }
+
+    fn binary(left: Expr, op: BinaryOp, right: Expr) -> Expr {
+        let span = left.span.to(right.span);
+        Expr::new(ExprKind::Binary {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        }, span)
+    }
+
+    // Record a syntax error, the statement that produced it is dropped from the AST
+    fn report(&mut self, err: anyhow::Error, pos: usize) {
+        let diagnostic = match err.downcast::<Diagnostic>() {
+            Ok(diagnostic) => diagnostic,
+            Err(err) => Diagnostic::error(err.to_string(), self.tokens[pos.min(self.tokens.len() - 1)].span),
+        };
+        self.diagnostics.push(diagnostic);
+    }
+
+    // Panic-mode recovery: skip the broken statement starting at `start`.
+    // Stops right after a ';' or a whole '{ ... }' block,
+    // or right before a '}' that closes the enclosing block
+    fn synchronize(&self, start: usize) -> usize {
+        let mut pos = start;
+        let mut depth = 0;
+
+        while pos < self.tokens.len() {
+            match self.tokens[pos].kind {
+                TokenKind::EOF => return pos,
+                TokenKind::Semicolon if depth == 0 => return pos + 1,
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace => {
+                    if depth == 0 {
+                        return pos;
+                    }
+                    depth -= 1;
+                    if depth == 0 {
+                        return pos + 1;
+                    }
+                }
+                _ => {}
+            }
+            pos += 1;
+        }
+
+        pos
+    }
+
+    pub fn parse(self) -> Result<AST>{
+        let (ast, diagnostics) = self.parse_with_diagnostics();
+
+        if diagnostics.is_empty() {
+            Ok(ast)
         } else {
-            anyhow!("{} at end of file", message)
+            Err(anyhow::Error::new(ParseErrors(diagnostics)))
         }
     }
 
-    pub fn parse(mut self) -> Result<AST>{
+    // Parse the whole program without stopping at the first error.
+    // Returns the statements that parsed correctly and every syntax error found
+    pub fn parse_with_diagnostics(mut self) -> (AST, Vec<Diagnostic>) {
 
         let mut current = 0;  
         let mut statements: Vec<Statement> = Vec::new();
@@ -39,14 +130,25 @@ impl Parser {
                     break;
                 }
                 _ => {
-                    let (statement, next_pos) = self.parse_statement(current)?;
-                    statements.push(statement);
-                    current = next_pos;
+                    match self.parse_statement(current) {
+                        Ok((statement, next_pos)) => {
+                            statements.push(statement);
+                            current = next_pos;
+                        }
+                        Err(e) => {
+                            self.report(e, current);
+                            let next_pos = self.synchronize(current);
+
+                            // synchronize stops before a '}', at top level
+                            // it has no block to close so we skip it
+                            current = if next_pos == current { current + 1 } else { next_pos };
+                        }
+                    }
                 }
             }
         }
 
-        Ok(AST { statements })
+        (AST { statements }, self.diagnostics)
     }
 
     // Parse an expression
@@ -54,10 +156,23 @@ impl Parser {
     // from primitive to logical_or
     // logical_or <- logical_and <- comparison <- sum <- mult <- primitive (func calls | identifiers | numbers ...)
     pub fn parse_expression(&mut self, start: usize) -> Result<(Expr, usize)> {
-        self.parse_logical_or(start)
+        self.parse_coalesce(start)
     }
 
-    // Parse logical OR (lowest precedence)
+    // Parse null coalescing (lowest precedence)
+    fn parse_coalesce(&mut self, start: usize) -> Result<(Expr, usize)> {
+        let (mut left, mut pos) = self.parse_logical_or(start)?;
+
+        while pos < self.tokens.len() && self.tokens[pos].kind == TokenKind::Coalesce {
+            let (right, next_pos) = self.parse_logical_or(pos + 1)?;
+            left = Self::binary(left, BinaryOp::Coalesce, right);
+            pos = next_pos;
+        }
+
+        Ok((left, pos))
+    }
+
+    // Parse logical OR
     fn parse_logical_or(&mut self, start: usize) -> Result<(Expr, usize)> {
         let (mut left, mut pos) = self.parse_logical_and(start)?;
 
@@ -66,11 +181,7 @@ impl Parser {
                 TokenKind::Or => {
                     pos += 1;
                     let (right, next_pos) = self.parse_logical_and(pos)?;
-                    left = Expr::Binary {
-                        left: Box::new(left),
-                        op: BinaryOp::Or,
-                        right: Box::new(right),
-                    };
+                    left = Self::binary(left, BinaryOp::Or, right);
                     pos = next_pos;
                 }
                 _ => break,
@@ -89,11 +200,7 @@ impl Parser {
                 TokenKind::And => {
                     pos += 1;
                     let (right, next_pos) = self.parse_comparison(pos)?;
-                    left = Expr::Binary {
-                        left: Box::new(left),
-                        op: BinaryOp::And,
-                        right: Box::new(right),
-                    };
+                    left = Self::binary(left, BinaryOp::And, right);
                     pos = next_pos;
                 }
                 _ => break,
@@ -112,61 +219,37 @@ impl Parser {
                 TokenKind::Eq => {
                     pos += 1;
                     let (right, next_pos) = self.parse_additive(pos)?;
-                    left = Expr::Binary {
-                        left: Box::new(left),
-                        op: BinaryOp::Eq,
-                        right: Box::new(right),
-                    };
+                    left = Self::binary(left, BinaryOp::Eq, right);
                     pos = next_pos;
                 }
                 TokenKind::Neq => {
                     pos += 1;
                     let (right, next_pos) = self.parse_additive(pos)?;
-                    left = Expr::Binary {
-                        left: Box::new(left),
-                        op: BinaryOp::Neq,
-                        right: Box::new(right),
-                    };
+                    left = Self::binary(left, BinaryOp::Neq, right);
                     pos = next_pos;
                 }
                 TokenKind::Gt => {
                     pos += 1;
                     let (right, next_pos) = self.parse_additive(pos)?;
-                    left = Expr::Binary {
-                        left: Box::new(left),
-                        op: BinaryOp::Gt,
-                        right: Box::new(right),
-                    };
+                    left = Self::binary(left, BinaryOp::Gt, right);
                     pos = next_pos;
                 }
                 TokenKind::Lt => {
                     pos += 1;
                     let (right, next_pos) = self.parse_additive(pos)?;
-                    left = Expr::Binary {
-                        left: Box::new(left),
-                        op: BinaryOp::Lt,
-                        right: Box::new(right),
-                    };
+                    left = Self::binary(left, BinaryOp::Lt, right);
                     pos = next_pos;
                 }
                 TokenKind::Gte => {
                     pos += 1;
                     let (right, next_pos) = self.parse_additive(pos)?;
-                    left = Expr::Binary {
-                        left: Box::new(left),
-                        op: BinaryOp::Gte,
-                        right: Box::new(right),
-                    };
+                    left = Self::binary(left, BinaryOp::Gte, right);
                     pos = next_pos;
                 }
                 TokenKind::Lte => {
                     pos += 1;
                     let (right, next_pos) = self.parse_additive(pos)?;
-                    left = Expr::Binary {
-                        left: Box::new(left),
-                        op: BinaryOp::Lte,
-                        right: Box::new(right),
-                    };
+                    left = Self::binary(left, BinaryOp::Lte, right);
                     pos = next_pos;
                 }
                 _ => break,
@@ -185,21 +268,13 @@ impl Parser {
                 TokenKind::Plus => {
                     pos += 1;
                     let (right, next_pos) = self.parse_multiplicative(pos)?;
-                    left = Expr::Binary {
-                        left: Box::new(left),
-                        op: BinaryOp::Add,
-                        right: Box::new(right),
-                    };
+                    left = Self::binary(left, BinaryOp::Add, right);
                     pos = next_pos;
                 }
                 TokenKind::Minus => {
                     pos += 1;
                     let (right, next_pos) = self.parse_multiplicative(pos)?;
-                    left = Expr::Binary {
-                        left: Box::new(left),
-                        op: BinaryOp::Sub,
-                        right: Box::new(right),
-                    };
+                    left = Self::binary(left, BinaryOp::Sub, right);
                     pos = next_pos;
                 }
                 _ => break,
@@ -218,21 +293,13 @@ impl Parser {
                 TokenKind::Multiply => {
                     pos += 1;
                     let (right, next_pos) = self.parse_unary(pos)?;
-                    left = Expr::Binary {
-                        left: Box::new(left),
-                        op: BinaryOp::Mul,
-                        right: Box::new(right),
-                    };
+                    left = Self::binary(left, BinaryOp::Mul, right);
                     pos = next_pos;
                 }
                 TokenKind::Divide => {
                     pos += 1;
                     let (right, next_pos) = self.parse_unary(pos)?;
-                    left = Expr::Binary {
-                        left: Box::new(left),
-                        op: BinaryOp::Div,
-                        right: Box::new(right),
-                    };
+                    left = Self::binary(left, BinaryOp::Div, right);
                     pos = next_pos;
                 }
                 _ => break,
@@ -251,13 +318,57 @@ impl Parser {
         match &self.tokens[start].kind {
             TokenKind::Minus => {
                 let (operand, pos) = self.parse_unary(start + 1)?;
-                Ok((Expr::Unary {
+                let span = self.tokens[start].span.to(operand.span);
+                Ok((Expr::new(ExprKind::Unary {
                     op: UnaryOp::Neg,
                     operand: Box::new(operand),
-                }, pos))
+                }, span), pos))
+            }
+            _ => self.parse_postfix(start),
+        }
+    }
+
+    // Parse indexing, field access and calls after a primary expression: items[0][1]
+    fn parse_postfix(&mut self, start: usize) -> Result<(Expr, usize)> {
+        let (mut expr, mut pos) = self.parse_primitive_expr(start)?;
+
+        while pos < self.tokens.len() {
+            match &mut self.tokens[pos].kind {
+                TokenKind::LParen => {
+                    let (args, next_pos) = self.args_parse(pos + 1)?;
+                    pos = next_pos;
+
+                    let span = expr.span.to(self.tokens[pos - 1].span);
+                    expr = Expr::new(ExprKind::Apply { callee: Box::new(expr), args }, span);
+                }
+                TokenKind::LBracket => {
+                    let (index, next_pos) = self.parse_expression(pos + 1)?;
+
+                    if next_pos >= self.tokens.len() || self.tokens[next_pos].kind != TokenKind::RBracket {
+                        return Err(self.error_at(next_pos, "Expected ']' after index"));
+                    }
+                    pos = next_pos + 1;
+
+                    let span = expr.span.to(self.tokens[pos - 1].span);
+                    expr = Expr::new(ExprKind::Index { object: Box::new(expr), index: Box::new(index) }, span);
+                }
+                TokenKind::Dot | TokenKind::QuestionDot => {
+                    let optional = self.tokens[pos].kind == TokenKind::QuestionDot;
+                    let Some(TokenKind::Identifier(field)) = self.tokens.get_mut(pos + 1).map(|t| &mut t.kind) else {
+                        let dot = if optional { "?." } else { "." };
+                        return Err(self.error_at(pos + 1, &format!("Expected field name after '{}'", dot)));
+                    };
+                    let field = take(field);
+                    pos += 2;
+
+                    let span = expr.span.to(self.tokens[pos - 1].span);
+                    expr = Expr::new(ExprKind::FieldAccess { field, object: Box::new(expr), optional }, span);
+                }
+                _ => break,
             }
-            _ => self.parse_primitive_expr(start),
         }
+
+        Ok((expr, pos))
     }
 
     // Parse primary expressions
@@ -268,63 +379,117 @@ impl Parser {
 
         match &mut self.tokens[start].kind {
             TokenKind::Number(n) => {
-                Ok((Expr::Number(*n), start + 1))
+                let n = take(n);
+                Ok((Expr::new(ExprKind::Number(n), self.tokens[start].span), start + 1))
             }
+            TokenKind::Null => Ok((Expr::new(ExprKind::Null, self.tokens[start].span), start + 1)),
+            TokenKind::Match => self.match_parse(start),
+            TokenKind::Fn => self.lambda_parse(start),
             TokenKind::Identifier(name) => {
                 // take, moves the String out of the TokenKind and replaces with empty String
                 // empty String has no heap allocation (special pointer to "")
-                let name_str = take(name);
+                let mut name_str = take(name);
                 let mut pos = start + 1;
+
+                // `util.greet(...)` calls a function of an imported module,
+                // `m.f(...)` on anything else calls the field `f` of the value `m`
+                let mut end = pos;
+                while end + 1 < self.tokens.len()
+                    && self.tokens[end].kind == TokenKind::Dot
+                    && matches!(self.tokens[end + 1].kind, TokenKind::Identifier(_))
+                {
+                    end += 2;
+                }
+                if end > pos
+                    && end < self.tokens.len()
+                    && self.tokens[end].kind == TokenKind::LParen
+                    && self.modules.contains(&name_str)
+                {
+                    for i in (pos + 1..end).step_by(2) {
+                        if let TokenKind::Identifier(part) = &mut self.tokens[i].kind {
+                            name_str.push('.');
+                            name_str.push_str(&take(part));
+                        }
+                    }
+                    pos = end;
+                }
                 
                 // Check if this is a function call
                 if pos < self.tokens.len() && self.tokens[pos].kind == TokenKind::LParen {
-                    pos += 1; // consume '('
-                    
-                    let mut args = Vec::new();
-                    
-                    // Parse arguments
-                    if pos < self.tokens.len() && self.tokens[pos].kind != TokenKind::RParen {
-                        loop {
-                            let (arg, next_pos) = self.parse_expression(pos)?;
-                            args.push(arg);
-                            pos = next_pos;
-                            
-                            if pos >= self.tokens.len() {
-                                return Err(anyhow!("Unexpected end of input in function call"));
-                            }
-                            
-                            match self.tokens[pos].kind {
-                                TokenKind::Comma => {
-                                    pos += 1;
-                                    continue;
-                                }
-                                TokenKind::RParen => {
-                                    break;
-                                }
-                                _ => return Err(anyhow!("Expected ',' or ')' in function call, found {:?}", self.tokens[pos].kind)),
-                            }
-                        }
-                    }
-                    
-                    // Expect ')'
-                    if pos >= self.tokens.len() || self.tokens[pos].kind != TokenKind::RParen {
-                        return Err(anyhow!("Expected ')' after function arguments"));
-                    }
-                    pos += 1;
-                    
-                    Ok((Expr::Call { name: name_str, args }, pos))
+                    let (args, pos) = self.args_parse(pos + 1)?;
+                    Ok((Expr::new(ExprKind::Call { name: name_str, args }, self.span_between(start, pos)), pos))
                 } else {
                     // Just an identifier
-                    Ok((Expr::Identifier(name_str), pos))
+                    Ok((Expr::new(ExprKind::Identifier(name_str), self.tokens[start].span), pos))
                 }
             }
             TokenKind::String(s) => {
                 // take, moves the String out of the TokenKind and replaces with empty String
                 // empty String has no heap allocation (special pointer to "")
-                Ok((Expr::String(take(s)), start + 1))
+                let s = take(s);
+                Ok((Expr::new(ExprKind::String(s), self.tokens[start].span), start + 1))
             }
             TokenKind::Boolean(b) => {
-                Ok((Expr::Boolean(*b), start + 1))
+                let b = *b;
+                Ok((Expr::new(ExprKind::Boolean(b), self.tokens[start].span), start + 1))
+            }
+            TokenKind::LBracket => {
+                let mut pos = start + 1;
+                let mut items = Vec::new();
+
+                // Parse items, a trailing comma is allowed
+                while pos < self.tokens.len() && self.tokens[pos].kind != TokenKind::RBracket {
+                    let (item, next_pos) = self.parse_expression(pos)?;
+                    items.push(item);
+                    pos = next_pos;
+
+                    if pos >= self.tokens.len() {
+                        return Err(self.error_at(pos.saturating_sub(1), "Expected ']' to close the list, found end of input"));
+                    }
+
+                    match self.tokens[pos].kind {
+                        TokenKind::Comma => pos += 1,
+                        TokenKind::RBracket => {}
+                        _ => return Err(self.error_at(pos, &format!("Expected ',' or ']' in list, found {:?}", self.tokens[pos].kind))),
+                    }
+                }
+
+                if pos >= self.tokens.len() {
+                    return Err(self.error_at(pos.saturating_sub(1), "Expected ']' to close the list, found end of input"));
+                }
+                pos += 1;
+
+                Ok((Expr::new(ExprKind::List(items), self.span_between(start, pos)), pos))
+            }
+            TokenKind::LBrace => {
+                let mut pos = start + 1;
+                let mut fields = Vec::new();
+
+                // Parse `key: value` fields, a trailing comma is allowed
+                while self.tokens[pos].kind != TokenKind::RBrace {
+                    let key = match &mut self.tokens[pos].kind {
+                        TokenKind::Identifier(key) | TokenKind::String(key) => take(key),
+                        TokenKind::EOF => return Err(self.error_at(pos, "Expected '}' to close the map, found end of input")),
+                        _ => return Err(self.error_at(pos, &format!("Expected field name in map, found {:?}", self.tokens[pos].kind))),
+                    };
+
+                    if self.tokens[pos + 1].kind != TokenKind::Colon {
+                        return Err(self.error_at(pos + 1, &format!("Expected ':' after field name, found {:?}", self.tokens[pos + 1].kind)));
+                    }
+
+                    let (value, next_pos) = self.parse_expression(pos + 2)?;
+                    fields.push((key, value));
+                    pos = next_pos;
+
+                    match self.tokens[pos].kind {
+                        TokenKind::Comma => pos += 1,
+                        TokenKind::RBrace => {}
+                        _ => return Err(self.error_at(pos, &format!("Expected ',' or '}}' in map, found {:?}", self.tokens[pos].kind))),
+                    }
+                }
+                pos += 1;
+
+                Ok((Expr::new(ExprKind::Map(fields), self.span_between(start, pos)), pos))
             }
             TokenKind::LParen => {
                 let (expr, pos) = self.parse_expression(start + 1)?;
@@ -342,25 +507,237 @@ impl Parser {
         }
     }
 
+    // fn(params) -> type => expr, or fn(params) -> type { statements }
+    fn lambda_parse(&mut self, start: usize) -> Result<(Expr, usize)> {
+        if self.tokens[start + 1].kind != TokenKind::LParen {
+            return Err(self.error_at(start + 1, &format!("Expected '(' after 'fn', found {:?}", self.tokens[start + 1].kind)));
+        }
+        let (params, return_type, pos) = self.signature_parse(start + 2)?;
+
+        let (body, pos) = match self.tokens[pos].kind {
+            TokenKind::FatArrow => {
+                let (expr, pos) = self.parse_expression(pos + 1)?;
+                (LambdaBody::Expr(Box::new(expr)), pos)
+            }
+            TokenKind::LBrace => {
+                let (statements, end) = self.block_parse(pos, "lambda signature", "lambda body")?;
+                (LambdaBody::Block(Block { statements, span: self.span_between(pos, end) }), end)
+            }
+            _ => return Err(self.error_at(pos, &format!("Expected '=>' or '{{' after the lambda parameters, found {:?}", self.tokens[pos].kind))),
+        };
+
+        Ok((Expr::new(ExprKind::Lambda { params, return_type, body }, self.span_between(start, pos)), pos))
+    }
+
+    // Arguments after the '(' up to the ')'
+    fn args_parse(&mut self, start: usize) -> Result<(Vec<Expr>, usize)> {
+        let mut pos = start;
+        let mut args = Vec::new();
+
+        if pos < self.tokens.len() && self.tokens[pos].kind != TokenKind::RParen {
+            loop {
+                let (arg, next_pos) = self.parse_expression(pos)?;
+                args.push(arg);
+                pos = next_pos;
+
+                if pos >= self.tokens.len() {
+                    return Err(self.error_at(pos, "Unexpected end of input in function call"));
+                }
+
+                match self.tokens[pos].kind {
+                    TokenKind::Comma => {
+                        pos += 1;
+                        continue;
+                    }
+                    TokenKind::RParen => {
+                        break;
+                    }
+                    _ => return Err(self.error_at(pos, &format!("Expected ',' or ')' in function call, found {:?}", self.tokens[pos].kind))),
+                }
+            }
+        }
+
+        // Expect ')'
+        if pos >= self.tokens.len() || self.tokens[pos].kind != TokenKind::RParen {
+            return Err(self.error_at(pos, "Expected ')' after function arguments"));
+        }
+        Ok((args, pos + 1))
+    }
+
+    // match value { pattern if guard => body, ... }, arms are separated by commas
+    fn match_parse(&mut self, start: usize) -> Result<(Expr, usize)> {
+        let (value, mut pos) = self.parse_expression(start + 1)?;
+
+        if self.tokens[pos].kind != TokenKind::LBrace {
+            return Err(self.error_at(pos, &format!("Expected '{{' after the match value, found {:?}", self.tokens[pos].kind)));
+        }
+        pos += 1;
+
+        let mut arms = Vec::new();
+        while self.tokens[pos].kind != TokenKind::RBrace {
+            if self.tokens[pos].kind == TokenKind::EOF {
+                return Err(self.error_at(pos, "Expected '}' at end of match, found end of input"));
+            }
+            let arm_start = pos;
+            let (pattern, next_pos) = self.pattern_parse(pos)?;
+            pos = next_pos;
+
+            let mut guard = None;
+            // `if` is only a keyword in a match arm
+            if matches!(&self.tokens[pos].kind, TokenKind::Identifier(name) if name == "if") {
+                let (condition, next_pos) = self.parse_expression(pos + 1)?;
+                guard = Some(condition);
+                pos = next_pos;
+            }
+
+            if self.tokens[pos].kind != TokenKind::FatArrow {
+                return Err(self.error_at(pos, &format!("Expected '=>' after the pattern, found {:?}", self.tokens[pos].kind)));
+            }
+            let (body, next_pos) = self.parse_expression(pos + 1)?;
+            pos = next_pos;
+            arms.push(MatchArm { pattern, guard, body, span: self.span_between(arm_start, pos) });
+
+            match self.tokens[pos].kind {
+                TokenKind::Comma => pos += 1,
+                TokenKind::RBrace => {}
+                _ => return Err(self.error_at(pos, &format!("Expected ',' or '}}' after a match arm, found {:?}", self.tokens[pos].kind))),
+            }
+        }
+        pos += 1;
+
+        Ok((Expr::new(ExprKind::Match { value: Box::new(value), arms }, self.span_between(start, pos)), pos))
+    }
+
+    fn pattern_parse(&mut self, start: usize) -> Result<(Pattern, usize)> {
+        let span = self.tokens[start].span;
+        let kind = match &mut self.tokens[start].kind {
+            TokenKind::Identifier(name) if name == "_" => PatternKind::Wildcard,
+            TokenKind::Identifier(name) => PatternKind::Binding(take(name)),
+            TokenKind::Number(n) => PatternKind::Number(take(n)),
+            TokenKind::Minus => match &self.tokens[start + 1].kind {
+                TokenKind::Number(n) => {
+                    return Ok((Pattern { kind: PatternKind::Number(n.negated()), span: self.span_between(start, start + 2) }, start + 2));
+                },
+                _ => return Err(self.error_at(start + 1, &format!("Expected a number after '-' in pattern, found {:?}", self.tokens[start + 1].kind))),
+            },
+            TokenKind::String(s) => PatternKind::String(take(s)),
+            TokenKind::Boolean(b) => PatternKind::Boolean(*b),
+            TokenKind::Null => PatternKind::Null,
+            TokenKind::LBracket => return self.list_pattern_parse(start),
+            TokenKind::LBrace => return self.map_pattern_parse(start),
+            _ => return Err(self.error_at(start, &format!("Expected a pattern, found {:?}", self.tokens[start].kind))),
+        };
+
+        Ok((Pattern { kind, span }, start + 1))
+    }
+
+    // [a, b, ..rest] or [a, ..]
+    fn list_pattern_parse(&mut self, start: usize) -> Result<(Pattern, usize)> {
+        let mut pos = start + 1;
+        let mut items = Vec::new();
+        let mut rest = None;
+
+        while self.tokens[pos].kind != TokenKind::RBracket {
+            if self.tokens[pos].kind == TokenKind::DotDot {
+                let kind = match &mut self.tokens[pos + 1].kind {
+                    TokenKind::Identifier(name) if name != "_" => Some(PatternKind::Binding(take(name))),
+                    TokenKind::Identifier(_) => Some(PatternKind::Wildcard),
+                    _ => None,
+                };
+                let pattern = match kind {
+                    Some(kind) => {
+                        pos += 2;
+                        Pattern { kind, span: self.span_between(pos - 2, pos) }
+                    },
+                    None => {
+                        pos += 1;
+                        Pattern { kind: PatternKind::Wildcard, span: self.tokens[pos - 1].span }
+                    },
+                };
+                rest = Some(Box::new(pattern));
+
+                if self.tokens[pos].kind == TokenKind::Comma {
+                    pos += 1;
+                }
+                if self.tokens[pos].kind != TokenKind::RBracket {
+                    return Err(self.error_at(pos, &format!("Expected ']' after the rest pattern, found {:?}", self.tokens[pos].kind)));
+                }
+                break;
+            }
+
+            let (item, next_pos) = self.pattern_parse(pos)?;
+            items.push(item);
+            pos = next_pos;
+
+            match self.tokens[pos].kind {
+                TokenKind::Comma => pos += 1,
+                TokenKind::RBracket => {}
+                _ => return Err(self.error_at(pos, &format!("Expected ',' or ']' in list pattern, found {:?}", self.tokens[pos].kind))),
+            }
+        }
+        pos += 1;
+
+        Ok((Pattern { kind: PatternKind::List { items, rest }, span: self.span_between(start, pos) }, pos))
+    }
+
+    // { name, age: 42, "full name": n }
+    fn map_pattern_parse(&mut self, start: usize) -> Result<(Pattern, usize)> {
+        let mut pos = start + 1;
+        let mut fields = Vec::new();
+
+        while self.tokens[pos].kind != TokenKind::RBrace {
+            let shorthand = matches!(self.tokens[pos].kind, TokenKind::Identifier(_));
+            let key = match &mut self.tokens[pos].kind {
+                TokenKind::Identifier(key) | TokenKind::String(key) => take(key),
+                TokenKind::EOF => return Err(self.error_at(pos, "Expected '}' to close the map pattern, found end of input")),
+                _ => return Err(self.error_at(pos, &format!("Expected field name in map pattern, found {:?}", self.tokens[pos].kind))),
+            };
+
+            let pattern = if self.tokens[pos + 1].kind == TokenKind::Colon {
+                let (pattern, next_pos) = self.pattern_parse(pos + 2)?;
+                pos = next_pos;
+                pattern
+            } else if shorthand {
+                pos += 1;
+                let kind = if key == "_" { PatternKind::Wildcard } else { PatternKind::Binding(key.clone()) };
+                Pattern { kind, span: self.tokens[pos - 1].span }
+            } else {
+                return Err(self.error_at(pos + 1, &format!("Expected ':' after field name, found {:?}", self.tokens[pos + 1].kind)));
+            };
+            fields.push((key, pattern));
+
+            match self.tokens[pos].kind {
+                TokenKind::Comma => pos += 1,
+                TokenKind::RBrace => {}
+                _ => return Err(self.error_at(pos, &format!("Expected ',' or '}}' in map pattern, found {:?}", self.tokens[pos].kind))),
+            }
+        }
+        pos += 1;
+
+        Ok((Pattern { kind: PatternKind::Map(fields), span: self.span_between(start, pos) }, pos))
+    }
+
     // parse get statement, to a given start, it must correspond to a get token.
+    // Waits for the storage engine, a missing record will then read as null
+    // like a missing map field
     fn get_parse(&self, start: usize) -> Result<Statement> {
 
-        Err(anyhow!("Not implemented"))
+        Err(self.error_at(start, "Not implemented"))
     }
 
     fn set_parse(&self, start: usize) -> Result<Statement> {
         // Placeholder implementation
-        Err(anyhow::anyhow!("Not implemented"))
+        Err(self.error_at(start, "Not implemented"))
     }
 
     // Parse where statement: where <condition>;
     fn where_parse(&mut self, start: usize) -> Result<(Statement, usize)> {
         if start >= self.tokens.len() {
-            return Err(anyhow!("Start index out of bounds"));
+            return Err(self.error_at(start.saturating_sub(1), "Start index out of bounds"));
         }
 
         if self.tokens[start].kind != TokenKind::Where {
-            return Err(anyhow!("Expected 'where' token"));
+            return Err(self.error_at(start, "Expected 'where' token"));
         }
 
         let mut pos = start + 1;
@@ -374,23 +751,26 @@ impl Parser {
             pos += 1;
         }
 
-        Ok((Statement::Where { condition }, pos))
+        Ok((Statement::new(StatementKind::Where { condition }, self.span_between(start, pos)), pos))
     }
 
     fn delete_parse(&self, start: usize) -> Result<Statement> {
         // Placeholder implementation
-        Err(anyhow::anyhow!("Not implemented"))
+        Err(self.error_at(start, "Not implemented"))
     }
 
-    // Parse let statement: let <identifier> = <expr>;
+    // Parse let statement: let <identifier> = <expr>; or const <identifier> = <expr>;
     fn let_parse(&mut self, start: usize) -> Result<(Statement, usize)> {
         if start >= self.tokens.len() {
             return Err(self.error_at(start.saturating_sub(1), "Start index out of bounds"));
         }
 
-        if self.tokens[start].kind != TokenKind::Let {
-            return Err(self.error_at(start, "Expected 'let' token"));
-        }
+        let constant = match self.tokens[start].kind {
+            TokenKind::Let => false,
+            TokenKind::Const => true,
+            _ => return Err(self.error_at(start, "Expected 'let' or 'const' token")),
+        };
+        let keyword = if constant { "const" } else { "let" };
 
         let mut pos = start + 1;
 
@@ -401,9 +781,17 @@ impl Parser {
                 pos += 1;
                 name
             }
-            _ => return Err(self.error_at(pos, &format!("Expected identifier after 'let', found {:?}", self.tokens[pos].kind))),
+            _ => return Err(self.error_at(pos, &format!("Expected identifier after '{}', found {:?}", keyword, self.tokens[pos].kind))),
         };
 
+        // Optional type annotation
+        let mut ty = None;
+        if pos < self.tokens.len() && self.tokens[pos].kind == TokenKind::Colon {
+            let (annotation, next_pos) = self.parse_type(pos + 1)?;
+            ty = Some(annotation);
+            pos = next_pos;
+        }
+
         // Expect '='
         if pos >= self.tokens.len() || self.tokens[pos].kind != TokenKind::Assign {
             return Err(self.error_at(pos, &format!("Expected '=' after variable name, found {:?}", self.tokens.get(pos).map(|t| &t.kind))));
@@ -419,14 +807,59 @@ impl Parser {
             pos += 1;
         }
 
-        Ok((Statement::Let { name, value }, pos))
+        Ok((Statement::new(StatementKind::Let { name, ty, value, constant }, self.span_between(start, pos)), pos))
+    }
+
+    // Parse import statement: import "<path>" as <identifier>;
+    fn import_parse(&mut self, start: usize) -> Result<(Statement, usize)> {
+        let mut pos = start + 1;
+
+        let path = match &mut self.tokens[pos].kind {
+            TokenKind::String(path) => {
+                pos += 1;
+                take(path)
+            }
+            _ => return Err(self.error_at(pos, &format!("Expected a file path after 'import', found {:?}", self.tokens[pos].kind))),
+        };
+
+        if self.tokens[pos].kind != TokenKind::As {
+            return Err(self.error_at(pos, &format!("Expected 'as' after the import path, found {:?}", self.tokens[pos].kind)));
+        }
+        pos += 1;
+
+        let alias = match &mut self.tokens[pos].kind {
+            TokenKind::Identifier(alias) => {
+                pos += 1;
+                take(alias)
+            }
+            _ => return Err(self.error_at(pos, &format!("Expected a module name after 'as', found {:?}", self.tokens[pos].kind))),
+        };
+
+        // Optionally consume semicolon
+        if pos < self.tokens.len() && self.tokens[pos].kind == TokenKind::Semicolon {
+            pos += 1;
+        }
+
+        self.modules.insert(alias.clone());
+        Ok((Statement::new(StatementKind::Import { path, alias }, self.span_between(start, pos)), pos))
+    }
+
+    // Parse a type name: number, string, bool or unit
+    fn parse_type(&mut self, start: usize) -> Result<(TypeAnnotation, usize)> {
+        match &self.tokens[start.min(self.tokens.len() - 1)].kind {
+            TokenKind::Identifier(name) => match Type::from_name(name) {
+                Some(ty) => Ok((TypeAnnotation { ty, span: self.tokens[start].span }, start + 1)),
+                None => Err(self.error_at(start, &format!("Unknown type '{}'", name))),
+            },
+            other => Err(self.error_at(start, &format!("Expected type name, found {:?}", other))),
+        }
     }
 
     // Parse a single statement at the given position
     // Returns (Statement, next_position)
     fn parse_statement(&mut self, pos: usize) -> Result<(Statement, usize)> {
         if pos >= self.tokens.len() {
-            return Err(anyhow!("Unexpected end of input"));
+            return Err(self.error_at(pos, "Unexpected end of input"));
         }
 
         match &self.tokens[pos].kind {
@@ -448,24 +881,70 @@ impl Parser {
             TokenKind::Where => {
                 self.where_parse(pos)
             }
-            TokenKind::Let => {
+            TokenKind::Let | TokenKind::Const => {
                 self.let_parse(pos)
             }
+            TokenKind::Import => {
+                self.import_parse(pos)
+            }
+            TokenKind::Try => {
+                self.try_parse(pos)
+            }
+            TokenKind::Throw => {
+                let (value, mut next_pos) = self.parse_expression(pos + 1)?;
+
+                // Optionally consume semicolon
+                if next_pos < self.tokens.len() && self.tokens[next_pos].kind == TokenKind::Semicolon {
+                    next_pos += 1;
+                }
+
+                Ok((Statement::new(StatementKind::Throw { value }, self.span_between(pos, next_pos)), next_pos))
+            }
             _ => {
                 // Parse as expression statement
                 let (expr, next_pos) = self.parse_expression(pos)?;
                 let mut final_pos = next_pos;
-                
+
+                // `target = value` or `target += value`
+                let op = match self.tokens.get(final_pos).map(|t| &t.kind) {
+                    Some(TokenKind::Assign) => Some(None),
+                    Some(TokenKind::PlusAssign) => Some(Some(BinaryOp::Add)),
+                    Some(TokenKind::MinusAssign) => Some(Some(BinaryOp::Sub)),
+                    Some(TokenKind::MultiplyAssign) => Some(Some(BinaryOp::Mul)),
+                    Some(TokenKind::DivideAssign) => Some(Some(BinaryOp::Div)),
+                    _ => None,
+                };
+                let kind = match op {
+                    Some(op) => {
+                        if !Self::is_assignable(&expr) {
+                            return Err(self.error_at(final_pos, "Invalid assignment target, expected a variable, an index or a field"));
+                        }
+                        let (value, next_pos) = self.parse_expression(final_pos + 1)?;
+                        final_pos = next_pos;
+                        StatementKind::Assign { target: expr, op, value }
+                    }
+                    None => StatementKind::Expr(expr),
+                };
+
                 // Optionally consume semicolon
                 if final_pos < self.tokens.len() && self.tokens[final_pos].kind == TokenKind::Semicolon {
                     final_pos += 1;
                 }
-                
-                Ok((Statement::Expr(expr), final_pos))
+
+                Ok((Statement::new(kind, self.span_between(pos, final_pos)), final_pos))
             }
         }
     }
 
+    // `x`, `items[0]`, `user.address.city`: a variable, maybe followed by indexes and fields
+    fn is_assignable(expr: &Expr) -> bool {
+        match &expr.kind {
+            ExprKind::Identifier(_) => true,
+            ExprKind::Index { object, .. } | ExprKind::FieldAccess { object, .. } => Self::is_assignable(object),
+            _ => false,
+        }
+    }
+
     // Parse function definition: func name(param1, param2) { ... }
     fn func_parse(&mut self, start: usize) -> Result<(Statement, usize)> {
         if start >= self.tokens.len() {
@@ -494,23 +973,42 @@ impl Parser {
         }
         pos += 1;
 
-        // Parse parameters
+        let (params, return_type, pos) = self.signature_parse(pos)?;
+        let (body, pos) = self.block_parse(pos, "function signature", "function body")?;
+
+        Ok((Statement::new(StatementKind::FuncDef { name, params, return_type, body }, self.span_between(start, pos)), pos))
+    }
+
+    // Parameters after the '(' up to the ')', and the optional `-> type`
+    fn signature_parse(&mut self, start: usize) -> Result<(Vec<Param>, Option<TypeAnnotation>, usize)> {
+        let mut pos = start;
         let mut params = Vec::new();
         
         // Check if there are any parameters
         if pos < self.tokens.len() && self.tokens[pos].kind != TokenKind::RParen {
             loop {
-                match &self.tokens[pos].kind {
+                let param_start = pos;
+                let param_name = match &mut self.tokens[pos].kind {
                     TokenKind::Identifier(param_name) => {
-                        params.push(param_name.clone());
                         pos += 1;
+                        take(param_name)
                     }
-                    _ => return Err(anyhow!("Expected parameter name, found {:?}", self.tokens[pos].kind)),
+                    _ => return Err(self.error_at(pos, &format!("Expected parameter name, found {:?}", self.tokens[pos].kind))),
+                };
+
+                // Optional type annotation
+                let mut ty = None;
+                if pos < self.tokens.len() && self.tokens[pos].kind == TokenKind::Colon {
+                    let (annotation, next_pos) = self.parse_type(pos + 1)?;
+                    ty = Some(annotation);
+                    pos = next_pos;
                 }
 
+                params.push(Param { name: param_name, ty, span: self.span_between(param_start, pos) });
+
                 // Check for comma or closing paren
                 if pos >= self.tokens.len() {
-                    return Err(anyhow!("Unexpected end of input in parameter list"));
+                    return Err(self.error_at(pos, "Unexpected end of input in parameter list"));
                 }
 
                 match self.tokens[pos].kind {
@@ -521,39 +1019,102 @@ impl Parser {
                     TokenKind::RParen => {
                         break;
                     }
-                    _ => return Err(anyhow!("Expected ',' or ')' in parameter list, found {:?}", self.tokens[pos].kind)),
+                    _ => return Err(self.error_at(pos, &format!("Expected ',' or ')' in parameter list, found {:?}", self.tokens[pos].kind))),
                 }
             }
         }
 
         // Expect ')'
         if pos >= self.tokens.len() || self.tokens[pos].kind != TokenKind::RParen {
-            return Err(anyhow!("Expected ')' after parameters"));
+            return Err(self.error_at(pos, "Expected ')' after parameters"));
         }
         pos += 1;
 
+        // Optional return type
+        let mut return_type = None;
+        if pos < self.tokens.len() && self.tokens[pos].kind == TokenKind::Arrow {
+            let (annotation, next_pos) = self.parse_type(pos + 1)?;
+            return_type = Some(annotation);
+            pos = next_pos;
+        }
+
+        Ok((params, return_type, pos))
+    }
+
+    // Parse `{ statements }`, broken statements inside are reported and skipped
+    fn block_parse(&mut self, start: usize, after: &str, block: &str) -> Result<(Vec<Statement>, usize)> {
         // Expect '{'
-        if pos >= self.tokens.len() || self.tokens[pos].kind != TokenKind::LBrace {
-            return Err(anyhow!("Expected '{{' after function signature"));
+        if start >= self.tokens.len() || self.tokens[start].kind != TokenKind::LBrace {
+            return Err(self.error_at(start, &format!("Expected '{{' after {}", after)));
         }
-        pos += 1;
+        let mut pos = start + 1;
 
-        // Parse function body
         let mut body = Vec::new();
-        
-        while pos < self.tokens.len() && self.tokens[pos].kind != TokenKind::RBrace {
-            let (stmt, next_pos) = self.parse_statement(pos)?;
-            body.push(stmt);
-            pos = next_pos;
+
+        while pos < self.tokens.len() && !matches!(self.tokens[pos].kind, TokenKind::RBrace | TokenKind::EOF) {
+            match self.parse_statement(pos) {
+                Ok((stmt, next_pos)) => {
+                    body.push(stmt);
+                    pos = next_pos;
+                }
+                Err(e) => {
+                    self.report(e, pos);
+                    pos = self.synchronize(pos);
+                }
+            }
         }
 
         // Expect '}'
         if pos >= self.tokens.len() || self.tokens[pos].kind != TokenKind::RBrace {
-            return Err(anyhow!("Expected '}}' at end of function body"));
+            return Err(self.error_at(pos, &format!("Expected '}}' at end of {}", block)));
+        }
+
+        Ok((body, pos + 1))
+    }
+
+    // Parse try statement: try { ... } catch (e) { ... } finally { ... }
+    fn try_parse(&mut self, start: usize) -> Result<(Statement, usize)> {
+        let (statements, mut pos) = self.block_parse(start + 1, "'try'", "try block")?;
+        let body = Block { statements, span: self.span_between(start + 1, pos) };
+
+        let mut catch = None;
+        if self.tokens[pos].kind == TokenKind::Catch {
+            pos += 1;
+            if self.tokens[pos].kind != TokenKind::LParen {
+                return Err(self.error_at(pos, &format!("Expected '(' after 'catch', found {:?}", self.tokens[pos].kind)));
+            }
+            pos += 1;
+
+            let span = self.tokens[pos].span;
+            let name = match &mut self.tokens[pos].kind {
+                TokenKind::Identifier(name) => {
+                    pos += 1;
+                    take(name)
+                }
+                _ => return Err(self.error_at(pos, &format!("Expected a variable name in 'catch', found {:?}", self.tokens[pos].kind))),
+            };
+
+            if self.tokens[pos].kind != TokenKind::RParen {
+                return Err(self.error_at(pos, &format!("Expected ')' after the catch variable, found {:?}", self.tokens[pos].kind)));
+            }
+            let (statements, next_pos) = self.block_parse(pos + 1, "the catch variable", "catch block")?;
+            let body = Block { statements, span: self.span_between(pos + 1, next_pos) };
+            pos = next_pos;
+            catch = Some(Catch { name, span, body });
+        }
+
+        let mut finally = None;
+        if self.tokens[pos].kind == TokenKind::Finally {
+            let (statements, next_pos) = self.block_parse(pos + 1, "'finally'", "finally block")?;
+            finally = Some(Block { statements, span: self.span_between(pos + 1, next_pos) });
+            pos = next_pos;
+        }
+
+        if catch.is_none() && finally.is_none() {
+            return Err(self.error_at(pos, &format!("Expected 'catch' or 'finally' after the try block, found {:?}", self.tokens[pos].kind)));
         }
-        pos += 1;
 
-        Ok((Statement::FuncDef { name, params, body }, pos))
+        Ok((Statement::new(StatementKind::Try { body, catch, finally }, self.span_between(start, pos)), pos))
     }
 
 }
\ No newline at end of file
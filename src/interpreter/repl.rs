@@ -0,0 +1,307 @@
+use std::borrow::Cow;
+use std::fs;
+use std::io::Write;
+use std::time::Instant;
+
+use anyhow::{Result, anyhow};
+use rustyline::completion::Completer;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+use unicode_ident::is_xid_continue;
+
+use crate::interpreter::builtins::BUILTINS;
+use crate::interpreter::document::KEYWORDS;
+use crate::interpreter::evaluation_error;
+use crate::interpreter::evaluator::Evaluator;
+use crate::interpreter::modules::ModuleLoader;
+use crate::interpreter::parser::Parser;
+use crate::interpreter::span::{self, Span};
+use crate::interpreter::tokenizer::{TokenKind, Tokenizer};
+
+const HELP: &str = "\
+Meta-commands:
+  :vars            list the defined variables
+  :funcs           list the defined functions
+  :load <file>     run a Mu file in this session
+  :reset           forget every variable and function
+  :ast <code>      show the syntax tree of some code without running it
+  :time <code>     run some code and show how long it took
+  :help            show this list";
+
+// Whether a REPL entry can be run as it is, or more lines are needed.
+// An entry is incomplete while a bracket, a string or a block comment is
+// left open; a stray closing bracket is complete so the parser reports it
+pub fn is_complete(source: &str) -> bool {
+    let tokens = match Tokenizer::tokenize(source.to_string()) {
+        Ok(tokens) => tokens,
+        Err(e) => return !e.to_string().starts_with("Unterminated"),
+    };
+
+    let mut depth: i64 = 0;
+    for token in &tokens {
+        match token.kind {
+            TokenKind::LParen | TokenKind::LBrace | TokenKind::LBracket => depth += 1,
+            TokenKind::RParen | TokenKind::RBrace | TokenKind::RBracket => depth -= 1,
+            _ => {}
+        }
+    }
+    depth <= 0
+}
+
+const KEYWORD_COLOR: &str = "\x1b[1;35m";
+const STRING_COLOR: &str = "\x1b[32m";
+const NUMBER_COLOR: &str = "\x1b[33m";
+const COMMENT_COLOR: &str = "\x1b[90m";
+const RESET: &str = "\x1b[0m";
+
+// Color keywords, strings, numbers and comments with ANSI escapes.
+// Input the tokenizer rejects, like a string still being typed, is left plain
+pub fn highlight(source: &str) -> String {
+    let Ok((tokens, comments)) = Tokenizer::tokenize_with_comments(source.to_string()) else {
+        return source.to_string();
+    };
+
+    // byte offset where each line starts, spans are 1-based line and column counting chars
+    let mut line_starts = vec![0];
+    line_starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+    let offset = |line: usize, col: usize| match line_starts.get(line - 1) {
+        Some(&start) => start + span::byte_offset(source[start..].split('\n').next().unwrap_or_default(), col),
+        None => source.len(),
+    };
+
+    let mut colored: Vec<(Span, &str)> = tokens
+        .iter()
+        .filter_map(|token| {
+            let color = match token.kind {
+                TokenKind::Let | TokenKind::Const | TokenKind::Func | TokenKind::Get | TokenKind::Set | TokenKind::Where
+                | TokenKind::Delete | TokenKind::Import | TokenKind::As
+                | TokenKind::Try | TokenKind::Catch | TokenKind::Finally | TokenKind::Throw | TokenKind::Match | TokenKind::Fn | TokenKind::True | TokenKind::False | TokenKind::Null | TokenKind::Boolean(_) => KEYWORD_COLOR,
+                TokenKind::String(_) => STRING_COLOR,
+                TokenKind::Number(_) => NUMBER_COLOR,
+                _ => return None,
+            };
+            Some((token.span, color))
+        })
+        .chain(comments.iter().map(|comment| (comment.span, COMMENT_COLOR)))
+        .collect();
+    colored.sort_by_key(|(span, _)| (span.line, span.col));
+
+    let mut highlighted = String::with_capacity(source.len() * 2);
+    let mut end = 0;
+    for (span, color) in colored {
+        let start = offset(span.line, span.col);
+        if start < end || !source.is_char_boundary(start) {
+            continue;
+        }
+        let stop = offset(span.end_line, span.end_col);
+        if !source.is_char_boundary(stop) {
+            continue;
+        }
+
+        highlighted.push_str(&source[end..start]);
+        highlighted.push_str(color);
+        highlighted.push_str(&source[start..stop]);
+        highlighted.push_str(RESET);
+        end = stop;
+    }
+    highlighted.push_str(&source[end..]);
+    highlighted
+}
+
+// Completions for the word ending at `pos`: keywords, builtins and the given names.
+// Returns where the word starts and the sorted candidates
+pub fn complete(line: &str, pos: usize, names: &[String]) -> (usize, Vec<String>) {
+    let start = line[..pos]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_xid_continue(*c))
+        .last()
+        .map_or(pos, |(i, _)| i);
+    let word = &line[start..pos];
+
+    let mut candidates: Vec<String> = KEYWORDS
+        .iter()
+        .map(|k| k.to_string())
+        .chain(BUILTINS.iter().map(|b| b.name.to_string()))
+        .chain(names.iter().cloned())
+        .filter(|candidate| candidate.starts_with(word))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+
+    (start, candidates)
+}
+
+// rustyline helper of the REPLs: tab completion and syntax highlighting.
+// The REPL refreshes the names after every entry
+#[derive(Default)]
+pub struct ReplHelper {
+    names: Vec<String>,
+}
+
+impl ReplHelper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_names(&mut self, names: Vec<String>) {
+        self.names = names;
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(line, pos, &self.names))
+    }
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        Cow::Owned(highlight(line))
+    }
+
+    // every edit can change how the line is colored
+    fn highlight_char(&self, _line: &str, _pos: usize, _kind: CmdKind) -> bool {
+        true
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+// State of an interactive session: variables and functions defined by earlier
+// entries stay visible to the next ones.
+// Entries are evaluated without the static checks, the analyzer only sees the
+// current entry and would reject names defined by the previous ones
+pub struct Session {
+    evaluator: Evaluator,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session { evaluator: Evaluator::new() }
+    }
+
+    // Program output goes to `output`, meta-commands return their text
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        Session { evaluator: Evaluator::with_output(output) }
+    }
+
+    pub fn evaluator(&self) -> &Evaluator {
+        &self.evaluator
+    }
+
+    // Variables and functions defined so far, for tab completion
+    pub fn names(&self) -> Vec<String> {
+        self.evaluator.variables()
+            .keys()
+            .map(String::as_str)
+            .chain(self.evaluator.functions().map(|(name, _)| name))
+            .map(str::to_string)
+            .collect()
+    }
+
+    // Run one complete entry, either Mu code or a `:command`.
+    // Returns what a meta-command has to show, empty for code
+    pub fn execute(&mut self, input: &str) -> Result<String> {
+        let input = input.trim();
+
+        match input.strip_prefix(':') {
+            Some(command) => self.command(command),
+            None => {
+                self.run(input)?;
+                Ok(String::new())
+            }
+        }
+    }
+
+    fn run(&mut self, source: &str) -> Result<()> {
+        let tokens = Tokenizer::tokenize(source.to_string())
+            .map_err(|e| anyhow!("Failed to tokenize input: {}", e))?;
+
+        let ast = Parser::new(tokens).parse()
+            .map_err(|e| anyhow!("Failed to parse tokens: {}", e))?;
+        // a fresh loader, a module edited between two imports is read again
+        let ast = ModuleLoader::new(Vec::new()).link(ast, None)?;
+
+        self.evaluator.run(ast).map_err(evaluation_error)
+    }
+
+    fn command(&mut self, command: &str) -> Result<String> {
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command, ""),
+        };
+
+        match name {
+            "vars" => {
+                let mut variables: Vec<_> = self.evaluator.variables().iter().collect();
+                variables.sort_by(|a, b| a.0.cmp(b.0));
+
+                if variables.is_empty() {
+                    return Ok("No variables".to_string());
+                }
+                Ok(variables
+                    .iter()
+                    .map(|(name, value)| format!("{} = {}", name, value))
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            "funcs" => {
+                let mut functions: Vec<_> = self.evaluator.functions().collect();
+                functions.sort();
+
+                if functions.is_empty() {
+                    return Ok("No functions".to_string());
+                }
+                Ok(functions
+                    .iter()
+                    .map(|(name, params)| format!("{}({})", name, params.join(", ")))
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            "load" => {
+                if argument.is_empty() {
+                    return Err(anyhow!("Usage: :load <file>"));
+                }
+                let source = fs::read_to_string(argument)
+                    .map_err(|e| anyhow!("Failed to read {}: {}", argument, e))?;
+                self.run(&source)?;
+                Ok(format!("Loaded {}", argument))
+            }
+            "reset" => {
+                self.evaluator.reset();
+                Ok("Session reset".to_string())
+            }
+            "ast" => {
+                let tokens = Tokenizer::tokenize(argument.to_string())
+                    .map_err(|e| anyhow!("Failed to tokenize input: {}", e))?;
+                let ast = Parser::new(tokens).parse()
+                    .map_err(|e| anyhow!("Failed to parse tokens: {}", e))?;
+                Ok(format!("{:#?}", ast))
+            }
+            "time" => {
+                let start = Instant::now();
+                self.run(argument)?;
+                Ok(format!("Took {:?}", start.elapsed()))
+            }
+            "help" => Ok(HELP.to_string()),
+            _ => Err(anyhow!("Unknown command ':{}', type :help for the list", name)),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use serde::Serialize;
+
+// A range in the source code, lines and columns are 1-based
+// the end position is exclusive (points right after the last char).
+// Columns count chars, see width
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Span {
+    pub line: usize,
+    pub col: usize,
+    pub end_line: usize,
+    pub end_col: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, col: usize, end_line: usize, end_col: usize) -> Self {
+        Span { line, col, end_line, end_col }
+    }
+
+    // Span that starts where self starts and ends where other ends
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            line: self.line,
+            col: self.col,
+            end_line: other.end_line,
+            end_col: other.end_col,
+        }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "line {}, column {}", self.line, self.col)
+    }
+}
+
+// Columns a char takes: a tab is 4 for visual alignment, '\r' of a "\r\n" is skipped
+pub fn width(c: char) -> usize {
+    match c {
+        '\t' => 4,
+        '\r' => 0,
+        _ => 1,
+    }
+}
+
+// Byte offset of column `col` in `line`, the length of the line when it is past the end
+pub fn byte_offset(line: &str, col: usize) -> usize {
+    let mut current = 1;
+    for (offset, c) in line.char_indices() {
+        if current >= col {
+            return offset;
+        }
+        current += width(c);
+    }
+    line.len()
+}
+
+// UTF-16 code units before column `col` in `line`, how editors count positions over LSP
+pub fn utf16_offset(line: &str, col: usize) -> usize {
+    line[..byte_offset(line, col)].encode_utf16().count()
+}
+
+// Column at `units` UTF-16 code units into `line`, the inverse of utf16_offset
+pub fn col_from_utf16(line: &str, units: usize) -> usize {
+    let mut col = 1;
+    let mut counted = 0;
+    for c in line.chars() {
+        if counted >= units || c == '\n' {
+            break;
+        }
+        counted += c.len_utf16();
+        col += width(c);
+    }
+    col
+}
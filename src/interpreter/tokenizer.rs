@@ -1,30 +1,53 @@
 use anyhow::{Result, anyhow};
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
-#[derive(Debug)]
+use serde::Serialize;
+use unicode_ident::{is_xid_continue, is_xid_start};
+
+use crate::interpreter::span::{self, Span};
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Token{
     pub kind: TokenKind,
-    pub line: usize,
-    pub col: usize
+    pub span: Span,
+}
+
+impl Token {
+    // Token that lives on a single line and is `len` columns wide
+    pub fn new(kind: TokenKind, line: usize, col: usize, len: usize) -> Self {
+        Token { kind, span: Span::new(line, col, line, col + len) }
+    }
 }
 
 impl Display for Token {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        write!(f, "{:?} at {}:{}", self.kind, self.line, self.col)
+        write!(f, "{:?} at {}:{}", self.kind, self.span.line, self.span.col)
     }
 }
 
-#[derive(Debug, PartialEq)]
+// Serialized as {"type": "Number", "value": 1.0}, unit variants have no value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", content = "value")]
 pub enum TokenKind{
     //keywords
     Let,
+    Const,
     Func,
+    Fn,
     Get,
     Set,
     Where,
     Delete,
+    Import,
+    As,
+    Try,
+    Catch,
+    Finally,
+    Throw,
+    Match,
     True,
     False,
+    Null,
 
     //op
     Plus,
@@ -32,6 +55,11 @@ pub enum TokenKind{
     Multiply,
     Divide,
     Assign,
+    // compound assignment: += -= *= /=
+    PlusAssign,
+    MinusAssign,
+    MultiplyAssign,
+    DivideAssign,
 
     //compounds
     LParen,
@@ -41,8 +69,12 @@ pub enum TokenKind{
     LBracket,
     RBracket,
     Dot,
+    DotDot,
     Semicolon,
     Comma,
+    Colon,
+    Arrow,
+    FatArrow,
 
     //comparison
     Eq,
@@ -56,15 +88,68 @@ pub enum TokenKind{
     And,
     Or,
 
+    //null handling: `??` and `?.`
+    Coalesce,
+    QuestionDot,
+
     //generic
     Expr,
     Identifier(String),
-    Number(f64),
+    Number(NumberLiteral),
     String(String),
     Boolean(bool),
     EOF,
 }
 
+// A number as written in the source. It serializes and prints in Debug
+// as the plain f64, Display gives the text back for the formatter
+#[derive(Clone, Default, PartialEq, Serialize)]
+#[serde(into = "f64")]
+pub struct NumberLiteral {
+    pub value: f64,
+    pub text: String,
+}
+
+impl NumberLiteral {
+    // `-1.50` for a negative literal in a pattern
+    pub fn negated(&self) -> Self {
+        NumberLiteral { value: -self.value, text: format!("-{}", self.text) }
+    }
+}
+
+// A number computed by the optimizer, written the way Display prints an f64
+impl From<f64> for NumberLiteral {
+    fn from(value: f64) -> Self {
+        NumberLiteral { value, text: value.to_string() }
+    }
+}
+
+impl From<NumberLiteral> for f64 {
+    fn from(number: NumberLiteral) -> Self {
+        number.value
+    }
+}
+
+impl std::fmt::Debug for NumberLiteral {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{:?}", self.value)
+    }
+}
+
+impl Display for NumberLiteral {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.text)
+    }
+}
+
+// `// line` or `/* block */` comment, the text keeps its delimiters.
+// The parser never sees them, the formatter puts them back
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Comment {
+    pub text: String,
+    pub span: Span,
+}
+
 pub struct Tokenizer{
 
 }
@@ -72,13 +157,19 @@ pub struct Tokenizer{
 impl Tokenizer{
     //it takes the source code and splits in token
     pub fn tokenize(source: String) -> Result<Vec<Token>>{
+        Self::tokenize_with_comments(source).map(|(tokens, _)| tokens)
+    }
+
+    // Same as tokenize, also returns the comments in source order
+    pub fn tokenize_with_comments(source: String) -> Result<(Vec<Token>, Vec<Comment>)>{
 
         // rough estimate
         let mut tokens: Vec<Token> = Vec::with_capacity(source.len() / 4);
+        let mut comments: Vec<Comment> = Vec::new();
 
-        // we work on bytes for easier indexing/slicing
-        // this is safe as we only deal with ascii characters
-        // non-ascii will be handled in string literals and identifiers using from_utf8
+        // we work on bytes for easier indexing/slicing, i always sits on a char boundary.
+        // Non-ascii chars only appear in identifiers, string literals and comments,
+        // columns count chars (see span::width), not bytes
         let bytes = source.as_bytes();
 
         let mut i: usize = 0;
@@ -86,7 +177,8 @@ impl Tokenizer{
         let mut col: usize = 1;
 
         while i < bytes.len() {
-            match bytes[i] as char {
+            let c = source[i..].chars().next().unwrap_or_default();
+            match c {
                 ' ' => {
                     col += 1;
                     i += 1;
@@ -105,100 +197,149 @@ impl Tokenizer{
                 },
                 '&' => {
                     if i + 1 < bytes.len() && bytes[i + 1] == b'&' {
-                        tokens.push(Token { kind: TokenKind::And, line: row, col: col });
+                        tokens.push(Token::new(TokenKind::And, row, col, 2));
                         col += 2;
                         i += 2;
                     } else {
-                        return Err(anyhow!("Expected char '&'"));
+                        return Err(anyhow!("Expected char '&' at line {}, column {}", row, col + 1));
                     }
                 },
                 '|' => {
                     if i + 1 < bytes.len() && bytes[i + 1] == b'|' {
-                        tokens.push(Token { kind: TokenKind::Or, line: row, col: col });
+                        tokens.push(Token::new(TokenKind::Or, row, col, 2));
                         col += 2;
                         i += 2;
                     } else {
-                        return Err(anyhow!("Expected char '|'"));
+                        return Err(anyhow!("Expected char '|' at line {}, column {}", row, col + 1));
                     }
                 },
+                '?' => {
+                    match bytes.get(i + 1) {
+                        Some(b'?') => tokens.push(Token::new(TokenKind::Coalesce, row, col, 2)),
+                        Some(b'.') => tokens.push(Token::new(TokenKind::QuestionDot, row, col, 2)),
+                        _ => return Err(anyhow!("Expected '?' or '.' after '?' at line {}, column {}", row, col + 1)),
+                    }
+                    col += 2;
+                    i += 2;
+                },
                 '.' => {
-                    tokens.push(Token { kind: TokenKind::Dot, line: row, col: col });
-                    col += 1;   
-                    i += 1;
+                    if bytes.get(i + 1) == Some(&b'.') {
+                        tokens.push(Token::new(TokenKind::DotDot, row, col, 2));
+                        col += 2;
+                        i += 2;
+                    } else {
+                        tokens.push(Token::new(TokenKind::Dot, row, col, 1));
+                        col += 1;
+                        i += 1;
+                    }
                 },
                 '+' => {
-                    tokens.push(Token { kind: TokenKind::Plus, line: row, col: col });
-                    col += 1;
-                    i += 1;
+                    if i + 1 < bytes.len() && bytes[i + 1] == b'=' {
+                        tokens.push(Token::new(TokenKind::PlusAssign, row, col, 2));
+                        col += 2;
+                        i += 2;
+                    } else {
+                        tokens.push(Token::new(TokenKind::Plus, row, col, 1));
+                        col += 1;
+                        i += 1;
+                    }
                 },
                 '-' => {
-                    tokens.push(Token { kind: TokenKind::Minus, line: row, col: col });
-                    col += 1;
-                    i += 1;
+                    // '->' introduces a function return type
+                    if i + 1 < bytes.len() && bytes[i + 1] == b'>' {
+                        tokens.push(Token::new(TokenKind::Arrow, row, col, 2));
+                        col += 2;
+                        i += 2;
+                    } else if i + 1 < bytes.len() && bytes[i + 1] == b'=' {
+                        tokens.push(Token::new(TokenKind::MinusAssign, row, col, 2));
+                        col += 2;
+                        i += 2;
+                    } else {
+                        tokens.push(Token::new(TokenKind::Minus, row, col, 1));
+                        col += 1;
+                        i += 1;
+                    }
                 },
                 '*' => {
-                    tokens.push(Token { kind: TokenKind::Multiply, line: row, col: col });
-                    col += 1;
-                    i += 1;
+                    if i + 1 < bytes.len() && bytes[i + 1] == b'=' {
+                        tokens.push(Token::new(TokenKind::MultiplyAssign, row, col, 2));
+                        col += 2;
+                        i += 2;
+                    } else {
+                        tokens.push(Token::new(TokenKind::Multiply, row, col, 1));
+                        col += 1;
+                        i += 1;
+                    }
                 },
                 '(' => {
-                    tokens.push(Token { kind: TokenKind::LParen, line: row, col: col });
+                    tokens.push(Token::new(TokenKind::LParen, row, col, 1));
                     col += 1;
                     i += 1;
                 },
                 ')' => {
-                    tokens.push(Token { kind: TokenKind::RParen, line: row, col: col });
+                    tokens.push(Token::new(TokenKind::RParen, row, col, 1));
                     col += 1;
                     i += 1;
                 },
                 '{' => {
-                    tokens.push(Token { kind: TokenKind::LBrace, line: row, col: col });
+                    tokens.push(Token::new(TokenKind::LBrace, row, col, 1));
                     col += 1;
                     i += 1;
                 },
                 '}' => {
-                    tokens.push(Token { kind: TokenKind::RBrace, line: row, col: col });
+                    tokens.push(Token::new(TokenKind::RBrace, row, col, 1));
                     col += 1;
                     i += 1;
                 },
                 '[' => {
-                    tokens.push(Token { kind: TokenKind::LBracket, line: row, col: col });
+                    tokens.push(Token::new(TokenKind::LBracket, row, col, 1));
                     col += 1;
                     i += 1;
                 }
                 ']' => {
-                    tokens.push(Token { kind: TokenKind::RBracket, line: row, col: col });
+                    tokens.push(Token::new(TokenKind::RBracket, row, col, 1));
                     col += 1;
                     i += 1;
                 },
                 ',' => {
-                    tokens.push(Token { kind: TokenKind::Comma, line: row, col: col });
+                    tokens.push(Token::new(TokenKind::Comma, row, col, 1));
                     col += 1;
                     i += 1;
                 },
                 ';' => {
-                    tokens.push(Token { kind: TokenKind::Semicolon, line: row, col: col });
+                    tokens.push(Token::new(TokenKind::Semicolon, row, col, 1));
                     col += 1;
                     i += 1;
                 },
-                // Division, or Comments (skipped)
+                ':' => {
+                    tokens.push(Token::new(TokenKind::Colon, row, col, 1));
+                    col += 1;
+                    i += 1;
+                },
+                // Division, or Comments
                 '/' => {
+                    let (start, start_row, start_col) = (i, row, col);
+
                     if i + 1 < bytes.len() && bytes[i + 1] == b'/' {
                         i += 2;
                         col += 2;
                         while i < bytes.len() && bytes[i] != b'\n' {
+                            col += width(bytes[i]);
                             i += 1;
-                            col += 1;
                         }
                         // we do not move i, \n will be handled in the next iteration
+                        let text = source[start..i].trim_end_matches('\r').to_string();
+                        comments.push(Comment { span: Span::new(start_row, start_col, row, col), text });
                     } else if i + 1 < bytes.len() && bytes[i + 1] == b'*' {
                         i += 2;
                         col += 2;
+                        let mut closed = false;
 
-                        while i + 1 < bytes.len() {
-                            if bytes[i] == b'*' && bytes[i + 1] == b'/' {
+                        while i < bytes.len() {
+                            if bytes[i] == b'*' && i + 1 < bytes.len() && bytes[i + 1] == b'/' {
                                 i += 2;
                                 col += 2;
+                                closed = true;
                                 break;
                             }
 
@@ -207,17 +348,23 @@ impl Tokenizer{
                                 col = 1;
                                 i += 1;
                             } else {
-                                col += 1;
+                                col += width(bytes[i]);
                                 i += 1;
                             }
                         }
 
-                        if i >= bytes.len() || (i == bytes.len() - 1 && bytes[i - 1] != b'/') {
-                            return Err(anyhow!("Unterminated multi-line comment"));
+                        if !closed {
+                            return Err(anyhow!("Unterminated multi-line comment at line {}, column {}", start_row, start_col));
                         }
+
+                        comments.push(Comment { text: source[start..i].to_string(), span: Span::new(start_row, start_col, row, col) });
+                    } else if i + 1 < bytes.len() && bytes[i + 1] == b'=' {
+                        tokens.push(Token::new(TokenKind::DivideAssign, row, col, 2));
+                        col += 2;
+                        i += 2;
                     } else {
                         // Division operator
-                        tokens.push(Token { kind: TokenKind::Divide, line: row, col: col });
+                        tokens.push(Token::new(TokenKind::Divide, row, col, 1));
                         col += 1;
                         i += 1;
                     }
@@ -225,16 +372,20 @@ impl Tokenizer{
                 '=' => {
                     if i + 1 < bytes.len(){
                         if bytes[i + 1] as char == '=' {
-                            tokens.push(Token { kind: TokenKind::Eq, line: row, col: col });
+                            tokens.push(Token::new(TokenKind::Eq, row, col, 2));
+                            col += 2;
+                            i += 2;
+                        } else if bytes[i + 1] as char == '>' {
+                            tokens.push(Token::new(TokenKind::FatArrow, row, col, 2));
                             col += 2;
                             i += 2;
                         } else {
-                            tokens.push(Token { kind: TokenKind::Assign, line: row, col: col });
+                            tokens.push(Token::new(TokenKind::Assign, row, col, 1));
                             col += 1;
                             i += 1;
                         }
                     } else {
-                        tokens.push(Token { kind: TokenKind::Assign, line: row, col: col });
+                        tokens.push(Token::new(TokenKind::Assign, row, col, 1));
                         col += 1;
                         i += 1;
                     }
@@ -242,33 +393,33 @@ impl Tokenizer{
                 // Comparison operators
                 '>' => {
                     if i + 1 < bytes.len() && bytes[i + 1] as char == '=' {
-                        tokens.push(Token { kind: TokenKind::Gte, line: row, col: col });
+                        tokens.push(Token::new(TokenKind::Gte, row, col, 2));
                         col += 2;
                         i += 2;
                     } else {
-                        tokens.push(Token { kind: TokenKind::Gt, line: row, col: col });
+                        tokens.push(Token::new(TokenKind::Gt, row, col, 1));
                         col += 1;
                         i += 1;
                     }
                 },
                 '<' => {
                     if i + 1 < bytes.len() && bytes[i + 1] as char == '=' {
-                        tokens.push(Token { kind: TokenKind::Lte, line: row, col: col });
+                        tokens.push(Token::new(TokenKind::Lte, row, col, 2));
                         col += 2;
                         i += 2;
                     } else {
-                        tokens.push(Token { kind: TokenKind::Lt, line: row, col: col });
+                        tokens.push(Token::new(TokenKind::Lt, row, col, 1));
                         col += 1;
                         i += 1;
                     }
                 },
                 '!' => {
                     if i + 1 < bytes.len() && bytes[i + 1] == b'=' {
-                        tokens.push(Token { kind: TokenKind::Neq, line: row, col: col });
+                        tokens.push(Token::new(TokenKind::Neq, row, col, 2));
                         col += 2;
                         i += 2;
                     } else {
-                        return Err(anyhow!("Expected char '='"));
+                        return Err(anyhow!("Expected char '=' at line {}, column {}", row, col + 1));
                     }
                 },
 
@@ -285,7 +436,8 @@ impl Tokenizer{
                     let num = num_str.parse::<f64>()
                         .map_err(|_| anyhow!("Invalid Number: {}", num_str))?;
 
-                    tokens.push(Token { kind: TokenKind::Number(num), line: row, col: col });
+                    let number = NumberLiteral { value: num, text: num_str.to_string() };
+                    tokens.push(Token::new(TokenKind::Number(number), row, col, j - i));
                     col += j - i;
                     i = j;
                 },
@@ -314,57 +466,89 @@ impl Tokenizer{
                     }
 
                     if j >= bytes.len(){
-                        return Err(anyhow!("Unterminated string literal"))
+                        return Err(anyhow!("Unterminated string literal at line {}, column {}", row, col))
                     }
 
                     let word = &bytes[i + 1..j];
+                    // both ends are quotes, so the slice falls on char boundaries
+                    let str_lit = source[i + 1..j].to_string();
+
+                    // string literals may span several lines, track where the closing '"' lands
+                    let (mut end_row, mut end_col) = (row, col + 1);
+                    for &b in word {
+                        if b == b'\n' {
+                            end_row += 1;
+                            end_col = 1;
+                        } else {
+                            end_col += width(b);
+                        }
+                    }
+                    end_col += 1;
 
-                    let str_lit = String::from_utf8(word.to_vec())
-                        .map_err(|_| anyhow!("Invalid UTF-8 in string literal"))?;
-
-                    tokens.push(Token { kind: TokenKind::String(str_lit), line: row, col: col });
+                    tokens.push(Token { kind: TokenKind::String(str_lit), span: Span::new(row, col, end_row, end_col) });
                     // skipping final '"' 
                     // the parent "while" will spot the overflow i < bytes.len()
-                    col += j - i + 1; 
+                    row = end_row;
+                    col = end_col;
                     i = j + 1;
                 },
 
-                // Identifiers and Keywords
-                'a'..='z' | 'A'..='Z' | '_' => {
-                    let mut j = i;
-
-                    while j < bytes.len() && ((bytes[j] as char).is_alphanumeric() || bytes[j] as char == '_') {
-                        j += 1;
-                    }
+                // Identifiers and Keywords, identifiers follow UAX #31 with '_' as a start char
+                c if c == '_' || is_xid_start(c) => {
+                    let j = source[i..]
+                        .char_indices()
+                        .find(|&(_, c)| !is_xid_continue(c))
+                        .map_or(bytes.len(), |(offset, _)| i + offset);
 
-                    let word = &bytes[i..j];
+                    let word = &source[i..j];
 
                     // Check if it's a keyword
-                    let token = match word.to_ascii_lowercase().as_slice() {
-                        b"let" => Token { kind: TokenKind::Let, line: row, col: col },
-                        b"func" => Token { kind: TokenKind::Func, line: row, col: col },
-                        b"get" => Token { kind: TokenKind::Get, line: row, col: col },
-                        b"set" => Token { kind: TokenKind::Set, line: row, col: col },
-                        b"where" => Token { kind: TokenKind::Where, line: row, col: col },
-                        b"delete" => Token { kind: TokenKind::Delete, line: row, col: col },
-                        b"true" => Token { kind: TokenKind::Boolean(true), line: row, col: col },
-                        b"false" => Token { kind: TokenKind::Boolean(false), line: row, col: col },
-                        _ => {
-                            let ident_str = String::from_utf8(word.to_vec())
-                                .map_err(|_| anyhow!("Invalid UTF-8 in identifier"))?;
-                            Token { kind: TokenKind::Identifier(ident_str), line: row, col: col }
-                        },
+                    let kind = match word.to_ascii_lowercase().as_str() {
+                        "let" => TokenKind::Let,
+                        "const" => TokenKind::Const,
+                        "func" => TokenKind::Func,
+                        "get" => TokenKind::Get,
+                        "set" => TokenKind::Set,
+                        "where" => TokenKind::Where,
+                        "delete" => TokenKind::Delete,
+                        "import" => TokenKind::Import,
+                        "as" => TokenKind::As,
+                        "try" => TokenKind::Try,
+                        "catch" => TokenKind::Catch,
+                        "finally" => TokenKind::Finally,
+                        "throw" => TokenKind::Throw,
+                        "match" => TokenKind::Match,
+                        "fn" => TokenKind::Fn,
+                        "true" => TokenKind::Boolean(true),
+                        "false" => TokenKind::Boolean(false),
+                        "null" => TokenKind::Null,
+                        _ => TokenKind::Identifier(word.to_string()),
                     };
-                    tokens.push(token);
-                    col += j - i;
+                    let len = word.chars().count();
+                    tokens.push(Token::new(kind, row, col, len));
+                    col += len;
                     i = j;
                 }
-                _ => return Err(anyhow!("Unexpected char: {}", bytes[i] as char)),
+                _ => return Err(anyhow!("Unexpected char: {} at line {}, column {}", c, row, col)),
             }
         }
 
-        tokens.push(Token { kind: TokenKind::EOF, line: row, col: col });
-        return Ok(tokens);
+        tokens.push(Token::new(TokenKind::EOF, row, col, 0));
+        Ok((tokens, comments))
+    }
+}
 
+// Columns taken by one byte of the source, the bytes that continue
+// a multi-byte char take none
+fn width(byte: u8) -> usize {
+    match byte {
+        0x80..=0xBF => 0,
+        _ => span::width(byte as char),
     }
-}
\ No newline at end of file
+}
+
+// Whether `name` can be written as an identifier, keywords cannot
+pub fn is_identifier(name: &str) -> bool {
+    Tokenizer::tokenize(name.to_string())
+        .is_ok_and(|tokens| matches!(tokens.as_slice(), [first, _eof] if first.kind == TokenKind::Identifier(name.to_string())))
+}
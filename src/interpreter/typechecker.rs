@@ -0,0 +1,468 @@
+use std::collections::HashMap;
+
+use crate::interpreter::ast::{AST, Statement, StatementKind, Expr, ExprKind, BinaryOp, UnaryOp, LambdaBody, Param, PatternKind, Type, TypeAnnotation};
+use crate::interpreter::builtins::{self, GLOBALS};
+use crate::interpreter::diagnostic::Diagnostic;
+use crate::interpreter::span::Span;
+
+// Inferred type of an expression, None means it can only be known at runtime
+// (unannotated parameters, record fields, ...)
+type Inferred = Option<Type>;
+
+#[derive(Clone)]
+struct Signature {
+    params: Vec<Inferred>,
+    ret: Inferred,
+}
+
+// Operand types accepted by each binary operator, mirrors Evaluator::eval_binary_op
+fn binary_rules(op: &BinaryOp) -> &'static [(Type, Type, Type)] {
+    match op {
+        BinaryOp::Add => &[
+            (Type::Number, Type::Number, Type::Number),
+            (Type::String, Type::String, Type::String),
+        ],
+        BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => &[(Type::Number, Type::Number, Type::Number)],
+        BinaryOp::Gt | BinaryOp::Lt | BinaryOp::Gte | BinaryOp::Lte => &[(Type::Number, Type::Number, Type::Bool)],
+        BinaryOp::Eq | BinaryOp::Neq => &[
+            (Type::Number, Type::Number, Type::Bool),
+            (Type::Bool, Type::Bool, Type::Bool),
+        ],
+        BinaryOp::And | BinaryOp::Or => &[(Type::Bool, Type::Bool, Type::Bool)],
+        // any operands, see infer_binary
+        BinaryOp::Coalesce => &[],
+    }
+}
+
+fn op_symbol(op: &BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Eq => "==",
+        BinaryOp::Neq => "!=",
+        BinaryOp::Gt => ">",
+        BinaryOp::Lt => "<",
+        BinaryOp::Gte => ">=",
+        BinaryOp::Lte => "<=",
+        BinaryOp::And => "&&",
+        BinaryOp::Or => "||",
+        BinaryOp::Coalesce => "??",
+    }
+}
+
+fn type_name(ty: Inferred) -> String {
+    match ty {
+        Some(ty) => ty.to_string(),
+        None => "unknown".to_string(),
+    }
+}
+
+// Infers types where it can and reports provable mismatches:
+// operators applied to the wrong types, and values that do not match
+// a `let`, parameter or return type annotation.
+// Anything that cannot be inferred is accepted, annotations are optional.
+pub struct TypeChecker {
+    functions: HashMap<String, Signature>,
+    scopes: Vec<HashMap<String, Inferred>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl TypeChecker {
+    pub fn check(ast: &AST) -> Vec<Diagnostic> {
+        let mut checker = TypeChecker {
+            functions: HashMap::new(),
+            scopes: vec![HashMap::new()],
+            diagnostics: Vec::new(),
+        };
+
+        for name in GLOBALS {
+            checker.scopes[0].insert(name.to_string(), Some(Type::List));
+        }
+        checker.collect_signatures(&ast.statements);
+
+        for statement in &ast.statements {
+            checker.check_statement(statement);
+        }
+
+        checker.diagnostics
+    }
+
+    // Annotated signatures are known before any body is checked
+    fn collect_signatures(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            if let StatementKind::FuncDef { name, params, return_type, body } = &statement.kind {
+                let signature = Signature {
+                    params: params.iter().map(|p| p.ty.as_ref().map(|t| t.ty)).collect(),
+                    ret: return_type.as_ref().map(|t| t.ty),
+                };
+                self.functions.insert(name.clone(), signature);
+                self.collect_signatures(body);
+            }
+        }
+    }
+
+    // Returns the type of the value the statement evaluates to
+    fn check_statement(&mut self, stmt: &Statement) -> Inferred {
+        match &stmt.kind {
+            StatementKind::Let { name, ty, value, .. } => {
+                let value_ty = self.infer(value);
+                let binding_ty = match ty {
+                    Some(annotation) => {
+                        self.expect(annotation, value_ty, value.span);
+                        Some(annotation.ty)
+                    }
+                    None => value_ty,
+                };
+
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(name.clone(), binding_ty);
+                }
+                binding_ty
+            }
+            StatementKind::Assign { target, op, value } => {
+                let target_ty = self.infer(target);
+                let value_ty = self.infer(value);
+                let assigned = match op {
+                    Some(op) => self.infer_binary(op, target_ty, value_ty, stmt.span),
+                    None => value_ty,
+                };
+
+                // a variable keeps the type it was declared with
+                if let ExprKind::Identifier(name) = &target.kind
+                    && let (Some(expected), Some(found)) = (target_ty, assigned)
+                    && expected != found
+                {
+                    self.diagnostics.push(Diagnostic::error(
+                        format!("Type mismatch: cannot assign {} to '{}' of type {}", found, name, expected),
+                        value.span,
+                    ));
+                }
+                assigned
+            }
+            StatementKind::FuncDef { name, params, return_type, body } => {
+                self.check_function(name, params, return_type.as_ref(), body, stmt.span);
+                Some(Type::Unit)
+            }
+            StatementKind::Expr(expr) => self.infer(expr),
+            StatementKind::Where { condition } => {
+                self.check_condition(condition);
+                Some(Type::Unit)
+            }
+            StatementKind::Get { filter, .. } => {
+                if let Some(filter) = filter {
+                    self.check_condition(filter);
+                }
+                Some(Type::Unit)
+            }
+            StatementKind::Set { value, .. } => {
+                self.infer(value);
+                Some(Type::Unit)
+            }
+            StatementKind::Delete { .. } | StatementKind::Import { .. } => Some(Type::Unit),
+            // the value of the try block or of the catch block
+            StatementKind::Try { body, catch, finally } => {
+                let mut result = self.check_block(&body.statements);
+                if let Some(catch) = catch {
+                    // what a catch binds is always an error map
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.insert(catch.name.clone(), Some(Type::Map));
+                    }
+                    let caught = self.check_block(&catch.body.statements);
+                    if caught != result {
+                        result = None;
+                    }
+                }
+                if let Some(finally) = finally {
+                    self.check_block(&finally.statements);
+                }
+                result
+            }
+            // never produces a value
+            StatementKind::Throw { value } => {
+                self.infer(value);
+                None
+            }
+        }
+    }
+
+    // The type of the last statement, unit for an empty block
+    fn check_block(&mut self, statements: &[Statement]) -> Inferred {
+        let mut result = Some(Type::Unit);
+        for stmt in statements {
+            result = self.check_statement(stmt);
+        }
+        result
+    }
+
+    fn check_function(&mut self, name: &str, params: &[Param], return_type: Option<&TypeAnnotation>, body: &[Statement], span: Span) {
+        let mut scope = HashMap::new();
+        for param in params {
+            scope.insert(param.name.clone(), param.ty.as_ref().map(|t| t.ty));
+        }
+
+        // globals stay visible, the locals of the enclosing code do not
+        let globals = self.scopes.first().cloned().unwrap_or_default();
+        let saved_scopes = std::mem::replace(&mut self.scopes, vec![globals, scope]);
+
+        // a function returns the value of its last statement, an empty body returns unit
+        let mut result = Some(Type::Unit);
+        let mut result_span = span;
+        for stmt in body {
+            result = self.check_statement(stmt);
+            result_span = stmt.span;
+        }
+
+        self.scopes = saved_scopes;
+
+        match return_type {
+            Some(annotation) => self.expect(annotation, result, result_span),
+            None => {
+                // later callers can use the inferred return type
+                if let Some(signature) = self.functions.get_mut(name) {
+                    signature.ret = result;
+                }
+            }
+        }
+    }
+
+    // where/get conditions and match guards, only the shape of the result is checked
+    fn check_condition(&mut self, condition: &Expr) {
+        let ty = self.infer(condition);
+        if ty.is_some() && ty != Some(Type::Bool) {
+            self.diagnostics.push(Diagnostic::error(
+                format!("Type mismatch: condition must be bool, found {}", type_name(ty)),
+                condition.span,
+            ));
+        }
+    }
+
+    fn expect(&mut self, annotation: &TypeAnnotation, found: Inferred, span: Span) {
+        if let Some(found) = found
+            && found != annotation.ty
+        {
+            self.diagnostics.push(Diagnostic::error(
+                format!("Type mismatch: expected {}, found {}", annotation.ty, found),
+                span,
+            ));
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Inferred {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+            .flatten()
+    }
+
+    fn infer(&mut self, expr: &Expr) -> Inferred {
+        match &expr.kind {
+            ExprKind::Number(_) => Some(Type::Number),
+            ExprKind::String(_) => Some(Type::String),
+            ExprKind::Boolean(_) => Some(Type::Bool),
+            // null has no type of its own, it is allowed anywhere
+            ExprKind::Null => None,
+            ExprKind::Identifier(name) => self.lookup(name),
+            ExprKind::FieldAccess { field, object, .. } => {
+                let object_ty = self.infer(object);
+                if let Some(found) = object_ty
+                    && found != Type::Map
+                {
+                    self.diagnostics.push(Diagnostic::error(
+                        format!("Type mismatch: cannot access field '{}' of {}", field, found),
+                        expr.span,
+                    ));
+                }
+                None
+            }
+            ExprKind::Unary { op, operand } => {
+                let operand_ty = self.infer(operand);
+                let expected = match op {
+                    UnaryOp::Neg => Type::Number,
+                    UnaryOp::Not => Type::Bool,
+                };
+
+                if let Some(found) = operand_ty
+                    && found != expected
+                {
+                    let symbol = match op {
+                        UnaryOp::Neg => "-",
+                        UnaryOp::Not => "!",
+                    };
+                    self.diagnostics.push(Diagnostic::error(
+                        format!("Type mismatch: cannot apply '{}' to {}", symbol, found),
+                        expr.span,
+                    ));
+                }
+                Some(expected)
+            }
+            ExprKind::Binary { left, op, right } => {
+                let left_ty = self.infer(left);
+                let right_ty = self.infer(right);
+                self.infer_binary(op, left_ty, right_ty, expr.span)
+            }
+            ExprKind::Call { name, args } => {
+                let arg_types: Vec<Inferred> = args.iter().map(|arg| self.infer(arg)).collect();
+
+                // a variable holding a function has no known signature
+                if self.scopes.iter().any(|scope| scope.contains_key(name)) {
+                    return None;
+                }
+                // unknown functions and argument counts are reported by the analyzer
+                let signature = match self.functions.get(name) {
+                    Some(signature) => signature.clone(),
+                    None => {
+                        let builtin = builtins::lookup(name)?;
+                        Signature {
+                            params: builtin.params.iter().map(|(_, ty)| *ty).collect(),
+                            ret: builtin.ret,
+                        }
+                    }
+                };
+
+                for ((arg, arg_ty), param_ty) in args.iter().zip(arg_types).zip(signature.params) {
+                    if let (Some(found), Some(expected)) = (arg_ty, param_ty)
+                        && found != expected
+                    {
+                        self.diagnostics.push(Diagnostic::error(
+                            format!("Type mismatch: expected {}, found {}", expected, found),
+                            arg.span,
+                        ));
+                    }
+                }
+                signature.ret
+            }
+            ExprKind::List(items) => {
+                for item in items {
+                    self.infer(item);
+                }
+                Some(Type::List)
+            }
+            ExprKind::Map(fields) => {
+                for (_, value) in fields {
+                    self.infer(value);
+                }
+                Some(Type::Map)
+            }
+            ExprKind::Index { object, index } => {
+                let object_ty = self.infer(object);
+                let index_ty = self.infer(index);
+
+                // lists take numbers, maps take field names
+                let valid = match (object_ty, index_ty) {
+                    (Some(Type::List), index) => index.is_none_or(|t| t == Type::Number),
+                    (Some(Type::Map), index) => index.is_none_or(|t| t == Type::String),
+                    (Some(_), _) => false,
+                    (None, index) => index.is_none_or(|t| t == Type::Number || t == Type::String),
+                };
+                if !valid {
+                    self.diagnostics.push(Diagnostic::error(
+                        format!("Type mismatch: cannot index {} with {}", type_name(object_ty), type_name(index_ty)),
+                        expr.span,
+                    ));
+                }
+                // the items and fields can have any type
+                None
+            }
+            ExprKind::Apply { callee, args } => {
+                self.infer(callee);
+                for arg in args {
+                    self.infer(arg);
+                }
+                None
+            }
+            // the body sees its parameters on top of the enclosing scopes,
+            // functions have no type of their own
+            ExprKind::Lambda { params, return_type, body } => {
+                let scope = params
+                    .iter()
+                    .map(|param| (param.name.clone(), param.ty.as_ref().map(|t| t.ty)))
+                    .collect();
+                self.scopes.push(scope);
+                let (result, result_span) = match body {
+                    LambdaBody::Expr(value) => (self.infer(value), value.span),
+                    LambdaBody::Block(block) => (
+                        self.check_block(&block.statements),
+                        block.statements.last().map_or(block.span, |s| s.span),
+                    ),
+                };
+                self.scopes.pop();
+
+                if let Some(annotation) = return_type {
+                    self.expect(annotation, result, result_span);
+                }
+                None
+            }
+            // the type all the arms agree on
+            ExprKind::Match { value, arms } => {
+                let value_ty = self.infer(value);
+                let mut result = None;
+                for (i, arm) in arms.iter().enumerate() {
+                    // a pattern that is only a name binds the whole value, a rest binds a list
+                    let mut scope: HashMap<String, Inferred> = arm.pattern
+                        .bindings()
+                        .into_iter()
+                        .map(|(name, _)| (name.to_string(), None))
+                        .collect();
+                    match &arm.pattern.kind {
+                        PatternKind::Binding(name) => {
+                            scope.insert(name.clone(), value_ty);
+                        }
+                        PatternKind::List { rest: Some(rest), .. } => {
+                            if let PatternKind::Binding(name) = &rest.kind {
+                                scope.insert(name.clone(), Some(Type::List));
+                            }
+                        }
+                        _ => {}
+                    }
+                    self.scopes.push(scope);
+                    if let Some(guard) = &arm.guard {
+                        self.check_condition(guard);
+                    }
+                    let body_ty = self.infer(&arm.body);
+                    self.scopes.pop();
+
+                    if i == 0 {
+                        result = body_ty;
+                    } else if body_ty != result {
+                        result = None;
+                    }
+                }
+                result
+            }
+        }
+    }
+
+    fn infer_binary(&mut self, op: &BinaryOp, left: Inferred, right: Inferred, span: Span) -> Inferred {
+        // a value of a known type is never null, the right side is then unused
+        if *op == BinaryOp::Coalesce {
+            return left;
+        }
+
+        let candidates: Vec<Type> = binary_rules(op)
+            .iter()
+            .filter(|(l, r, _)| left.is_none_or(|t| t == *l) && right.is_none_or(|t| t == *r))
+            .map(|(_, _, result)| *result)
+            .collect();
+
+        match candidates.as_slice() {
+            [] => {
+                self.diagnostics.push(Diagnostic::error(
+                    format!(
+                        "Type mismatch: cannot apply '{}' to {} and {}",
+                        op_symbol(op),
+                        type_name(left),
+                        type_name(right)
+                    ),
+                    span,
+                ));
+                None
+            }
+            [first, rest @ ..] if rest.iter().all(|t| t == first) => Some(*first),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,655 @@
+use std::collections::BTreeMap;
+use std::io::{Write, stdout};
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::interpreter::builtins::{self, Builtin};
+use crate::interpreter::bytecode::{Binding, Function, Instr, Program};
+use crate::interpreter::evaluator::{collapse_frames, Callable, ErrorKind, Evaluator, Exit, Frame, Halt, MuError, RuntimeError, Value};
+use crate::interpreter::interrupt;
+use crate::interpreter::span::Span;
+
+// What PrepareCall or PrepareApply resolved the called function to
+enum Callee {
+    // `name` is the one shown in tracebacks
+    Function { function: Arc<Function>, captured: Vec<Option<Value>>, name: u32 },
+    Builtin(&'static Builtin),
+}
+
+impl Callee {
+    // `name` is the one the function value was called by, if any
+    fn new(callable: &Callable, name: Option<u32>) -> Result<Callee> {
+        match callable {
+            Callable::Compiled { function, captured, .. } => Ok(Callee::Function {
+                function: function.clone(),
+                captured: captured.to_vec(),
+                name: name.unwrap_or(function.name),
+            }),
+            Callable::Builtin(builtin) => Ok(Callee::Builtin(builtin)),
+            Callable::Closure(_) => Err(MuError::new(ErrorKind::TypeError, "Invalid call to a function of the tree-walker").into()),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Callee::Function { function, .. } => function.arity,
+            Callee::Builtin(builtin) => builtin.params.len(),
+        }
+    }
+}
+
+struct CallFrame {
+    function: Arc<Function>,
+    // name id the function was called by
+    name: u32,
+    ip: usize,
+    // index of slot 0 in Vm::locals
+    base: usize,
+    call_span: Span,
+}
+
+// A `try` being run, set up by PushHandler
+struct Handler {
+    target: usize,
+    finally: bool,
+    // sizes to unwind to when an error is raised
+    frames: usize,
+    stack: usize,
+    pending_calls: usize,
+    errors: usize,
+    arms: usize,
+}
+
+// What a name bound by a match arm held before, put back by EndArm
+enum Saved {
+    // index into Vm::locals
+    Local { index: usize, name: u32, value: Option<Value>, constant: bool },
+    Global { name: u32, value: Option<Value>, constant: bool },
+}
+
+// Stack VM running the bytecode produced by the Compiler.
+// It keeps the observable behaviour of the tree-walking Evaluator,
+// including its dynamic scoping: a name that is not a local of the
+// running function is looked up in the callers' locals, then in the globals.
+pub struct Vm {
+    stack: Vec<Value>,
+    // slots of every active frame, None until the `let` runs
+    locals: Vec<Option<Value>>,
+    // whether each slot of `locals` was declared with `const`
+    constant_locals: Vec<bool>,
+    frames: Vec<CallFrame>,
+    // indexed by name id
+    globals: Vec<Option<Value>>,
+    constant_globals: Vec<bool>,
+    functions: Vec<Option<Arc<Function>>>,
+    // number of initialized locals with a given name across all frames,
+    // when it is zero LoadName can go straight to the globals
+    shadowed: Vec<usize>,
+    // functions resolved by PrepareCall, waiting for their arguments
+    pending_calls: Vec<Callee>,
+    // innermost `try` last
+    handlers: Vec<Handler>,
+    // errors waiting for their finally block to end
+    errors: Vec<anyhow::Error>,
+    // match arms being run, innermost last
+    arms: Vec<Vec<Saved>>,
+    // globals set before the program runs, like the script `args`
+    predefined: Vec<(String, Value)>,
+    output: Box<dyn Write>,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Self::with_output(Box::new(stdout()))
+    }
+
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        Vm {
+            stack: Vec::new(),
+            locals: Vec::new(),
+            constant_locals: Vec::new(),
+            frames: Vec::new(),
+            globals: Vec::new(),
+            constant_globals: Vec::new(),
+            functions: Vec::new(),
+            shadowed: Vec::new(),
+            pending_calls: Vec::new(),
+            handlers: Vec::new(),
+            errors: Vec::new(),
+            arms: Vec::new(),
+            predefined: Vec::new(),
+            output,
+        }
+    }
+
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.predefined.push((name.to_string(), value));
+    }
+
+    pub fn run(mut self, program: Program) -> Result<()> {
+        let names = program.names.len();
+        self.globals = vec![None; names];
+        self.constant_globals = vec![false; names];
+        self.functions = vec![None; names];
+        self.shadowed = vec![0; names];
+
+        // a global the program never mentions has no name id and is not needed
+        for (name, value) in std::mem::take(&mut self.predefined) {
+            if let Some(id) = program.names.iter().position(|n| *n == name) {
+                self.globals[id] = Some(value);
+            }
+        }
+
+        self.frames.push(CallFrame {
+            function: program.main.clone(),
+            name: program.main.name,
+            ip: 0,
+            base: 0,
+            call_span: Span::default(),
+        });
+
+        loop {
+            match self.step(&program) {
+                Ok(true) => continue,
+                Ok(false) => return Ok(()),
+                Err(e) => {
+                    let err = self.with_traceback(e, &program);
+                    self.handle(err)?;
+                }
+            }
+        }
+    }
+
+    // Give the error to the innermost `try`, or return it when nothing catches it
+    fn handle(&mut self, err: anyhow::Error) -> Result<()> {
+        let Some(value) = Evaluator::error_value(&err) else {
+            return Err(err);
+        };
+        let Some(handler) = self.handlers.pop() else {
+            return Err(err);
+        };
+
+        // bindings first, their slots belong to the frames being dropped
+        while self.arms.len() > handler.arms {
+            self.end_arm();
+        }
+        while self.frames.len() > handler.frames {
+            self.pop_frame();
+        }
+        self.stack.truncate(handler.stack);
+        self.pending_calls.truncate(handler.pending_calls);
+        self.errors.truncate(handler.errors);
+
+        if handler.finally {
+            self.errors.push(err);
+        } else {
+            self.stack.push(value);
+        }
+        if let Some(frame) = self.frames.last_mut() {
+            frame.ip = handler.target;
+        }
+        Ok(())
+    }
+
+    // Same traceback as the tree-walker, the top level code is not a frame
+    fn with_traceback(&self, err: anyhow::Error, program: &Program) -> anyhow::Error {
+        if err.is::<RuntimeError>() || err.is::<Exit>() || err.is::<Halt>() {
+            return err;
+        }
+
+        let value = Evaluator::error_value(&err).unwrap_or(Value::Unit);
+        let trace = collapse_frames(self.frames
+            .iter()
+            .skip(1)
+            .map(|frame| Frame::new(program.names[frame.name as usize].clone(), frame.call_span)));
+
+        anyhow::Error::new(RuntimeError { message: err.to_string(), value, trace })
+    }
+
+    // Execute one instruction, returns false when the program is over
+    fn step(&mut self, program: &Program) -> Result<bool> {
+        let frame = self.frames.last_mut().expect("vm always has a frame");
+        let function = frame.function.clone();
+
+        let Some(&instr) = function.chunk.code.get(frame.ip) else {
+            // only the top level code can run off its end, functions always Return
+            return Ok(false);
+        };
+        let span = function.chunk.spans[frame.ip];
+        frame.ip += 1;
+        let base = frame.base;
+
+        match instr {
+            Instr::Constant(index) => {
+                self.stack.push(function.chunk.constants[index as usize].clone());
+            }
+            Instr::Unit => self.stack.push(Value::Unit),
+            Instr::Jump(target) => {
+                // a backward jump is a loop, it can run for ever
+                if (target as usize) < self.frames.last().map_or(0, |frame| frame.ip) {
+                    interrupt::check()?;
+                }
+                if let Some(frame) = self.frames.last_mut() {
+                    frame.ip = target as usize;
+                }
+            }
+            Instr::JumpIfNotNull(target) => {
+                if !matches!(self.stack.last(), Some(Value::Null))
+                    && let Some(frame) = self.frames.last_mut()
+                {
+                    frame.ip = target as usize;
+                }
+            }
+            Instr::Pop => {
+                self.pop();
+            }
+            Instr::LoadLocal(slot) => {
+                let value = self.locals[base + slot as usize]
+                    .clone()
+                    .ok_or_else(|| {
+                        let name = &program.names[function.locals[slot as usize] as usize];
+                        MuError::new(ErrorKind::UndefinedVariable, format!("Undefined variable: {}", name)).at(span)
+                    })?;
+                self.stack.push(value);
+            }
+            Instr::StoreLocal { slot, constant } => {
+                let value = self.peek().clone();
+                let index = base + slot as usize;
+                let name = function.locals[slot as usize] as usize;
+
+                if self.locals[index].is_some() && self.constant_locals[index] {
+                    return Err(MuError::new(ErrorKind::AssignmentError, format!("Cannot redeclare constant: {}", program.names[name])).at(span).into());
+                }
+                if self.locals[index].is_none() {
+                    self.shadowed[name] += 1;
+                }
+                self.locals[index] = Some(value);
+                self.constant_locals[index] = constant;
+            }
+            Instr::LoadName(name) => {
+                let value = self.load_name(name)
+                    .or_else(|| self.function_value(name, program))
+                    .ok_or_else(|| MuError::new(ErrorKind::UndefinedVariable, format!("Undefined variable: {}", program.names[name as usize])).at(span))?;
+                self.stack.push(value);
+            }
+            Instr::DefineGlobal { name, constant } => {
+                if self.globals[name as usize].is_some() && self.constant_globals[name as usize] {
+                    return Err(MuError::new(ErrorKind::AssignmentError, format!("Cannot redeclare constant: {}", program.names[name as usize])).at(span).into());
+                }
+                self.globals[name as usize] = Some(self.peek().clone());
+                self.constant_globals[name as usize] = constant;
+            }
+            Instr::MakeList(count) => {
+                let start = self.stack.len() - count as usize;
+                let items = self.stack.split_off(start);
+                self.stack.push(Value::List(items));
+            }
+            Instr::MakeMap(count) => {
+                let start = self.stack.len() - 2 * count as usize;
+                let mut fields = BTreeMap::new();
+                let mut pairs = self.stack.drain(start..);
+                while let (Some(Value::String(name)), Some(value)) = (pairs.next(), pairs.next()) {
+                    fields.insert(name, value);
+                }
+                drop(pairs);
+                self.stack.push(Value::Map(fields));
+            }
+            Instr::Field { name, optional } => {
+                let object = self.pop();
+                let value = Evaluator::eval_field(&object, &program.names[name as usize], optional)
+                    .map_err(|e| Evaluator::located(e, span))?;
+                self.stack.push(value);
+            }
+            Instr::Assign { name, keys, op } => {
+                let value = self.pop();
+                let keys = self.stack.split_off(self.stack.len() - keys as usize);
+                let (binding, constant) = self.binding_mut(name).ok_or_else(|| {
+                    MuError::new(ErrorKind::AssignmentError, format!("Cannot assign to undeclared variable: {}", program.names[name as usize])).at(span)
+                })?;
+                if constant {
+                    return Err(MuError::new(ErrorKind::AssignmentError, format!("Cannot assign to constant: {}", program.names[name as usize])).at(span).into());
+                }
+                let assigned = Evaluator::assign_path(binding, &keys, op.as_ref(), value)
+                    .map_err(|e| Evaluator::located(e, span))?;
+                self.stack.push(assigned);
+            }
+            Instr::Index => {
+                let index = self.pop();
+                let object = self.pop();
+                let value = Evaluator::eval_index(&object, &index)
+                    .map_err(|e| Evaluator::located(e, span))?;
+                self.stack.push(value);
+            }
+            Instr::Binary(op) => {
+                let right = self.pop();
+                let left = self.pop();
+                let value = Evaluator::eval_binary_op(&left, &op, &right)
+                    .map_err(|e| Evaluator::located(e, span))?;
+                self.stack.push(value);
+            }
+            Instr::Unary(op) => {
+                let operand = self.pop();
+                let value = Evaluator::eval_unary_op(&op, &operand)
+                    .map_err(|e| Evaluator::located(e, span))?;
+                self.stack.push(value);
+            }
+            Instr::PrintValue => {
+                let value = self.peek().to_string();
+                writeln!(self.output, "{}", value)?;
+            }
+            Instr::PrintLet { name, constant } => {
+                let value = self.peek().to_string();
+                let keyword = if constant { "Const" } else { "Let" };
+                writeln!(self.output, "{} {} = {}", keyword, program.names[name as usize], value)?;
+            }
+            Instr::PrintAssign(index) => {
+                let value = self.peek().to_string();
+                writeln!(self.output, "{} = {}", function.chunk.constants[index as usize], value)?;
+            }
+            Instr::DefineFunction(index) => {
+                let defined = program.functions[index as usize].clone();
+                let name = defined.name as usize;
+                writeln!(self.output, "Defined function: {}", program.names[name])?;
+                self.functions[name] = Some(defined);
+                self.stack.push(Value::Unit);
+            }
+            Instr::PrepareCall { name, argc } => {
+                let function_name = &program.names[name as usize];
+                // a variable holding a function, then a `func`, then a builtin
+                let callee = match self.peek_name(name) {
+                    Some(Value::Function(callable)) => Callee::new(callable, Some(name))?,
+                    _ => match &self.functions[name as usize] {
+                        Some(function) => Callee::Function { function: function.clone(), captured: Vec::new(), name },
+                        None => builtins::lookup(function_name)
+                            .map(Callee::Builtin)
+                            .ok_or_else(|| MuError::new(ErrorKind::UndefinedFunction, format!("Undefined function: {}", function_name)).at(span))?,
+                    },
+                };
+
+                if callee.arity() != argc as usize {
+                    return Err(Evaluator::arity_error(function_name, callee.arity(), argc as usize).at(span).into());
+                }
+                self.pending_calls.push(callee);
+            }
+            Instr::PrepareApply(argc) => {
+                let callable = match self.pop() {
+                    Value::Function(callable) => callable,
+                    other => return Err(Evaluator::not_callable_error(&other).at(span).into()),
+                };
+                if callable.arity() != argc as usize {
+                    let function_name = callable.name().unwrap_or("fn");
+                    return Err(Evaluator::arity_error(function_name, callable.arity(), argc as usize).at(span).into());
+                }
+                self.pending_calls.push(Callee::new(&callable, None)?);
+            }
+            Instr::Call(argc) => {
+                // so is recursion, Ctrl-C stops both
+                interrupt::check()?;
+                let args = self.stack.split_off(self.stack.len() - argc as usize);
+                match self.pending_calls.pop().expect("Call without PrepareCall") {
+                    Callee::Function { function, captured, name } => {
+                        self.push_frame(function, args, captured, name, span);
+                    }
+                    Callee::Builtin(builtin) => {
+                        let value = self.call_builtin(builtin, args, program, span)?;
+                        self.stack.push(value);
+                    }
+                }
+            }
+            Instr::MakeClosure(index) => {
+                let lambda = program.functions[index as usize].clone();
+                let captured = lambda.captures
+                    .iter()
+                    .map(|&slot| self.locals[base + slot as usize].clone())
+                    .collect();
+                self.stack.push(Value::Function(Callable::Compiled { function: lambda, name: None, captured: Arc::new(captured) }));
+            }
+            Instr::Return => {
+                let result = self.pop();
+                self.pop_frame();
+                self.stack.push(result);
+            }
+            Instr::PushHandler { target, finally } => {
+                self.handlers.push(Handler {
+                    target: target as usize,
+                    finally,
+                    frames: self.frames.len(),
+                    stack: self.stack.len(),
+                    pending_calls: self.pending_calls.len(),
+                    errors: self.errors.len(),
+                    arms: self.arms.len(),
+                });
+            }
+            Instr::PopHandler => {
+                self.handlers.pop();
+            }
+            Instr::Throw => {
+                let value = self.pop();
+                return Err(Evaluator::throw(value, span));
+            }
+            Instr::Rethrow => {
+                return Err(self.errors.pop().expect("Rethrow without error"));
+            }
+            Instr::MatchPattern { pattern, next } => {
+                let pattern = &function.chunk.patterns[pattern as usize];
+                let mut bound = Vec::new();
+                if !Evaluator::match_pattern(&pattern.pattern, self.peek(), &mut bound) {
+                    if let Some(frame) = self.frames.last_mut() {
+                        frame.ip = next as usize;
+                    }
+                    return Ok(true);
+                }
+
+                let mut saved = Vec::with_capacity(bound.len());
+                for (&binding, value) in pattern.bindings.iter().zip(bound) {
+                    match binding {
+                        Binding::Local(slot) => {
+                            let index = base + slot as usize;
+                            let name = function.locals[slot as usize];
+                            if self.locals[index].is_none() {
+                                self.shadowed[name as usize] += 1;
+                            }
+                            let value = self.locals[index].replace(value);
+                            let constant = std::mem::replace(&mut self.constant_locals[index], false);
+                            saved.push(Saved::Local { index, name, value, constant });
+                        }
+                        Binding::Global(name) => {
+                            let value = self.globals[name as usize].replace(value);
+                            let constant = std::mem::replace(&mut self.constant_globals[name as usize], false);
+                            saved.push(Saved::Global { name, value, constant });
+                        }
+                    }
+                }
+                self.arms.push(saved);
+            }
+            Instr::MatchGuard(fail) => match self.pop() {
+                Value::Boolean(true) => {}
+                Value::Boolean(false) => {
+                    if let Some(frame) = self.frames.last_mut() {
+                        frame.ip = fail as usize;
+                    }
+                }
+                other => return Err(Evaluator::guard_error(&other).at(span).into()),
+            },
+            Instr::EndArm => self.end_arm(),
+            Instr::PopUnder => {
+                let top = self.pop();
+                self.pop();
+                self.stack.push(top);
+            }
+            Instr::NoMatch => {
+                let value = self.pop();
+                return Err(Evaluator::no_match_error(&value).at(span).into());
+            }
+            Instr::NotImplemented(index) => {
+                writeln!(self.output, "{}", function.chunk.constants[index as usize])?;
+                self.stack.push(Value::Unit);
+            }
+        }
+
+        Ok(true)
+    }
+
+    // Start running `function`, its slots are the arguments, then the captured values
+    fn push_frame(&mut self, function: Arc<Function>, args: Vec<Value>, captured: Vec<Option<Value>>, name: u32, call_span: Span) {
+        let base = self.locals.len();
+        let bound = args.len() + captured.len();
+        self.locals.extend(args.into_iter().map(Some));
+        self.locals.extend(captured);
+        self.locals.resize(base + function.locals.len(), None);
+        self.constant_locals.resize(base + function.locals.len(), false);
+
+        for (slot, &local) in function.locals[..bound].iter().enumerate() {
+            if self.locals[base + slot].is_some() {
+                self.shadowed[local as usize] += 1;
+            }
+        }
+
+        self.frames.push(CallFrame { function, name, ip: 0, base, call_span });
+    }
+
+    fn call_builtin(&mut self, builtin: &'static Builtin, args: Vec<Value>, program: &Program, span: Span) -> Result<Value> {
+        self.run_builtin(builtin, args, program, span).map_err(|e| Evaluator::located(e, span))
+    }
+
+    // Like the tree-walker, a builtin called back by `map` or `filter` fails
+    // without a span, the outermost builtin call adds it
+    fn run_builtin(&mut self, builtin: &'static Builtin, args: Vec<Value>, program: &Program, span: Span) -> Result<Value> {
+        // functions given to `map` and `filter` are called back from here
+        builtin.call(args, &mut |function: &Callable, args: Vec<Value>| self.invoke(function, args, program, span))
+    }
+
+    // Run a function value to its Return, for a builtin calling it back
+    fn invoke(&mut self, callable: &Callable, args: Vec<Value>, program: &Program, span: Span) -> Result<Value> {
+        if args.len() != callable.arity() {
+            let name = callable.name().unwrap_or("fn");
+            return Err(Evaluator::arity_error(name, callable.arity(), args.len()).into());
+        }
+
+        let (function, captured) = match callable {
+            Callable::Compiled { function, captured, .. } => (function.clone(), captured.to_vec()),
+            Callable::Builtin(builtin) => return self.run_builtin(builtin, args, program, span),
+            Callable::Closure(_) => return Err(MuError::new(ErrorKind::TypeError, "Invalid call to a function of the tree-walker").into()),
+        };
+
+        let depth = self.frames.len();
+        let name = function.name;
+        self.push_frame(function, args, captured, name, span);
+
+        while self.frames.len() > depth {
+            if let Err(e) = self.step(program) {
+                let err = self.with_traceback(e, program);
+                // a `try` outside the called function is handled once the builtin returns
+                if self.handlers.last().is_some_and(|handler| handler.frames > depth) {
+                    self.handle(err)?;
+                } else {
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(self.pop())
+    }
+
+    // A `func` or a builtin named by an identifier
+    fn function_value(&self, name: u32, program: &Program) -> Option<Value> {
+        let callable = match &self.functions[name as usize] {
+            Some(function) => Callable::Compiled {
+                function: function.clone(),
+                name: Some(program.names[name as usize].clone()),
+                captured: Arc::default(),
+            },
+            None => Callable::Builtin(builtins::lookup(&program.names[name as usize])?),
+        };
+        Some(Value::Function(callable))
+    }
+
+    // Drop the innermost frame and its locals
+    fn pop_frame(&mut self) {
+        let frame = self.frames.pop().expect("vm always has a frame");
+
+        for (slot, &name) in frame.function.locals.iter().enumerate() {
+            if self.locals[frame.base + slot].is_some() {
+                self.shadowed[name as usize] -= 1;
+            }
+        }
+        self.locals.truncate(frame.base);
+        self.constant_locals.truncate(frame.base);
+    }
+
+    // Put back the names bound by the innermost match arm, last bound first
+    fn end_arm(&mut self) {
+        let saved = self.arms.pop().expect("EndArm without MatchPattern");
+        for saved in saved.into_iter().rev() {
+            match saved {
+                Saved::Local { index, name, value, constant } => {
+                    if value.is_none() && self.locals[index].is_some() {
+                        self.shadowed[name as usize] -= 1;
+                    }
+                    self.locals[index] = value;
+                    self.constant_locals[index] = constant;
+                }
+                Saved::Global { name, value, constant } => {
+                    self.globals[name as usize] = value;
+                    self.constant_globals[name as usize] = constant;
+                }
+            }
+        }
+    }
+
+    fn load_name(&self, name: u32) -> Option<Value> {
+        self.peek_name(name).cloned()
+    }
+
+    fn peek_name(&self, name: u32) -> Option<&Value> {
+        if self.shadowed[name as usize] > 0 {
+            for frame in self.frames.iter().rev() {
+                for (slot, &local) in frame.function.locals.iter().enumerate() {
+                    if local == name
+                        && let Some(value) = &self.locals[frame.base + slot]
+                    {
+                        return Some(value);
+                    }
+                }
+            }
+        }
+
+        self.globals[name as usize].as_ref()
+    }
+
+    // Same lookup as load_name, for an assignment. Also tells if the binding is a constant
+    fn binding_mut(&mut self, name: u32) -> Option<(&mut Value, bool)> {
+        if self.shadowed[name as usize] > 0 {
+            let slot = self.frames.iter().rev().find_map(|frame| {
+                frame.function.locals
+                    .iter()
+                    .enumerate()
+                    .map(|(slot, &local)| (local, frame.base + slot))
+                    .find(|&(local, slot)| local == name && self.locals[slot].is_some())
+                    .map(|(_, slot)| slot)
+            });
+            if let Some(slot) = slot {
+                let constant = self.constant_locals[slot];
+                return self.locals[slot].as_mut().map(|value| (value, constant));
+            }
+        }
+
+        let constant = self.constant_globals[name as usize];
+        self.globals[name as usize].as_mut().map(|value| (value, constant))
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("vm stack underflow")
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack.last().expect("vm stack underflow")
+    }
+}
@@ -1,6 +1,13 @@
 use clap::Parser;
+use misty_db::interpreter::Interpreter;
+use misty_db::interpreter::evaluator::Exit;
 use std::fmt::{Display, Formatter, Result as FmtResult};
-use std::io::{stdin, stdout, Write};
+use misty_db::interpreter::interrupt;
+use misty_db::interpreter::repl::ReplHelper;
+use rustyline::Editor;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use std::path::Path;
 use std::process::exit;
 
 #[derive(Parser)]
@@ -41,29 +48,76 @@ fn main() {
     println!("Type 'exit', 'quit', or 'q' to exit.");
     println!();
 
-    loop {
-        print!("misty> ");
-        stdout().flush().unwrap();
+    let mut editor: Editor<ReplHelper, DefaultHistory> = match Editor::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("Error: Failed to start the line editor: {}", e);
+            exit(1);
+        }
+    };
+    // each command runs on its own, only the keywords can be completed
+    editor.set_helper(Some(ReplHelper::new()));
+
+    // Ctrl-C while a command runs stops it and keeps the database
+    if let Err(e) = interrupt::install_handler() {
+        eprintln!("Warning: {}", e);
+    }
 
-        let mut input = String::new();
+    let history = std::env::var_os("HOME").map(|home| Path::new(&home).join(".misty_history"));
+    if let Some(path) = &history {
+        // there is no history yet on the first run
+        let _ = editor.load_history(path);
+    }
+
+    let mut exit_code = None;
 
-        stdin()
-            .read_line(&mut input)
-            .expect("Failed to read the line");
+    loop {
+        let input = match editor.readline("misty> ") {
+            Ok(input) => input,
+            Err(ReadlineError::Interrupted) => continue,
+            // Ctrl-D, nothing more will come
+            Err(ReadlineError::Eof) => {
+                println!("Goodbye!");
+                break;
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                break;
+            }
+        };
 
         let input = input.trim().to_string();
 
+        if input.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(input.as_str());
+
         if input == "exit" || input == "quit" || input == "q" {
             println!("Goodbye!");
             break;
         }
 
-        if input.is_empty() {
-            continue;
+        // database commands are Mu statements (get, set, delete, where ...)
+        interrupt::clear();
+        if let Err(e) = Interpreter::execute_full_pipeline(input) {
+            match e.downcast_ref::<Exit>() {
+                Some(request) => {
+                    exit_code = Some(request.code);
+                    break;
+                }
+                None => eprintln!("Error: {}", e),
+            }
         }
+    }
+
+    if let Some(path) = &history
+        && let Err(e) = editor.save_history(path)
+    {
+        eprintln!("Warning: Failed to save history to {}: {}", path.display(), e);
+    }
 
-        println!("Processing: {}", input);
-        // TODO: Add actual database command processing here
-        // For now, this is just a placeholder REPL
+    if let Some(code) = exit_code {
+        exit(code);
     }
 }
@@ -0,0 +1,254 @@
+// Test file for the static analysis pass
+
+use misty_db::interpreter::{Interpreter, RunOptions};
+use misty_db::interpreter::analyzer::Analyzer;
+use misty_db::interpreter::diagnostic::{Diagnostic, Severity};
+use misty_db::interpreter::parser::Parser;
+use misty_db::interpreter::tokenizer::Tokenizer;
+
+fn analyze(source: &str) -> Vec<Diagnostic> {
+    let ast = Parser::new(Tokenizer::tokenize(source.to_string()).unwrap()).parse().unwrap();
+    Analyzer::analyze(&ast)
+}
+
+fn messages(diagnostics: &[Diagnostic], severity: Severity) -> Vec<String> {
+    diagnostics
+        .iter()
+        .filter(|d| d.severity == severity)
+        .map(|d| d.message.clone())
+        .collect()
+}
+
+#[test]
+fn test_clean_program() {
+    let diagnostics = analyze(r#"
+        let base = 10;
+        func scale(x, factor) {
+            let scaled = x * factor;
+            scaled + base;
+        }
+        scale(2, 3);
+    "#);
+
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+}
+
+#[test]
+fn test_undefined_variable() {
+    let diagnostics = analyze("let a = b + 1;\nfunc f() {\n    c;\n}");
+
+    assert_eq!(
+        messages(&diagnostics, Severity::Error),
+        vec!["Undefined variable: b", "Undefined variable: c"]
+    );
+    assert_eq!(diagnostics[0].span.line, 1);
+    assert_eq!(diagnostics[1].span.line, 3);
+}
+
+#[test]
+fn test_variable_used_before_let() {
+    let diagnostics = analyze("let a = later;\nlet later = 1;");
+    assert_eq!(messages(&diagnostics, Severity::Error), vec!["Undefined variable: later"]);
+}
+
+#[test]
+fn test_functions_see_globals_declared_later() {
+    let diagnostics = analyze("func f() {\n    limit;\n}\nlet limit = 5;\nf();");
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+}
+
+#[test]
+fn test_unknown_function_and_argument_count() {
+    let diagnostics = analyze("func add(a, b) {\n    a + b;\n}\nadd(1);\nmissing(2);");
+
+    assert_eq!(
+        messages(&diagnostics, Severity::Error),
+        vec!["Function 'add' expects 2 arguments, got 1", "Undefined function: missing"]
+    );
+}
+
+#[test]
+fn test_call_before_definition() {
+    let diagnostics = analyze("f();\nfunc f() {\n    1;\n}");
+    assert_eq!(messages(&diagnostics, Severity::Error), vec!["Function 'f' is called before its definition"]);
+
+    // calls from other function bodies only run later
+    let diagnostics = analyze("func g() {\n    f();\n}\nfunc f() {\n    1;\n}\ng();");
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+}
+
+#[test]
+fn test_duplicate_parameters() {
+    let diagnostics = analyze("func f(a, a) {\n    a;\n}");
+    assert_eq!(messages(&diagnostics, Severity::Error), vec!["Duplicate parameter 'a' in function 'f'"]);
+}
+
+#[test]
+fn test_unused_variables_are_warnings() {
+    let diagnostics = analyze("func f(a, b, _c) {\n    let tmp = 1;\n    a;\n}");
+
+    assert!(messages(&diagnostics, Severity::Error).is_empty());
+    assert_eq!(
+        messages(&diagnostics, Severity::Warning),
+        vec!["Unused parameter: b", "Unused variable: tmp"]
+    );
+}
+
+#[test]
+fn test_where_refers_to_record_fields() {
+    let diagnostics = analyze("where age > 18 && status == \"active\";");
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+}
+
+#[test]
+fn test_errors_stop_before_side_effects() {
+    let source = "let a = 1 / 0;\nlet b = missing;".to_string();
+
+    // the analysis error is reported instead of the runtime one
+    let message = Interpreter::execute_full_pipeline(source.clone()).unwrap_err().to_string();
+    assert!(message.contains("Failed semantic analysis: Undefined variable: missing"), "{}", message);
+
+    let unchecked = RunOptions { check: false, ..RunOptions::default() };
+    let message = Interpreter::execute_with_options(source, &unchecked).unwrap_err().to_string();
+    assert!(message.contains("Division by zero"), "{}", message);
+}
+
+#[test]
+fn test_builtins_and_args() {
+    let diagnostics = analyze(r#"
+        let count = len(args);
+        func check(code) {
+            exit(code + len(args));
+        }
+        check(count);
+    "#);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let diagnostics = analyze("len(args, 1);\nexit();");
+    assert_eq!(messages(&diagnostics, Severity::Error), vec![
+        "Function 'len' expects 1 arguments, got 2",
+        "Function 'exit' expects 1 arguments, got 0",
+    ]);
+}
+
+#[test]
+fn test_assignments() {
+    let diagnostics = analyze(r#"
+        let total = 0;
+        let user = {name: "Ada"};
+        func add(x) {
+            total += x;
+            x = x * 2;
+            user.name = "Grace";
+        }
+        total = add(1);
+    "#);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let diagnostics = analyze("missing = 1;\nfunc f() { other[0] += 1; }\nlet items = [];\nitems[index] = 1;");
+    assert_eq!(messages(&diagnostics, Severity::Error), vec![
+        "Assignment to undeclared variable: missing",
+        "Assignment to undeclared variable: other",
+        "Undefined variable: index",
+    ]);
+}
+
+#[test]
+fn test_constants() {
+    let diagnostics = analyze(r#"
+        const LIMIT = 100;
+        func f(LIMIT) {
+            LIMIT = 1;
+        }
+        func g() {
+            let LIMIT = 2;
+            LIMIT += 1;
+        }
+        let total = LIMIT;
+    "#);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let diagnostics = analyze("const A = 1;\nA = 2;\nlet A = 3;\nfunc f() { A += 1; const B = 1; B = 2; }");
+    assert_eq!(messages(&diagnostics, Severity::Error), vec![
+        "Cannot assign to constant: A",
+        "Cannot redeclare constant: A",
+        "Cannot assign to constant: A",
+        "Cannot assign to constant: B",
+    ]);
+}
+
+#[test]
+fn test_try_catch() {
+    let diagnostics = analyze(r#"
+        try {
+            let x = 1;
+        } catch (e) {
+            e.message;
+        } finally {
+            x;
+        }
+        func f() {
+            try { throw "boom"; } catch (_ignored) {}
+            try { 1; } catch (err) { err; }
+        }
+        e;
+    "#);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let diagnostics = analyze("const E = 1;\ntry { throw missing; } catch (E) {}\nfunc f() { try { 1; } catch (unused) {} }");
+    assert_eq!(messages(&diagnostics, Severity::Error), vec![
+        "Undefined variable: missing",
+        "Cannot redeclare constant: E",
+    ]);
+    assert_eq!(messages(&diagnostics, Severity::Warning), vec!["Unused variable: unused"]);
+}
+
+#[test]
+fn test_match() {
+    let diagnostics = analyze(r#"
+        func describe(value) {
+            match value {
+                [first, .._rest] => first,
+                { name, age } if age > 18 => name,
+                _ => "other",
+            }
+        }
+        let flag = true;
+        match flag { true => 1, false => 0 };
+    "#);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let diagnostics = analyze("match 1 { x => 1, 2 => 2 };
+match 1 { 1 => 1, y if y > 1 => y };
+match [1] { [a, ..rest] => a, _ => 0 };
+rest;");
+    assert_eq!(messages(&diagnostics, Severity::Error), vec!["Undefined variable: rest"]);
+    assert_eq!(messages(&diagnostics, Severity::Warning), vec![
+        "Unused variable: x",
+        "Unreachable match arm: an earlier arm matches every value",
+        "Non-exhaustive match: no arm matches every value, add a '_' arm",
+        "Unused variable: rest",
+    ]);
+}
+
+#[test]
+fn test_lambdas() {
+    let diagnostics = analyze(r#"
+        func make_adder(n) {
+            fn(x) => x + n;
+        }
+        let double = fn(x) => x * 2;
+        double(2);
+        make_adder(1)(2);
+        map([1], len);
+        filter([1], fn(_x) { true; });
+    "#);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let diagnostics = analyze("let f = fn(a, b) => a + c;\nf(1, 2);\nfn(x, x) => x;");
+    assert_eq!(messages(&diagnostics, Severity::Error), vec![
+        "Undefined variable: c",
+        "Duplicate parameter 'x' in function 'fn'",
+    ]);
+    assert_eq!(messages(&diagnostics, Severity::Warning), vec!["Unused parameter: b", "Unused parameter: x"]);
+}
@@ -0,0 +1,83 @@
+// Test file for the mu command line: script arguments, inline code, stdin and exit codes
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn mu(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mu"))
+        .args(args)
+        // the REPL keeps its history in the home directory
+        .env("HOME", std::env::temp_dir())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn test_script_arguments() {
+    let path = std::env::temp_dir().join(format!("mu_cli_args_{}.mu", std::process::id()));
+    std::fs::write(&path, "let count = len(args);\nargs[1];\n").unwrap();
+
+    let output = mu(&["run", path.to_str().unwrap(), "--", "one", "two words", "--flag"], "");
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Let count = 3\ntwo words\n"), "{}", stdout(&output));
+}
+
+#[test]
+fn test_run_from_stdin() {
+    let output = mu(&["run", "-", "--", "x"], "let from_stdin = args[0] + \"!\";\n");
+
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Let from_stdin = x!\n"));
+}
+
+#[test]
+fn test_eval() {
+    let output = mu(&["eval", "-e", "let total = 2 * 21;"], "");
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Let total = 42\n"));
+
+    let output = mu(&["eval", "-e", "len(args);", "--", "a", "b"], "");
+    assert!(stdout(&output).contains("2\n"));
+}
+
+#[test]
+fn test_exit_code() {
+    let output = mu(&["eval", "-e", "let before = 1; exit(3); let after = 2;"], "");
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stdout(&output).contains("Let before = 1\n"));
+    assert!(!stdout(&output).contains("after"));
+    assert!(output.stderr.is_empty());
+
+    let output = mu(&["run", "-", "--vm"], "exit(0);");
+    assert_eq!(output.status.code(), Some(0));
+
+    // a failing script still exits with 1
+    let output = mu(&["eval", "-e", "let x = 1 / 0;"], "");
+    assert_eq!(output.status.code(), Some(1));
+
+    // a code that does not fit an exit status is an error, not a truncated status
+    let output = mu(&["eval", "-e", "exit(99999999999);"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("exit() expects an integer code from 0 to 255, got Number(99999999999.0)"));
+}
+
+#[test]
+fn test_exit_from_the_repl() {
+    let output = mu(&["repl"], "let a = 1;\nexit(5);\nlet b = 2;\n");
+
+    assert_eq!(output.status.code(), Some(5));
+    assert!(!stdout(&output).contains("Let b"));
+}
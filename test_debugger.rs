@@ -0,0 +1,117 @@
+// Test file for the `mu debug` step debugger
+
+use std::io::Cursor;
+
+use misty_db::interpreter::debugger::Debugger;
+use misty_db::interpreter::evaluator::Evaluator;
+use misty_db::interpreter::parser::Parser;
+use misty_db::interpreter::tokenizer::Tokenizer;
+
+mod test_support;
+
+use test_support::SharedBuffer;
+
+const SOURCE: &str = "\
+let base = 10;
+func add(a, b) {
+    let s = a + b;
+    s * 2;
+}
+func outer(x) {
+    let y = add(x, 1);
+    y + base;
+}
+outer(5);
+let done = 1;
+";
+
+// Runs SOURCE with the given debugger commands,
+// returns the debugger transcript and the error of the run
+fn debug(commands: &str) -> (String, Option<String>) {
+    let ast = Parser::new(Tokenizer::tokenize(SOURCE.to_string()).unwrap()).parse().unwrap();
+    let transcript = SharedBuffer::default();
+
+    let mut evaluator = Evaluator::with_output(Box::new(std::io::sink()));
+    evaluator.set_debug_hook(Box::new(Debugger::new(
+        SOURCE,
+        Box::new(Cursor::new(commands.to_string())),
+        Box::new(transcript.clone()),
+    )));
+
+    let result = evaluator.evaluate(ast);
+    (transcript.contents(), result.err().map(|e| e.to_string()))
+}
+
+// "Paused at ..." lines of a transcript
+fn pauses(transcript: &str) -> Vec<&str> {
+    transcript
+        .lines()
+        .filter_map(|line| line.rsplit("(mu-debug) ").next())
+        .filter(|line| line.starts_with("Paused at"))
+        .collect()
+}
+
+#[test]
+fn test_starts_paused_and_continues() {
+    let (transcript, error) = debug("c\n");
+
+    assert_eq!(pauses(&transcript), vec!["Paused at line 1 at top level: let base = 10;"]);
+    assert!(error.is_none());
+}
+
+#[test]
+fn test_breakpoints() {
+    let (transcript, _) = debug("b 3\nb 8\nc\nc\nc\n");
+
+    assert_eq!(pauses(&transcript), vec![
+        "Paused at line 1 at top level: let base = 10;",
+        "Paused at line 3 in add(): let s = a + b;",
+        "Paused at line 8 in outer(): y + base;",
+    ]);
+    assert!(transcript.contains("Breakpoint set at line 3"));
+}
+
+#[test]
+fn test_step_in_over_out() {
+    // into outer, over the call to add, then out to the top level
+    let (transcript, _) = debug("n\nn\nn\ns\ns\nn\no\nc\n");
+
+    assert_eq!(pauses(&transcript), vec![
+        "Paused at line 1 at top level: let base = 10;",
+        "Paused at line 2 at top level: func add(a, b) {",
+        "Paused at line 6 at top level: func outer(x) {",
+        "Paused at line 10 at top level: outer(5);",
+        "Paused at line 7 in outer(): let y = add(x, 1);",
+        "Paused at line 3 in add(): let s = a + b;",
+        "Paused at line 4 in add(): s * 2;",
+        "Paused at line 8 in outer(): y + base;",
+    ]);
+}
+
+#[test]
+fn test_inspect_paused_frame() {
+    let (transcript, _) = debug("b 4\nc\nlocals\nstack\np s + base\np missing\nc\n");
+
+    // only the bindings of add(), not the caller's or the globals
+    assert!(transcript.contains("(mu-debug)   a = 5\n  b = 1\n  s = 6\n(mu-debug)"), "{}", transcript);
+    assert!(transcript.contains(
+        "  line 4 in add()\n  add() called at line 7, column 13 in outer()\n  outer() called at line 10, column 1 at top level\n"
+    ), "{}", transcript);
+    assert!(transcript.contains("(mu-debug) 16\n"), "{}", transcript);
+    assert!(transcript.contains("Error: Undefined variable: missing"), "{}", transcript);
+}
+
+#[test]
+fn test_quit_stops_the_program() {
+    let (_, error) = debug("b 3\nc\nq\n");
+    assert!(error.unwrap().starts_with("Debugging session ended"));
+}
+
+#[test]
+fn test_end_of_input_runs_to_completion() {
+    let (transcript, error) = debug("b 3\n");
+
+    // the breakpoint is dropped once there is nobody to answer
+    assert_eq!(pauses(&transcript).len(), 1);
+    assert!(error.is_none());
+}
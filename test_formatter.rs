@@ -0,0 +1,189 @@
+// Test file for the `mu fmt` source formatter
+
+use misty_db::interpreter::evaluator::Evaluator;
+use misty_db::interpreter::formatter::Formatter;
+use misty_db::interpreter::parser::Parser;
+use misty_db::interpreter::tokenizer::Tokenizer;
+
+mod test_support;
+
+use test_support::SharedBuffer;
+
+fn format(source: &str) -> String {
+    Formatter::format(source).unwrap()
+}
+
+fn run(source: &str) -> (String, Option<String>) {
+    let ast = Parser::new(Tokenizer::tokenize(source.to_string()).unwrap()).parse().unwrap();
+    let buffer = SharedBuffer::default();
+    let result = Evaluator::with_output(Box::new(buffer.clone())).evaluate(ast);
+    let output = buffer.contents();
+    (output, result.err().map(|e| e.to_string()))
+}
+
+#[test]
+fn test_canonical_layout() {
+    let source = "LET x=1+2*3 ;let y : number=(x-1)-(2-3);\nfunc add(a:number,b)->number{let s=a+b; s;}\nwhere (x>1)&&(y<2||false);add(1,(2));";
+
+    assert_eq!(format(source), "\
+let x = 1 + 2 * 3;
+let y: number = x - 1 - (2 - 3);
+func add(a: number, b) -> number {
+    let s = a + b;
+    s;
+}
+where x > 1 && (y < 2 || false);
+add(1, 2);
+");
+}
+
+#[test]
+fn test_parentheses() {
+    assert_eq!(format("(1 + 2) * 3;"), "(1 + 2) * 3;\n");
+    assert_eq!(format("1 - (2 + 3);"), "1 - (2 + 3);\n");
+    assert_eq!(format("(1 - 2) + 3;"), "1 - 2 + 3;\n");
+    assert_eq!(format("-(1 + 2) - -3;"), "-(1 + 2) - -3;\n");
+    // number literals are printed as written
+    assert_eq!(format("let x = 1.0 + 2.50 * 007;"), "let x = 1.0 + 2.50 * 007;\n");
+    assert_eq!(format("match n { -1.0 => 0, 1.50 => 1 };"), "match n { -1.0 => 0, 1.50 => 1 };\n");
+    assert_eq!(format("a && b || c;"), "(a && b) || c;\n");
+    assert_eq!(format("[ 1,2 , [a+1] ,][ 0 ];"), "[1, 2, [a + 1]][0];\n");
+    assert_eq!(format("(a + b)[i - 1];"), "(a + b)[i - 1];\n");
+    assert_eq!(format("(-a)[0]; -a[0];"), "(-a)[0];\n-a[0];\n");
+    assert_eq!(format("(-a).b; util . inner.value[0];"), "(-a).b;\nutil.inner.value[0];\n");
+}
+
+#[test]
+fn test_assignments_and_maps() {
+    assert_eq!(format("x=x+1;y  +=2\nitems [0]*= 3;"), "x = x + 1;\ny += 2;\nitems[0] *= 3;\n");
+    assert_eq!(format("user . age -= 1;"), "user.age -= 1;\n");
+    assert_eq!(
+        format("let city=user ?. address?.city??( a||b ) ?? NULL;x==null;"),
+        "let city = user?.address?.city ?? a || b ?? null;\nx == null;\n"
+    );
+    assert_eq!(format("const   LIMIT:number=100"), "const LIMIT: number = 100;\n");
+    assert_eq!(
+        format("let user={ name:\"Ada\" ,\"home town\" : x, \"let\": 1,};{};"),
+        "let user = {name: \"Ada\", \"home town\": x, \"let\": 1};\n{};\n"
+    );
+}
+
+#[test]
+fn test_try_catch() {
+    let source = "try{risky( ) ;}catch(e){// log it\ne . message;}FINALLY{ }\nthrow {kind:\"Custom\"}";
+    assert_eq!(format(source), "\
+try {
+    risky();
+} catch (e) {
+    // log it
+    e.message;
+} finally {}
+throw {kind: \"Custom\"};
+");
+    assert_eq!(format("func f() { try { 1; } finally { 2; } }"), "func f() {\n    try {\n        1;\n    } finally {\n        2;\n    }\n}\n");
+}
+
+#[test]
+fn test_match() {
+    assert_eq!(
+        format("let r = MATCH x{0=>\"zero\",-1=>\"minus\",[first,..rest]=>first,[..]=>0,{name,age:a} if a>18=>name,{\"full name\":n}=>n,_=>null,};"),
+        "let r = match x { 0 => \"zero\", -1 => \"minus\", [first, ..rest] => first, [..] => 0, {name, age: a} if a > 18 => name, {\"full name\": n} => n, _ => null };\n"
+    );
+}
+
+#[test]
+fn test_imports() {
+    assert_eq!(
+        format("import   \"lib/util.mu\"   as util\nutil.greet( \"mu\" );"),
+        "import \"lib/util.mu\" as util;\nutil.greet(\"mu\");\n"
+    );
+}
+
+#[test]
+fn test_keeps_comments() {
+    let source = "\
+// header
+
+/* block
+   comment */
+let x = 1;   // trailing
+func f() {
+  // inside
+  x;
+  // end of body
+} // after
+func todo() {
+// nothing yet
+}
+let y = 1 + /* inline */ 2;
+// footer
+";
+
+    assert_eq!(format(source), "\
+// header
+
+/* block
+   comment */
+let x = 1; // trailing
+func f() {
+    // inside
+    x;
+    // end of body
+} // after
+func todo() {
+    // nothing yet
+}
+/* inline */
+let y = 1 + 2;
+// footer
+");
+}
+
+#[test]
+fn test_blank_lines_collapse() {
+    assert_eq!(format("let a = 1;\n\n\n\nlet b = 2;\nfunc f() {\n\n    a;\n\n\n    b;\n}"), "\
+let a = 1;
+
+let b = 2;
+func f() {
+    a;
+
+    b;
+}
+");
+    assert_eq!(format("func empty() {   }"), "func empty() {}\n");
+}
+
+#[test]
+fn test_idempotent() {
+    let sample = include_str!("sample_program.mu");
+    let once = format(sample);
+    assert_eq!(format(&once), once);
+}
+
+#[test]
+fn test_same_behaviour_after_formatting() {
+    // errors point at the formatted source, only the output is compared
+    let sample = include_str!("sample_program.mu");
+    assert_eq!(run(&format(sample)).0, run(sample).0);
+
+    let source = "let a = 10 - (4 - 1);\nfunc f(x) { x * (a + 1); }\nf(2) / (1 + 1);";
+    assert_eq!(run(&format(source)), run(source));
+}
+
+#[test]
+fn test_syntax_errors_are_reported() {
+    let err = Formatter::format("let = 1;").unwrap_err();
+    assert!(err.to_string().contains("Expected identifier after 'let'"), "{}", err);
+
+    let err = Formatter::format("let a = 1; /* open").unwrap_err();
+    assert!(err.to_string().contains("Unterminated multi-line comment"), "{}", err);
+}
+
+#[test]
+fn test_lambdas() {
+    assert_eq!(
+        format("let f=fn(x:number)->number=>x*2;map(items,fn (x){let y=x+1;y;});make_adder(1)(2);(fn(x)=>x)(1);g(fn(){});"),
+        "let f = fn(x: number) -> number => x * 2;\nmap(items, fn(x) { let y = x + 1; y; });\nmake_adder(1)(2);\n(fn(x) => x)(1);\ng(fn() {});\n"
+    );
+}
@@ -0,0 +1,59 @@
+// Test file for interrupting running Mu code
+// The interrupt flag is process wide, so everything runs in a single test
+
+use misty_db::interpreter::compiler::Compiler;
+use misty_db::interpreter::interrupt;
+use misty_db::interpreter::parser::Parser;
+use misty_db::interpreter::repl::Session;
+use misty_db::interpreter::tokenizer::Tokenizer;
+use misty_db::interpreter::vm::Vm;
+
+#[test]
+fn test_interrupt_stops_the_entry_and_keeps_the_session() {
+    let mut session = Session::with_output(Box::new(std::io::sink()));
+    session.execute("let kept = 1; func work(n) { let step = n + 1; step * 2; }").unwrap();
+
+    // the entry stops at its next statement
+    interrupt::interrupt();
+    let err = session.execute("let lost = work(kept);").unwrap_err();
+    assert!(err.to_string().contains("Interrupted"), "{}", err);
+
+    // an interrupt is reported once
+    session.execute("let after = work(kept);").unwrap();
+    assert_eq!(session.execute(":vars").unwrap(), "after = 4\nkept = 1");
+    assert_eq!(session.execute(":funcs").unwrap(), "work(n)");
+
+    // a try in the running code does not catch it
+    interrupt::interrupt();
+    let err = session.execute("try { work(kept); } catch (e) { let caught = e; }").unwrap_err();
+    assert!(err.to_string().contains("Interrupted"), "{}", err);
+    assert!(!session.execute(":vars").unwrap().contains("caught"));
+
+    // an interrupt arriving between entries can be dropped
+    interrupt::interrupt();
+    interrupt::clear();
+    assert!(interrupt::check().is_ok());
+    session.execute("work(1);").unwrap();
+
+    // tail calls run in a loop, a lambda body that is a single expression is stopped too
+    let sender = std::thread::spawn(|| {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        interrupt::interrupt();
+    });
+    let err = session.execute("let spin = fn(n) => spin(n + 1); spin(0);").unwrap_err();
+    sender.join().unwrap();
+    assert!(err.to_string().contains("Interrupted"), "{}", err);
+    assert!(session.execute(":vars").unwrap().contains("kept = 1"));
+
+    // the VM checks on every call, a `try` does not catch it there either
+    let source = "let spin = fn(n) => spin(n + 1); try { spin(0); } catch (e) { e; }";
+    let ast = Parser::new(Tokenizer::tokenize(source.to_string()).unwrap()).parse().unwrap();
+    let program = Compiler::compile(&ast).unwrap();
+    let sender = std::thread::spawn(|| {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        interrupt::interrupt();
+    });
+    let err = Vm::with_output(Box::new(std::io::sink())).run(program).unwrap_err();
+    sender.join().unwrap();
+    assert!(err.to_string().contains("Interrupted"), "{}", err);
+}
@@ -0,0 +1,139 @@
+// Test file for the `mu check` lint rules
+
+use misty_db::interpreter::diagnostic::Severity;
+use misty_db::interpreter::linter::{Lint, LintConfig, Linter, Rule};
+
+fn check(source: &str) -> Vec<Lint> {
+    Linter::check_source(source, &LintConfig::default())
+}
+
+fn rule_messages(lints: &[Lint], rule: Rule) -> Vec<String> {
+    lints
+        .iter()
+        .filter(|lint| lint.rule == Some(rule))
+        .map(|lint| lint.diagnostic.message.clone())
+        .collect()
+}
+
+#[test]
+fn test_clean_program() {
+    let lints = check("let base = 10;\nfunc scale(x) {\n    x * base;\n}\nscale(2);");
+    assert!(lints.is_empty(), "{:?}", lints);
+}
+
+#[test]
+fn test_unused_variables() {
+    let lints = check("let used = 1;\nlet unused = 2;\nlet _ignored = 3;\nfunc f(a, b) {\n    let tmp = a;\n    a;\n}\nf(used, 1);");
+
+    assert_eq!(
+        rule_messages(&lints, Rule::UnusedVariable),
+        vec!["Unused variable: unused", "Unused parameter: b", "Unused variable: tmp"]
+    );
+    assert_eq!(lints[0].diagnostic.span.line, 2);
+}
+
+#[test]
+fn test_globals_read_by_functions_are_used() {
+    // the function is defined before the global it reads
+    let lints = check("func f() {\n    limit;\n}\nlet limit = 3;\nf();");
+    assert!(rule_messages(&lints, Rule::UnusedVariable).is_empty(), "{:?}", lints);
+}
+
+#[test]
+fn test_match_arms() {
+    let lints = check("let v = 1;\nmatch v { _ => 0, 1 => 1 };\nmatch v { 1 => 1 };\nmatch v { [a, b] => a, _ => 0 };");
+
+    assert_eq!(rule_messages(&lints, Rule::UnreachableArm), vec!["Unreachable match arm: an earlier arm matches every value"]);
+    assert_eq!(rule_messages(&lints, Rule::NonExhaustiveMatch), vec!["Non-exhaustive match: no arm matches every value, add a '_' arm"]);
+    assert_eq!(rule_messages(&lints, Rule::UnusedVariable), vec!["Unused variable: b"]);
+    assert_eq!(lints[1].diagnostic.span.line, 3);
+}
+
+#[test]
+fn test_unused_functions() {
+    let lints = check("func used() {\n    1;\n}\nfunc never() {\n    2;\n}\nfunc recursive(n) {\n    recursive(n - 1);\n}\nfunc _helper() {\n    3;\n}\nused();");
+
+    assert_eq!(
+        rule_messages(&lints, Rule::UnusedFunction),
+        vec!["Function 'never' is never called", "Function 'recursive' is never called"]
+    );
+}
+
+#[test]
+fn test_shadowed_parameters() {
+    let lints = check("func f(x) {\n    let x = x + 1;\n    x;\n}\nf(1);");
+
+    assert_eq!(
+        rule_messages(&lints, Rule::ShadowedParameter),
+        vec!["Variable 'x' shadows a parameter with the same name"]
+    );
+    assert_eq!(lints[0].diagnostic.span.line, 2);
+}
+
+#[test]
+fn test_bool_comparisons() {
+    let lints = check("where premium == true;\nwhere false != active;\nwhere trial == false;\nwhere a == b;");
+
+    assert_eq!(
+        rule_messages(&lints, Rule::BoolComparison),
+        vec![
+            "Comparison with boolean literal: 'premium == true' can be written as 'premium'",
+            "Comparison with boolean literal: 'false != active' can be written as 'active'",
+            "Comparison with boolean literal: 'trial == false'",
+        ]
+    );
+}
+
+#[test]
+fn test_constant_conditions() {
+    let lints = check("where 1 > 2;\nwhere true || false;\nwhere age > 18;");
+
+    assert_eq!(
+        rule_messages(&lints, Rule::ConstantCondition),
+        vec!["Condition of 'where' is always false", "Condition of 'where' is always true"]
+    );
+}
+
+#[test]
+fn test_allow_and_deny() {
+    let source = "let unused = 1;\nwhere premium == true;";
+
+    let config = LintConfig {
+        allowed: [Rule::UnusedVariable].into_iter().collect(),
+        denied: [Rule::BoolComparison].into_iter().collect(),
+    };
+    let lints = Linter::check_source(source, &config);
+
+    assert_eq!(lints.len(), 1);
+    assert_eq!(lints[0].rule, Some(Rule::BoolComparison));
+    assert_eq!(lints[0].diagnostic.severity, Severity::Error);
+}
+
+#[test]
+fn test_errors_have_no_rule() {
+    let lints = check("let a = ;\nlet b = 1;");
+    assert_eq!(lints.len(), 1);
+    assert_eq!(lints[0].rule, None);
+    assert!(lints[0].diagnostic.is_error());
+
+    let lints = check("let a = missing + 1;\na;");
+    assert_eq!(lints.len(), 1);
+    assert_eq!(lints[0].diagnostic.message, "Undefined variable: missing");
+}
+
+#[test]
+fn test_rule_names() {
+    for rule in Rule::ALL {
+        assert_eq!(Rule::from_name(rule.name()), Some(rule));
+    }
+    assert_eq!(Rule::from_name("bool-comparison"), Some(Rule::BoolComparison));
+    assert_eq!(Rule::from_name("nope"), None);
+}
+
+#[test]
+fn test_functions_as_values() {
+    let lints = check("func double(x) { x * 2; }\nfunc unused() { 1; }\nlet twice = map([1], double);\nlet f = fn(x, y) => x;\nf(1, 2);\ntwice;");
+
+    assert_eq!(rule_messages(&lints, Rule::UnusedFunction), vec!["Function 'unused' is never called"]);
+    assert_eq!(rule_messages(&lints, Rule::UnusedVariable), vec!["Unused parameter: y"]);
+}
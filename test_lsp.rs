@@ -0,0 +1,213 @@
+// Test file for the editor tooling behind `mu lsp`
+
+use std::thread;
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId};
+use serde_json::{Value, json};
+
+use misty_db::interpreter::document::{CompletionKind, Document, SymbolKind};
+use misty_db::interpreter::lsp::LanguageServer;
+
+const SOURCE: &str = "\
+let rate: number = 2;
+func scale(x: number, factor) -> number {
+    let scaled = x * factor;
+    scaled * rate;
+}
+let result = scale(3, 4);
+";
+
+#[test]
+fn test_definitions() {
+    let document = Document::new(SOURCE);
+
+    // `scale` in the call on line 6
+    let definition = document.definition_at(6, 15).unwrap();
+    assert_eq!(definition.kind, SymbolKind::Function);
+    assert_eq!((definition.span.line, definition.span.col), (2, 6));
+
+    // `scaled` and `x` inside the body resolve to the local and the parameter
+    let definition = document.definition_at(4, 5).unwrap();
+    assert_eq!(definition.kind, SymbolKind::Variable);
+    assert_eq!((definition.span.line, definition.span.col), (3, 9));
+
+    let definition = document.definition_at(3, 18).unwrap();
+    assert_eq!(definition.kind, SymbolKind::Parameter);
+    assert_eq!((definition.span.line, definition.span.col), (2, 12));
+
+    // a global used in a function body
+    let definition = document.definition_at(4, 14).unwrap();
+    assert_eq!((definition.span.line, definition.span.col), (1, 5));
+
+    // numbers and keywords have no definition
+    assert!(document.definition_at(6, 21).is_none());
+    assert!(document.definition_at(1, 1).is_none());
+}
+
+#[test]
+fn test_hover() {
+    let document = Document::new(SOURCE);
+
+    assert_eq!(document.hover_at(6, 14).as_deref(), Some("func scale(x: number, factor) -> number"));
+    assert_eq!(document.hover_at(3, 22).as_deref(), Some("(parameter) factor"));
+    assert_eq!(document.hover_at(4, 14).as_deref(), Some("let rate: number"));
+}
+
+#[test]
+fn test_completions() {
+    let completions = Document::new(SOURCE).completions();
+    let labels = |kind| completions.iter().filter(|c| c.kind == kind).map(|c| c.label.as_str()).collect::<Vec<_>>();
+
+    assert!(labels(CompletionKind::Keyword).contains(&"func"));
+    assert!(labels(CompletionKind::Type).contains(&"number"));
+    assert_eq!(labels(CompletionKind::Function), vec!["len", "exit", "map", "filter", "scale"]);
+    // locals of function bodies are not offered at the top level
+    assert_eq!(labels(CompletionKind::Variable), vec!["args", "rate", "result"]);
+
+    let exit = completions.iter().find(|c| c.label == "exit").unwrap();
+    assert_eq!(exit.detail.as_deref(), Some("exit(code: number) -> unit"));
+}
+
+#[test]
+fn test_document_symbols() {
+    let document = Document::new(SOURCE);
+    let symbols = document.symbols();
+
+    let names: Vec<&str> = symbols.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, vec!["rate", "scale", "result"]);
+
+    let children: Vec<&str> = symbols[1].children.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(children, vec!["x", "factor", "scaled"]);
+    assert_eq!((symbols[1].span.line, symbols[1].span.end_line), (2, 5));
+}
+
+#[test]
+fn test_diagnostics() {
+    let document = Document::new("let a = 1;\nlet b = ;\nfunc f() {\n    a;\n}");
+    assert_eq!(document.diagnostics().len(), 1);
+    // definitions still come from the statements that parsed
+    assert_eq!(document.symbols().len(), 2);
+
+    let document = Document::new("let a = missing;");
+    assert_eq!(document.diagnostics()[0].message, "Undefined variable: missing");
+}
+
+fn request(connection: &Connection, id: i32, method: &str, params: Value) -> Value {
+    connection.sender.send(Message::Request(Request::new(RequestId::from(id), method.to_string(), params))).unwrap();
+
+    loop {
+        if let Message::Response(response) = connection.receiver.recv().unwrap() {
+            assert_eq!(response.id, RequestId::from(id));
+            return response.result.unwrap_or(Value::Null);
+        }
+    }
+}
+
+fn notify(connection: &Connection, method: &str, params: Value) {
+    connection.sender.send(Message::Notification(Notification::new(method.to_string(), params))).unwrap();
+}
+
+#[test]
+fn test_protocol() {
+    let (server, client) = Connection::memory();
+    let handle = thread::spawn(move || LanguageServer::new(server).run());
+
+    let initialized = request(&client, 1, "initialize", json!({ "capabilities": {} }));
+    assert_eq!(initialized["capabilities"]["definitionProvider"], true);
+    notify(&client, "initialized", json!({}));
+
+    let uri = "file:///tmp/script.mu";
+    notify(&client, "textDocument/didOpen", json!({
+        "textDocument": { "uri": uri, "languageId": "mu", "version": 1, "text": "let a = b;\n" }
+    }));
+
+    let Message::Notification(published) = client.receiver.recv().unwrap() else {
+        panic!("Expected diagnostics");
+    };
+    assert_eq!(published.method, "textDocument/publishDiagnostics");
+    assert_eq!(published.params["diagnostics"][0]["message"], "Undefined variable: b");
+    assert_eq!(published.params["diagnostics"][0]["range"]["start"], json!({ "line": 0, "character": 8 }));
+
+    // fixed in the editor, the diagnostics are cleared on save
+    notify(&client, "textDocument/didChange", json!({
+        "textDocument": { "uri": uri, "version": 2 },
+        "contentChanges": [{ "text": SOURCE }]
+    }));
+    notify(&client, "textDocument/didSave", json!({ "textDocument": { "uri": uri } }));
+    let Message::Notification(published) = client.receiver.recv().unwrap() else {
+        panic!("Expected diagnostics");
+    };
+    assert_eq!(published.params["diagnostics"], json!([]));
+
+    let position = json!({ "textDocument": { "uri": uri }, "position": { "line": 5, "character": 14 } });
+    let definition = request(&client, 2, "textDocument/definition", position.clone());
+    assert_eq!(definition["range"]["start"], json!({ "line": 1, "character": 5 }));
+
+    let hover = request(&client, 3, "textDocument/hover", position);
+    assert!(hover["contents"]["value"].as_str().unwrap().contains("func scale(x: number, factor) -> number"));
+
+    let symbols = request(&client, 4, "textDocument/documentSymbol", json!({ "textDocument": { "uri": uri } }));
+    assert_eq!(symbols.as_array().unwrap().len(), 3);
+
+    let completion = request(&client, 5, "textDocument/completion", json!({
+        "textDocument": { "uri": uri }, "position": { "line": 6, "character": 0 }
+    }));
+    assert!(completion.as_array().unwrap().iter().any(|item| item["label"] == "scale"));
+
+    request(&client, 6, "shutdown", Value::Null);
+    notify(&client, "exit", Value::Null);
+    handle.join().unwrap().unwrap();
+}
+
+#[test]
+fn test_bad_notification_is_logged() {
+    let (server, client) = Connection::memory();
+    let handle = thread::spawn(move || LanguageServer::new(server).run());
+    request(&client, 1, "initialize", json!({ "capabilities": {} }));
+    notify(&client, "initialized", json!({}));
+
+    notify(&client, "textDocument/didOpen", json!({ "textDocument": "missing fields" }));
+    let Message::Notification(logged) = client.receiver.recv().unwrap() else {
+        panic!("Expected a log message");
+    };
+    assert_eq!(logged.method, "window/logMessage");
+    assert_eq!(logged.params["type"], 1);
+
+    // the server keeps answering
+    let symbols = request(&client, 2, "textDocument/documentSymbol", json!({ "textDocument": { "uri": "file:///tmp/none.mu" } }));
+    assert_eq!(symbols, json!([]));
+
+    request(&client, 3, "shutdown", Value::Null);
+    notify(&client, "exit", Value::Null);
+    handle.join().unwrap().unwrap();
+}
+
+#[test]
+fn test_utf16_positions() {
+    let (server, client) = Connection::memory();
+    let handle = thread::spawn(move || LanguageServer::new(server).run());
+    request(&client, 1, "initialize", json!({ "capabilities": {} }));
+    notify(&client, "initialized", json!({}));
+
+    // the emoji is one column for Mu and two UTF-16 code units for the editor
+    let uri = "file:///tmp/emoji.mu";
+    notify(&client, "textDocument/didOpen", json!({
+        "textDocument": { "uri": uri, "languageId": "mu", "version": 1, "text": "let 名 = \"😀\"; let t = 名 + u;\n" }
+    }));
+    let Message::Notification(published) = client.receiver.recv().unwrap() else {
+        panic!("Expected diagnostics");
+    };
+    assert_eq!(published.params["diagnostics"][0]["message"], "Undefined variable: u");
+    assert_eq!(published.params["diagnostics"][0]["range"]["start"], json!({ "line": 0, "character": 26 }));
+
+    let definition = request(&client, 2, "textDocument/definition", json!({
+        "textDocument": { "uri": uri }, "position": { "line": 0, "character": 22 }
+    }));
+    assert_eq!(definition["range"], json!({
+        "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 5 }
+    }));
+
+    request(&client, 3, "shutdown", Value::Null);
+    notify(&client, "exit", Value::Null);
+    handle.join().unwrap().unwrap();
+}
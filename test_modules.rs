@@ -0,0 +1,144 @@
+// Test file for `import`: resolution, namespaced access, caching and cycles
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use misty_db::interpreter::evaluator::Evaluator;
+use misty_db::interpreter::modules::ModuleLoader;
+use misty_db::interpreter::parser::Parser;
+use misty_db::interpreter::tokenizer::Tokenizer;
+
+mod test_support;
+
+use test_support::SharedBuffer;
+
+// A directory of .mu files removed at the end of the test
+struct Project(PathBuf);
+
+impl Project {
+    fn new(name: &str, files: &[(&str, &str)]) -> Self {
+        let root = std::env::temp_dir().join(format!("mu_modules_{}_{}", name, std::process::id()));
+        for (path, source) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+        Project(root)
+    }
+
+    fn path(&self, path: &str) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// Links and runs a file, returns what it printed
+fn run(file: &Path, search_path: Vec<PathBuf>) -> Result<String> {
+    let source = fs::read_to_string(file)?;
+    let ast = Parser::new(Tokenizer::tokenize(source)?).parse()?;
+    let ast = ModuleLoader::new(search_path).link(ast, Some(file))?;
+
+    let buffer = SharedBuffer::default();
+    Evaluator::with_output(Box::new(buffer.clone())).evaluate(ast)?;
+    Ok(buffer.contents())
+}
+
+#[test]
+fn test_namespaced_access() {
+    let project = Project::new("access", &[
+        ("main.mu", "import \"lib/util.mu\" as util;\nutil.greet(\"mu\");\nutil.greeting;\n"),
+        ("lib/util.mu", "let greeting = \"hello\";\nfunc greet(name) { greeting + \" \" + name; }\n"),
+    ]);
+
+    let output = run(&project.path("main.mu"), Vec::new()).unwrap();
+    assert!(output.starts_with("Let util.greeting = hello\nDefined function: util.greet\n"), "{}", output);
+    assert!(output.ends_with("hello mu\nhello\n"), "{}", output);
+}
+
+#[test]
+fn test_nested_imports_are_relative_to_the_importing_file() {
+    let project = Project::new("nested", &[
+        ("main.mu", "import \"lib/text.mu\" as text;\ntext.shout(\"hi\");\ntext.base.suffix;\n"),
+        ("lib/text.mu", "import \"base.mu\" as base;\nfunc shout(s) { s + base.suffix; }\n"),
+        ("lib/base.mu", "let suffix = \"!\";\n"),
+    ]);
+
+    let output = run(&project.path("main.mu"), Vec::new()).unwrap();
+    assert!(output.contains("Let text.base.suffix = !\n"), "{}", output);
+    assert!(output.ends_with("hi!\n!\n"), "{}", output);
+}
+
+#[test]
+fn test_locals_shadow_module_variables() {
+    let project = Project::new("shadow", &[
+        ("main.mu", "import \"util.mu\" as util;\nutil.pick(2);\n"),
+        ("util.mu", "let value = 1;\nfunc pick(value) { value; }\n"),
+    ]);
+
+    let output = run(&project.path("main.mu"), Vec::new()).unwrap();
+    assert!(output.ends_with("2\n"), "{}", output);
+}
+
+#[test]
+fn test_search_path() {
+    let project = Project::new("search", &[
+        ("app/main.mu", "import \"util.mu\" as util;\nutil.answer();\n"),
+        ("shared/util.mu", "func answer() { 42; }\n"),
+    ]);
+
+    let output = run(&project.path("app/main.mu"), vec![project.path("shared")]).unwrap();
+    assert!(output.ends_with("42\n"), "{}", output);
+
+    let err = run(&project.path("app/main.mu"), Vec::new()).unwrap_err();
+    assert_eq!(err.to_string(), "Failed to import \"util.mu\" at line 1, column 1: Module not found");
+}
+
+#[test]
+fn test_module_is_defined_once() {
+    let project = Project::new("once", &[
+        ("main.mu", "import \"util.mu\" as util;\nimport \"util.mu\" as util;\nimport \"util.mu\" as other;\n"),
+        ("util.mu", "let loaded = true;\n"),
+    ]);
+
+    // every alias refers to the module linked by the first import
+    let output = run(&project.path("main.mu"), Vec::new()).unwrap();
+    assert_eq!(output, "Let util.loaded = true\n");
+}
+
+#[test]
+fn test_diamond_import_shares_one_module() {
+    let project = Project::new("diamond", &[
+        ("main.mu", "import \"a.mu\" as a;\nimport \"b.mu\" as b;\nimport \"c.mu\" as c2;\na.c.bump();\nb.bump_c();\nc2.bump();\nc2.counter;\na.c.counter == b.c.counter;\n"),
+        ("a.mu", "import \"c.mu\" as c;\n"),
+        ("b.mu", "import \"c.mu\" as c;\nfunc bump_c() { c.bump(); }\n"),
+        ("c.mu", "let counter = 0;\nfunc bump() { counter += 1; }\n"),
+    ]);
+
+    let output = run(&project.path("main.mu"), Vec::new()).unwrap();
+    assert_eq!(output.matches("Let a.c.counter = 0").count(), 1, "{}", output);
+    assert!(!output.contains("b.c.counter") && !output.contains("c2.counter"), "{}", output);
+    assert!(output.ends_with("3\ntrue\n"), "{}", output);
+}
+
+#[test]
+fn test_import_cycle() {
+    let project = Project::new("cycle", &[
+        ("a.mu", "import \"b.mu\" as b;\n"),
+        ("b.mu", "import \"a.mu\" as a;\n"),
+    ]);
+
+    let err = run(&project.path("a.mu"), Vec::new()).unwrap_err().to_string();
+    let a = project.path("a.mu").canonicalize().unwrap();
+    let b = project.path("b.mu").canonicalize().unwrap();
+    assert!(
+        err.ends_with(&format!("Import cycle: {} -> {} -> {}", a.display(), b.display(), a.display())),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,162 @@
+// Test file for constant folding and dead code elimination
+
+use misty_db::interpreter::ast::{AST, ExprKind, StatementKind};
+use misty_db::interpreter::evaluator::Evaluator;
+use misty_db::interpreter::optimizer::Optimizer;
+use misty_db::interpreter::parser::Parser;
+use misty_db::interpreter::tokenizer::Tokenizer;
+
+mod test_support;
+
+use test_support::SharedBuffer;
+
+fn parse(source: &str) -> AST {
+    Parser::new(Tokenizer::tokenize(source.to_string()).unwrap()).parse().unwrap()
+}
+
+fn run(ast: AST) -> (String, Option<String>) {
+    let buffer = SharedBuffer::default();
+    let result = Evaluator::with_output(Box::new(buffer.clone())).evaluate(ast);
+    let output = buffer.contents();
+    (output, result.err().map(|e| e.to_string()))
+}
+
+// value of the first `let`
+fn first_value(ast: &AST) -> &ExprKind {
+    match &ast.statements[0].kind {
+        StatementKind::Let { value, .. } => &value.kind,
+        other => panic!("Expected let statement, found {:?}", other),
+    }
+}
+
+#[test]
+fn test_folds_arithmetic() {
+    let ast = Optimizer::optimize(parse("let result = (10 + 5) * 2 - 8 / 4;"));
+    assert!(matches!(first_value(&ast), ExprKind::Number(n) if n.value == 28.0));
+}
+
+#[test]
+fn test_folds_strings_and_booleans() {
+    let ast = Optimizer::optimize(parse("let s = \"Hello, \" + \"Mu\";"));
+    assert!(matches!(first_value(&ast), ExprKind::String(s) if s == "Hello, Mu"));
+
+    let ast = Optimizer::optimize(parse("let b = 1 < 2 && (3 == 3 || false);"));
+    assert!(matches!(first_value(&ast), ExprKind::Boolean(true)));
+}
+
+#[test]
+fn test_folds_null() {
+    let ast = Optimizer::optimize(parse("let n = null ?? 1 + 1;"));
+    assert!(matches!(first_value(&ast), ExprKind::Number(n) if n.value == 2.0));
+
+    // the right side is never evaluated, it is dropped even when it is not a literal
+    let ast = Optimizer::optimize(parse("let n = \"a\" ?? f(1 / 0);"));
+    assert!(matches!(first_value(&ast), ExprKind::String(s) if s == "a"));
+
+    let ast = Optimizer::optimize(parse("let b = null == 1;"));
+    assert!(matches!(first_value(&ast), ExprKind::Boolean(false)));
+}
+
+#[test]
+fn test_folds_inside_larger_expressions() {
+    let ast = Optimizer::optimize(parse("let x = 1;\nlet y = x * (2 + 3);"));
+
+    match &ast.statements[1].kind {
+        StatementKind::Let { value, .. } => match &value.kind {
+            ExprKind::Binary { right, .. } => assert!(matches!(&right.kind, ExprKind::Number(n) if n.value == 5.0)),
+            other => panic!("Expected binary expression, found {:?}", other),
+        },
+        other => panic!("Expected let statement, found {:?}", other),
+    }
+}
+
+#[test]
+fn test_simplifies_boolean_identities() {
+    let ast = Optimizer::optimize(parse("let a = 1;\nlet b = true && a > 0;"));
+    match &ast.statements[1].kind {
+        StatementKind::Let { value, .. } => {
+            assert!(matches!(value.kind, ExprKind::Binary { op: misty_db::interpreter::ast::BinaryOp::Gt, .. }))
+        }
+        other => panic!("Expected let statement, found {:?}", other),
+    }
+
+    // `flag` could be anything, `true && flag` fails at runtime when it is not a bool
+    let ast = Optimizer::optimize(parse("let flag = 1;\nlet b = true && flag;"));
+    match &ast.statements[1].kind {
+        StatementKind::Let { value, .. } => {
+            assert!(matches!(value.kind, ExprKind::Binary { op: misty_db::interpreter::ast::BinaryOp::And, .. }))
+        }
+        other => panic!("Expected let statement, found {:?}", other),
+    }
+}
+
+#[test]
+fn test_keeps_runtime_errors() {
+    let source = "let a = 1;\nlet b = 10 / (5 - 5);\nlet c = 3;";
+    let ast = Optimizer::optimize(parse(source));
+
+    // the division stays, the statement after it can never run
+    assert_eq!(ast.statements.len(), 2);
+
+    let (_, error) = run(ast);
+    assert_eq!(error.as_deref(), Some("Division by zero at line 2, column 9"));
+}
+
+#[test]
+fn test_removes_unreachable_statements_in_functions() {
+    let ast = Optimizer::optimize(parse("func f() {\n    let a = 1 + true;\n    a;\n    2;\n}\nlet after = 1;"));
+
+    assert_eq!(ast.statements.len(), 2);
+    match &ast.statements[0].kind {
+        StatementKind::FuncDef { body, .. } => assert_eq!(body.len(), 1),
+        other => panic!("Expected function definition, found {:?}", other),
+    }
+}
+
+#[test]
+fn test_propagates_constants() {
+    let ast = Optimizer::optimize(parse("const RATE = 2;\nconst DOUBLE = RATE * 3;\nfunc f() {\n    DOUBLE + 1;\n}"));
+
+    match &ast.statements[1].kind {
+        StatementKind::Let { value, .. } => assert!(matches!(&value.kind, ExprKind::Number(n) if n.value == 6.0)),
+        other => panic!("Expected const statement, found {:?}", other),
+    }
+    match &ast.statements[2].kind {
+        StatementKind::FuncDef { body, .. } => {
+            assert!(matches!(&body[0].kind, StatementKind::Expr(e) if matches!(&e.kind, ExprKind::Number(n) if n.value == 7.0)))
+        }
+        other => panic!("Expected function definition, found {:?}", other),
+    }
+
+    // a parameter with the same name could be what a use sees at runtime
+    let ast = Optimizer::optimize(parse("const LIMIT = 10;\nLIMIT;\nfunc f(LIMIT) {\n    g();\n}"));
+    assert!(matches!(&ast.statements[1].kind, StatementKind::Expr(e) if matches!(e.kind, ExprKind::Identifier(_))));
+
+    // a `let` is not a constant, and nothing is known before the declaration
+    let ast = Optimizer::optimize(parse("let a = 1;\na;\nb;\nconst b = 2;"));
+    assert!(matches!(&ast.statements[1].kind, StatementKind::Expr(e) if matches!(e.kind, ExprKind::Identifier(_))));
+    assert!(matches!(&ast.statements[2].kind, StatementKind::Expr(e) if matches!(e.kind, ExprKind::Identifier(_))));
+}
+
+#[test]
+fn test_same_behaviour_as_unoptimized() {
+    let sources = [
+        "let r = (10 + 5) * 2 - 8 / 4;\nlet s = \"a\" + \"b\";\nr;\ns;",
+        "func f(x) {\n    x * (2 + 3);\n}\nf(4);\nlet ok = true && f(1) > 2;",
+        "func g(x) {\n    x;\n}\ng(1);\nlet bad = g(2) + (1 / 0);\ng(3);",
+        "let t = true && 5;",
+        "let u = -(2 * 3);",
+        "const K = 4;\nfunc h() {\n    K * 2;\n}\nh();\nK = 5;",
+        "const N = 1;\nfunc f(N) {\n    g();\n}\nfunc g() {\n    N;\n}\nf(2);",
+        "let m = {a: 1};\nlet x = m.b ?? null ?? 2;\nlet y = null ?? m.a;\nm?.a == null;",
+        "try {\n    1 / 0;\n    \"unreachable\";\n} catch (e) {\n    e.kind;\n}\n\"after\";\nthrow 1 + 1;\n\"unreachable\";",
+        "const N = 2;\nmatch N * 2 { 4 => N + 1, _ => 0 };\nmatch 5 { N => N * 2 };\nmatch [N] { [n, ..] if n == N => n, _ => 0 };",
+        "let x = 5;\nlet a = x ?? (1 / 0);\na;\nlet b = 7;\nb;",
+    ];
+
+    for source in sources {
+        let expected = run(parse(source));
+        let actual = run(Optimizer::optimize(parse(source)));
+        assert_eq!(expected, actual, "optimizer changed the behaviour of:\n{}", source);
+    }
+}
@@ -0,0 +1,122 @@
+// Test file for parser error recovery
+
+use misty_db::interpreter::Interpreter;
+use misty_db::interpreter::ast::StatementKind;
+use misty_db::interpreter::parser::Parser;
+use misty_db::interpreter::tokenizer::Tokenizer;
+
+fn parse(source: &str) -> (Vec<StatementKind>, Vec<String>) {
+    let tokens = Tokenizer::tokenize(source.to_string()).unwrap();
+    let (ast, diagnostics) = Parser::new(tokens).parse_with_diagnostics();
+
+    let statements = ast.statements.into_iter().map(|s| s.kind).collect();
+    let messages = diagnostics.iter().map(|d| d.to_string()).collect();
+    (statements, messages)
+}
+
+#[test]
+fn test_collects_all_errors() {
+    let (statements, errors) = parse("let a = ;\nlet b = 2;\nlet c = 3 +;\nlet d = 4;");
+
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(errors[0].contains("line 1, column 9"));
+    assert!(errors[1].contains("line 3, column 12"));
+
+    // the valid statements are still in the partial AST
+    assert_eq!(statements.len(), 2);
+    assert!(matches!(&statements[0], StatementKind::Let { name, .. } if name == "b"));
+    assert!(matches!(&statements[1], StatementKind::Let { name, .. } if name == "d"));
+}
+
+#[test]
+fn test_recovers_inside_function_body() {
+    let (statements, errors) = parse("func f() {\n    let x = (1 + ;\n    x;\n}\nlet y = 1;");
+
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(statements.len(), 2);
+    match &statements[0] {
+        StatementKind::FuncDef { body, .. } => assert_eq!(body.len(), 1),
+        other => panic!("Expected function definition, found {:?}", other),
+    }
+}
+
+#[test]
+fn test_skips_broken_function_header() {
+    let (statements, errors) = parse("func f(a b) {\n    a;\n}\nlet y = 1;");
+
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(statements.len(), 1);
+    assert!(matches!(&statements[0], StatementKind::Let { name, .. } if name == "y"));
+}
+
+#[test]
+fn test_reports_stray_closing_brace() {
+    let (statements, errors) = parse("let a = 1;\n}\nlet b = 2;");
+
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(errors[0].contains("line 2, column 1"));
+    assert_eq!(statements.len(), 2);
+}
+
+#[test]
+fn test_unterminated_function_body() {
+    let (_, errors) = parse("func f() {\n    let x = 1;\n");
+
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(errors[0].contains("Expected '}' at end of function body"));
+}
+
+#[test]
+fn test_try_statements() {
+    let (statements, errors) = parse("try {\n    let x = (1 + ;\n    x;\n} catch (e) {\n    e;\n}\ntry { 1; }\nlet y = 1;");
+
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(errors[1].starts_with("Expected 'catch' or 'finally' after the try block"), "{:?}", errors);
+    assert_eq!(statements.len(), 2);
+    match &statements[0] {
+        StatementKind::Try { body, catch: Some(catch), finally: None } => {
+            assert_eq!(body.statements.len(), 1);
+            assert_eq!(catch.name, "e");
+        }
+        other => panic!("Expected try statement, found {:?}", other),
+    }
+
+    let (_, errors) = parse("try { 1; } catch e { e; }");
+    assert!(errors[0].starts_with("Expected '(' after 'catch'"), "{:?}", errors);
+}
+
+#[test]
+fn test_match_errors() {
+    let (_, errors) = parse("match 1 { 1 -> 2 };\nlet ok = 1;");
+    assert!(errors[0].starts_with("Expected '=>' after the pattern"), "{:?}", errors);
+
+    let (_, errors) = parse("match [1] { [..rest, x] => x };");
+    assert!(errors[0].starts_with("Expected ']' after the rest pattern"), "{:?}", errors);
+
+    let (_, errors) = parse("match 1 { 1 + 2 => 3 };");
+    assert!(errors[0].starts_with("Expected '=>' after the pattern"), "{:?}", errors);
+
+    let (_, errors) = parse("match 1 { (1) => 3 };");
+    assert!(errors[0].starts_with("Expected a pattern"), "{:?}", errors);
+}
+
+#[test]
+fn test_pipeline_reports_every_error_without_running() {
+    let err = Interpreter::execute_full_pipeline("let a = ;\nlet b = 1 / 0;\nlet c = ;".to_string()).unwrap_err();
+    let message = err.to_string();
+
+    assert!(message.contains("2 syntax errors"), "{}", message);
+    assert!(!message.contains("Division by zero"), "{}", message);
+}
+
+#[test]
+fn test_lambda_errors() {
+    let (_, errors) = parse("let f = fn x => x;\nlet ok = 1;");
+    assert!(errors[0].starts_with("Expected '(' after 'fn'"), "{:?}", errors);
+
+    let (_, errors) = parse("let f = fn(x) -> x;");
+    assert!(errors[0].starts_with("Unknown type 'x'"), "{:?}", errors);
+
+    let (_, errors) = parse("let f = fn(x) x;");
+    assert!(errors[0].starts_with("Expected '=>' or '{' after the lambda parameters"), "{:?}", errors);
+}
@@ -0,0 +1,133 @@
+// Test file for the REPL session and multi-line input
+
+use misty_db::interpreter::repl::{Session, is_complete};
+
+mod test_support;
+
+use test_support::SharedBuffer;
+
+fn session() -> (Session, SharedBuffer) {
+    let output = SharedBuffer::default();
+    (Session::with_output(Box::new(output.clone())), output)
+}
+
+#[test]
+fn test_is_complete() {
+    assert!(is_complete("let x = 1;"));
+    assert!(is_complete(""));
+    assert!(!is_complete("func add(a, b) {"));
+    assert!(!is_complete("func add(a, b) {\n    a + b;"));
+    assert!(is_complete("func add(a, b) {\n    a + b;\n}"));
+    assert!(!is_complete("add(1,"));
+    assert!(!is_complete("let s = \"first line"));
+    assert!(!is_complete("/* still"));
+
+    // the parser reports stray closing brackets
+    assert!(is_complete("}"));
+}
+
+#[test]
+fn test_definitions_persist_between_entries() {
+    let (mut session, output) = session();
+
+    session.execute("let x = 2;").unwrap();
+    session.execute("func double(n) {\n    n * 2;\n}").unwrap();
+    session.execute("double(x);").unwrap();
+
+    let output = output.contents();
+    assert_eq!(output, "Let x = 2\nDefined function: double\n4\n4\n");
+}
+
+#[test]
+fn test_vars_and_funcs() {
+    let (mut session, _) = session();
+    assert_eq!(session.execute(":vars").unwrap(), "No variables");
+    assert_eq!(session.execute(":funcs").unwrap(), "No functions");
+
+    session.execute("let b = \"two\"; let a = 1;").unwrap();
+    session.execute("func add(x, y) { x + y; } func zero() { 0; }").unwrap();
+
+    assert_eq!(session.execute(":vars").unwrap(), "a = 1\nb = two");
+    assert_eq!(session.execute(":funcs").unwrap(), "add(x, y)\nzero()");
+}
+
+#[test]
+fn test_reset() {
+    let (mut session, _) = session();
+    session.execute("let a = 1; func f() { 1; }").unwrap();
+
+    assert_eq!(session.execute(":reset").unwrap(), "Session reset");
+    assert_eq!(session.execute(":vars").unwrap(), "No variables");
+    assert!(session.execute("a;").unwrap_err().to_string().contains("Undefined variable: a"));
+}
+
+#[test]
+fn test_load() {
+    let path = std::env::temp_dir().join(format!("mu_repl_load_{}.mu", std::process::id()));
+    std::fs::write(&path, "func square(n) {\n    n * n;\n}\nlet four = square(2);\n").unwrap();
+
+    let (mut session, _) = session();
+    let message = session.execute(&format!(":load {}", path.display())).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(message.starts_with("Loaded "));
+    assert_eq!(session.execute(":vars").unwrap(), "four = 4");
+    assert!(session.execute(":load /no/such/file.mu").is_err());
+}
+
+#[test]
+fn test_ast_and_time() {
+    let (mut session, output) = session();
+
+    let ast = session.execute(":ast let y = 1 + 2;").unwrap();
+    assert!(ast.contains("Let") && ast.contains("Binary"), "{}", ast);
+    // :ast does not run the code
+    assert_eq!(session.execute(":vars").unwrap(), "No variables");
+
+    let timing = session.execute(":time let y = 1 + 2;").unwrap();
+    assert!(timing.starts_with("Took "), "{}", timing);
+    assert_eq!(session.execute(":vars").unwrap(), "y = 3");
+    assert_eq!(output.contents(), "Let y = 3\n");
+}
+
+#[test]
+fn test_unknown_command() {
+    let (mut session, _) = session();
+    let err = session.execute(":nope").unwrap_err();
+    assert_eq!(err.to_string(), "Unknown command ':nope', type :help for the list");
+}
+
+#[test]
+fn test_highlight() {
+    use misty_db::interpreter::repl::highlight;
+
+    assert_eq!(
+        highlight("let s = \"hi\"; // note"),
+        "\x1b[1;35mlet\x1b[0m s = \x1b[32m\"hi\"\x1b[0m; \x1b[90m// note\x1b[0m"
+    );
+    assert_eq!(highlight("f(2, true)"), "f(\x1b[33m2\x1b[0m, \x1b[1;35mtrue\x1b[0m)");
+    assert_eq!(
+        highlight("let café = \"☕\"; 1"),
+        "\x1b[1;35mlet\x1b[0m café = \x1b[32m\"☕\"\x1b[0m; \x1b[33m1\x1b[0m"
+    );
+
+    // a string still being typed is left alone
+    assert_eq!(highlight("let s = \"unfinished"), "let s = \"unfinished");
+}
+
+#[test]
+fn test_complete() {
+    use misty_db::interpreter::repl::complete;
+
+    let (mut session, _) = session();
+    session.execute("let total = 1; func twice(n) { n * 2; }").unwrap();
+    let names = session.names();
+
+    assert_eq!(
+        complete("t", 1, &names),
+        (0, vec!["throw".to_string(), "total".to_string(), "true".to_string(), "try".to_string(), "twice".to_string()])
+    );
+    assert_eq!(complete("let x = tw", 10, &names), (8, vec!["twice".to_string()]));
+    assert_eq!(complete("de", 2, &names), (0, vec!["delete".to_string()]));
+    assert_eq!(complete("zzz", 3, &names), (0, Vec::<String>::new()));
+}
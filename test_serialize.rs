@@ -0,0 +1,69 @@
+// Test file for the JSON form of tokens and syntax trees
+
+use serde_json::{Value, json};
+
+use misty_db::interpreter::parser::Parser;
+use misty_db::interpreter::tokenizer::Tokenizer;
+
+fn ast_json(source: &str) -> Value {
+    let ast = Parser::new(Tokenizer::tokenize(source.to_string()).unwrap()).parse().unwrap();
+    serde_json::to_value(&ast).unwrap()
+}
+
+#[test]
+fn test_tokens() {
+    let tokens = Tokenizer::tokenize("let x = 1.5;".to_string()).unwrap();
+    let value = serde_json::to_value(&tokens).unwrap();
+
+    assert_eq!(value[0], json!({
+        "kind": { "type": "Let" },
+        "span": { "line": 1, "col": 1, "end_line": 1, "end_col": 4 }
+    }));
+    assert_eq!(value[1]["kind"], json!({ "type": "Identifier", "value": "x" }));
+    assert_eq!(value[3]["kind"], json!({ "type": "Number", "value": 1.5 }));
+    assert_eq!(value[5]["kind"], json!({ "type": "EOF" }));
+}
+
+#[test]
+fn test_expressions() {
+    let value = ast_json("-a + f(\"s\", true);");
+    let expr = &value["statements"][0]["kind"]["value"];
+
+    assert_eq!(value["statements"][0]["kind"]["type"], "Expr");
+    assert_eq!(expr["kind"]["type"], "Binary");
+    assert_eq!(expr["kind"]["value"]["op"], "Add");
+
+    let left = &expr["kind"]["value"]["left"]["kind"];
+    assert_eq!(left["type"], "Unary");
+    assert_eq!(left["value"]["op"], "Neg");
+    assert_eq!(left["value"]["operand"]["kind"], json!({ "type": "Identifier", "value": "a" }));
+
+    let call = &expr["kind"]["value"]["right"]["kind"];
+    assert_eq!(call["type"], "Call");
+    assert_eq!(call["value"]["name"], "f");
+    assert_eq!(call["value"]["args"][0]["kind"], json!({ "type": "String", "value": "s" }));
+    assert_eq!(call["value"]["args"][1]["kind"], json!({ "type": "Boolean", "value": true }));
+}
+
+#[test]
+fn test_statements() {
+    let value = ast_json("let x: number = 1;\nfunc f(a: string, b) -> bool {\n    true;\n}\nwhere age > 1;");
+    let statements = &value["statements"];
+
+    let let_stmt = &statements[0]["kind"];
+    assert_eq!(let_stmt["type"], "Let");
+    assert_eq!(let_stmt["value"]["name"], "x");
+    assert_eq!(let_stmt["value"]["ty"]["ty"], "number");
+
+    let func = &statements[1]["kind"];
+    assert_eq!(func["type"], "FuncDef");
+    assert_eq!(func["value"]["params"][0]["name"], "a");
+    assert_eq!(func["value"]["params"][0]["ty"]["ty"], "string");
+    assert_eq!(func["value"]["params"][1]["ty"], Value::Null);
+    assert_eq!(func["value"]["return_type"]["ty"], "bool");
+    assert_eq!(func["value"]["body"].as_array().unwrap().len(), 1);
+    assert_eq!(statements[1]["span"], json!({ "line": 2, "col": 1, "end_line": 4, "end_col": 2 }));
+
+    assert_eq!(statements[2]["kind"]["type"], "Where");
+    assert_eq!(statements[2]["kind"]["value"]["condition"]["kind"]["value"]["op"], "Gt");
+}
@@ -0,0 +1,122 @@
+// Test file for source spans on tokens and AST nodes
+
+use misty_db::interpreter::Interpreter;
+use misty_db::interpreter::ast::{ExprKind, StatementKind};
+use misty_db::interpreter::parser::Parser;
+use misty_db::interpreter::span::Span;
+use misty_db::interpreter::tokenizer::{TokenKind, Tokenizer};
+
+#[test]
+fn test_token_spans() {
+    let tokens = Tokenizer::tokenize("let total = 42;\nx >= 10;".to_string()).unwrap();
+
+    assert_eq!(tokens[0].span, Span::new(1, 1, 1, 4));   // let
+    assert_eq!(tokens[1].span, Span::new(1, 5, 1, 10));  // total
+    assert_eq!(tokens[3].span, Span::new(1, 13, 1, 15)); // 42
+    assert_eq!(tokens[6].span, Span::new(2, 3, 2, 5));   // >=
+}
+
+#[test]
+fn test_multiline_string_span() {
+    let tokens = Tokenizer::tokenize("\"ab\ncd\" x".to_string()).unwrap();
+
+    assert_eq!(tokens[0].span, Span::new(1, 1, 2, 4));
+    assert_eq!(tokens[1].span, Span::new(2, 5, 2, 6));
+}
+
+#[test]
+fn test_unicode_identifiers() {
+    let tokens = Tokenizer::tokenize("let größe = 名前 + _ñ1;".to_string()).unwrap();
+
+    assert_eq!(tokens[1].kind, TokenKind::Identifier("größe".to_string()));
+    assert_eq!(tokens[1].span, Span::new(1, 5, 1, 10));
+    assert_eq!(tokens[3].kind, TokenKind::Identifier("名前".to_string()));
+    assert_eq!(tokens[3].span, Span::new(1, 13, 1, 15));
+    assert_eq!(tokens[5].kind, TokenKind::Identifier("_ñ1".to_string()));
+    assert_eq!(tokens[6].span, Span::new(1, 21, 1, 22));
+
+    // a symbol is not an identifier, the message shows the whole char
+    let err = Tokenizer::tokenize("let a = 1 € 2;".to_string()).unwrap_err();
+    assert_eq!(err.to_string(), "Unexpected char: € at line 1, column 11");
+    assert!(Tokenizer::tokenize("let ½ = 0;".to_string()).is_err());
+}
+
+#[test]
+fn test_multibyte_strings_and_comments() {
+    let source = "\"héllo ☕\" x // ünïcödé\n/* 😀\n ☕ */ y".to_string();
+    let (tokens, comments) = Tokenizer::tokenize_with_comments(source).unwrap();
+
+    assert_eq!(tokens[0].kind, TokenKind::String("héllo ☕".to_string()));
+    assert_eq!(tokens[0].span, Span::new(1, 1, 1, 10));
+    assert_eq!(tokens[1].span, Span::new(1, 11, 1, 12));
+    assert_eq!(comments[0].text, "// ünïcödé");
+    assert_eq!(comments[0].span, Span::new(1, 13, 1, 23));
+    assert_eq!(comments[1].span, Span::new(2, 1, 3, 6));
+    assert_eq!(tokens[2].span, Span::new(3, 7, 3, 8));
+
+    // a tab is 4 columns wide in comments too
+    let (tokens, _) = Tokenizer::tokenize_with_comments("/*\t*/ x".to_string()).unwrap();
+    assert_eq!(tokens[0].span, Span::new(1, 10, 1, 11));
+}
+
+#[test]
+fn test_column_conversions() {
+    use misty_db::interpreter::span::{byte_offset, col_from_utf16, utf16_offset};
+
+    let line = "let s = \"😀\"; t";
+    assert_eq!(byte_offset(line, 12), 14);   // ;
+    assert_eq!(utf16_offset(line, 12), 12);
+    assert_eq!(col_from_utf16(line, 12), 12);
+    assert_eq!(byte_offset(line, 100), line.len());
+
+    let tabbed = "\tx";
+    assert_eq!(utf16_offset(tabbed, 5), 1);
+    assert_eq!(col_from_utf16(tabbed, 1), 5);
+}
+
+#[test]
+fn test_statement_and_expr_spans() {
+    let source = "let r = (1 + 2) * x;\nfunc f(a) {\n    a;\n}".to_string();
+    let ast = Parser::new(Tokenizer::tokenize(source).unwrap()).parse().unwrap();
+
+    assert_eq!(ast.statements[0].span, Span::new(1, 1, 1, 21));
+    match &ast.statements[0].kind {
+        StatementKind::Let { value, .. } => {
+            assert_eq!(value.span, Span::new(1, 10, 1, 20));
+            match &value.kind {
+                ExprKind::Binary { left, right, .. } => {
+                    assert_eq!(left.span, Span::new(1, 10, 1, 15));
+                    assert_eq!(right.span, Span::new(1, 19, 1, 20));
+                }
+                other => panic!("Expected binary expression, found {:?}", other),
+            }
+        }
+        other => panic!("Expected let statement, found {:?}", other),
+    }
+
+    assert_eq!(ast.statements[1].span, Span::new(2, 1, 4, 2));
+}
+
+#[test]
+fn test_call_span() {
+    let ast = Parser::new(Tokenizer::tokenize("add(1, 2);".to_string()).unwrap()).parse().unwrap();
+
+    match &ast.statements[0].kind {
+        StatementKind::Expr(expr) => assert_eq!(expr.span, Span::new(1, 1, 1, 10)),
+        other => panic!("Expected expression statement, found {:?}", other),
+    }
+}
+
+#[test]
+fn test_runtime_error_location() {
+    let source = "let a = 1;\nlet b = a / 0;".to_string();
+
+    let err = Interpreter::execute_full_pipeline(source).unwrap_err();
+    assert!(err.to_string().contains("Division by zero at line 2, column 9"), "{}", err);
+}
+
+#[test]
+fn test_undefined_variable_location() {
+    let err = Interpreter::execute_full_pipeline("let a = 1 + missing;".to_string()).unwrap_err();
+    assert!(err.to_string().contains("Undefined variable: missing at line 1, column 13"), "{}", err);
+}
@@ -0,0 +1,62 @@
+// Test file for Mu-level tracebacks on runtime errors
+
+use misty_db::interpreter::Interpreter;
+use misty_db::interpreter::evaluator::{Evaluator, RuntimeError};
+use misty_db::interpreter::parser::Parser;
+use misty_db::interpreter::span::Span;
+use misty_db::interpreter::tokenizer::Tokenizer;
+
+fn run(source: &str) -> anyhow::Error {
+    let ast = Parser::new(Tokenizer::tokenize(source.to_string()).unwrap()).parse().unwrap();
+    Evaluator::new().evaluate(ast).unwrap_err()
+}
+
+#[test]
+fn test_trace_of_nested_calls() {
+    let err = run(r#"
+func area(w, h) {
+    w * h / zero;
+}
+func report(w) {
+    area(w, 2);
+}
+report(3);
+"#);
+
+    let runtime = err.downcast_ref::<RuntimeError>().expect("Expected a RuntimeError");
+    assert!(runtime.message.starts_with("Undefined variable: zero"), "{}", runtime.message);
+
+    let names: Vec<&str> = runtime.trace.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, vec!["report", "area"]);
+    assert_eq!(runtime.trace[0].call_span, Span::new(8, 1, 8, 10));
+    assert_eq!(runtime.trace[1].call_span, Span::new(6, 5, 6, 15));
+}
+
+#[test]
+fn test_top_level_error_has_empty_trace() {
+    let err = run("let a = 1 / 0;");
+
+    let runtime = err.downcast_ref::<RuntimeError>().expect("Expected a RuntimeError");
+    assert!(runtime.trace.is_empty());
+    assert_eq!(err.to_string(), "Division by zero at line 1, column 9");
+}
+
+#[test]
+fn test_trace_is_printed() {
+    let source = "func f(a) {\n    a / 0;\n}\nfunc g() {\n    f(1);\n}\ng();".to_string();
+
+    let message = Interpreter::execute_full_pipeline(source).unwrap_err().to_string();
+    assert!(message.ends_with(
+        "Traceback (most recent call last):\n  in g() called at line 7, column 1\n  in f() called at line 5, column 5"
+    ), "{}", message);
+}
+
+#[test]
+fn test_error_in_arguments_reports_caller_frame() {
+    // the bad argument is evaluated before entering `inner`
+    let err = run("func inner(x) {\n    x;\n}\nfunc outer() {\n    inner(missing);\n}\nouter();");
+
+    let runtime = err.downcast_ref::<RuntimeError>().expect("Expected a RuntimeError");
+    let names: Vec<&str> = runtime.trace.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, vec!["outer"]);
+}
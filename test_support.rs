@@ -0,0 +1,28 @@
+// Helpers shared by the test files, each one includes this module with
+// `mod test_support;`
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+// Output sink the test keeps a handle on while the interpreter owns a clone
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    // What was written so far
+    pub fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
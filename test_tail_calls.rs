@@ -0,0 +1,101 @@
+// Calls in tail position do not grow the native stack of the tree-walker,
+// the VM keeps its frames on the heap. Recursion far deeper than the native stack
+// could hold without that runs on both
+
+use misty_db::interpreter::compiler::Compiler;
+use misty_db::interpreter::evaluator::{Evaluator, RuntimeError};
+use misty_db::interpreter::parser::Parser;
+use misty_db::interpreter::tokenizer::Tokenizer;
+use misty_db::interpreter::vm::Vm;
+
+mod test_support;
+
+use test_support::SharedBuffer;
+
+fn run(source: &str) -> (String, anyhow::Result<()>) {
+    let ast = Parser::new(Tokenizer::tokenize(source.to_string()).unwrap()).parse().unwrap();
+    let buffer = SharedBuffer::default();
+    let result = Evaluator::with_output(Box::new(buffer.clone())).evaluate(ast);
+    (buffer.contents(), result)
+}
+
+fn run_vm(source: &str) -> (String, anyhow::Result<()>) {
+    let ast = Parser::new(Tokenizer::tokenize(source.to_string()).unwrap()).parse().unwrap();
+    let program = Compiler::compile(&ast).unwrap();
+    let buffer = SharedBuffer::default();
+    let result = Vm::with_output(Box::new(buffer.clone())).run(program);
+    (buffer.contents(), result)
+}
+
+const COUNT_DOWN: &str = r#"
+func count(n, acc) {
+    match n { 0 => acc, _ => count(n - 1, acc + 1) };
+}
+let total = count(150000, 0);
+"#;
+
+#[test]
+fn test_deep_recursion() {
+    let (output, result) = run(COUNT_DOWN);
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(output.ends_with("Let total = 150000\n"), "{}", &output[output.len() - 100..]);
+}
+
+#[test]
+fn test_deep_recursion_on_the_vm() {
+    let (output, result) = run_vm(COUNT_DOWN);
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(output.ends_with("Let total = 150000\n"), "{}", &output[output.len() - 100..]);
+}
+
+#[test]
+fn test_mutual_recursion_and_lambdas() {
+    let (output, result) = run(r#"
+func is_even(n) { match n { 0 => true, _ => is_odd(n - 1) }; }
+func is_odd(n) { match n { 0 => false, _ => is_even(n - 1) }; }
+let even = is_even(150001);
+
+let loop = fn(n, step) => match n { 0 => "done", _ => step(n - 1, step) };
+loop(150000, loop);
+"#);
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(output.contains("false\nLet even = false\n"), "{}", &output[output.len() - 100..]);
+    // a `=>` body is not a statement, nothing is printed until the top level call
+    assert!(output.ends_with("Let loop = <fn>\ndone\n"), "{}", &output[output.len() - 100..]);
+}
+
+#[test]
+fn test_tail_calls_keep_their_frames_and_scopes() {
+    // the caller's locals stay visible to the callee, its assignments reach the globals
+    let (output, result) = run(r#"
+let calls = 0;
+func show() { calls = calls + 1; level; }
+func nest(level) { match level { 3 => show(), l => nest(l + 1) }; }
+nest(1);
+calls;
+"#);
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(output.ends_with("3\n3\n3\n3\n3\n1\n"), "{}", output);
+
+    let (_, result) = run("func fail(n) { match n { 0 => 1 / n, _ => fail(n - 1) }; }\nfail(2);");
+    let err = result.unwrap_err();
+    let runtime = err.downcast_ref::<RuntimeError>().expect("Expected a RuntimeError");
+    let calls: Vec<(&str, usize, usize)> = runtime.trace.iter().map(|f| (f.name.as_str(), f.call_span.line, f.repeated)).collect();
+    assert_eq!(calls, vec![("fail", 2, 0), ("fail", 1, 1)]);
+}
+
+#[test]
+fn test_traceback_of_a_deep_failure_stays_short() {
+    let source = "func fail(n) { match n { 0 => 1 / n, _ => fail(n - 1) }; }\nfail(50000);";
+
+    for (_, result) in [run(source), run_vm(source)] {
+        let message = result.unwrap_err().to_string();
+        assert!(message.lines().count() < 6, "{}", message);
+        assert!(message.ends_with(
+            "in fail() called at line 2, column 1\n  in fail() called at line 1, column 43\n  ... repeated 49999 more times"
+        ), "{}", message);
+    }
+}
@@ -0,0 +1,211 @@
+// Test file for type annotations and the type checker
+
+use misty_db::interpreter::Interpreter;
+use misty_db::interpreter::ast::{StatementKind, Type};
+use misty_db::interpreter::parser::Parser;
+use misty_db::interpreter::tokenizer::Tokenizer;
+use misty_db::interpreter::typechecker::TypeChecker;
+
+fn check(source: &str) -> Vec<String> {
+    let ast = Parser::new(Tokenizer::tokenize(source.to_string()).unwrap()).parse().unwrap();
+    TypeChecker::check(&ast).iter().map(|d| d.to_string()).collect()
+}
+
+#[test]
+fn test_parse_annotations() {
+    let source = "let n: number = 1;\nfunc area(w: number, h) -> number {\n    w * h;\n}";
+    let ast = Parser::new(Tokenizer::tokenize(source.to_string()).unwrap()).parse().unwrap();
+
+    match &ast.statements[0].kind {
+        StatementKind::Let { ty, .. } => assert_eq!(ty.as_ref().map(|t| t.ty), Some(Type::Number)),
+        other => panic!("Expected let statement, found {:?}", other),
+    }
+
+    match &ast.statements[1].kind {
+        StatementKind::FuncDef { params, return_type, .. } => {
+            assert_eq!(params[0].name, "w");
+            assert_eq!(params[0].ty.as_ref().map(|t| t.ty), Some(Type::Number));
+            assert!(params[1].ty.is_none());
+            assert_eq!(return_type.as_ref().map(|t| t.ty), Some(Type::Number));
+        }
+        other => panic!("Expected function definition, found {:?}", other),
+    }
+}
+
+#[test]
+fn test_unknown_type_name() {
+    let tokens = Tokenizer::tokenize("let n: integer = 1;".to_string()).unwrap();
+    let err = Parser::new(tokens).parse().unwrap_err();
+    assert!(err.to_string().contains("Unknown type 'integer' at line 1, column 8"), "{}", err);
+}
+
+#[test]
+fn test_well_typed_program() {
+    let errors = check(r#"
+        let name: string = "Misty";
+        func area(w: number, h: number) -> number {
+            w * h;
+        }
+        func greet(who) {
+            "Hello, " + who;
+        }
+        let a: number = area(2, 3) + 1;
+        let ok: bool = a > 5 && true;
+        greet(name);
+    "#);
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn test_let_annotation_mismatch() {
+    let errors = check("let n: number = \"ten\";");
+    assert_eq!(errors, vec!["Type mismatch: expected number, found string at line 1, column 17"]);
+}
+
+#[test]
+fn test_operator_mismatch() {
+    let errors = check("let a = 1;\nlet b = a + true;");
+    assert_eq!(errors, vec!["Type mismatch: cannot apply '+' to number and bool at line 2, column 9"]);
+}
+
+#[test]
+fn test_argument_and_return_mismatch() {
+    let errors = check(r#"
+func area(w: number, h: number) -> string {
+    w * h;
+}
+area("wide", 2);
+"#);
+
+    assert_eq!(errors, vec![
+        "Type mismatch: expected string, found number at line 3, column 5",
+        "Type mismatch: expected number, found string at line 5, column 6",
+    ]);
+}
+
+#[test]
+fn test_inferred_return_type() {
+    let errors = check("func double(x: number) {\n    x * 2;\n}\nlet s: string = double(4);");
+    assert_eq!(errors, vec!["Type mismatch: expected string, found number at line 4, column 17"]);
+}
+
+#[test]
+fn test_unknown_types_are_accepted() {
+    // unannotated parameters may hold anything at runtime
+    let errors = check("func f(x) {\n    x + 1;\n}\nfunc g(y) {\n    y && true;\n}");
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    // but some operators can never work, whatever the other operand is
+    let errors = check("func h(z) {\n    z + true;\n}");
+    assert_eq!(errors, vec!["Type mismatch: cannot apply '+' to unknown and bool at line 2, column 5"]);
+}
+
+#[test]
+fn test_pipeline_rejects_before_running() {
+    let err = Interpreter::execute_full_pipeline("let a = 1 / 0;\nlet b: bool = 3;".to_string()).unwrap_err();
+    let message = err.to_string();
+
+    assert!(message.contains("Type mismatch: expected bool, found number"), "{}", message);
+    assert!(!message.contains("Division by zero"), "{}", message);
+}
+
+#[test]
+fn test_lists_and_builtins() {
+    assert!(check(r#"
+        let items: list = [1, 2];
+        let n: number = len(items) + len(args);
+        items[n - 2];
+        args[0];
+    "#).is_empty());
+
+    assert_eq!(check("let n = 5;\nn[0];\n[1][\"a\"];\nexit(\"x\");\nlet s: string = len(\"ab\");"), vec![
+        "Type mismatch: cannot index number with number at line 2, column 1",
+        "Type mismatch: cannot index list with string at line 3, column 1",
+        "Type mismatch: expected number, found string at line 4, column 6",
+        "Type mismatch: expected string, found number at line 5, column 17",
+    ]);
+}
+
+#[test]
+fn test_assignments_and_maps() {
+    assert!(check(r#"
+        let count = 0;
+        count += 1;
+        let user: map = {name: "Ada", age: 36};
+        user.age = "unknown";
+        user["name"] + "!";
+        let label: string = "a";
+        label += "b";
+    "#).is_empty());
+
+    assert_eq!(check("let n = 1;\nn = \"one\";\nlet s = \"a\";\ns *= 2;\nn.field;\n{a: 1}[0];"), vec![
+        "Type mismatch: cannot assign string to 'n' of type number at line 2, column 5",
+        "Type mismatch: cannot apply '*' to string and number at line 4, column 1",
+        "Type mismatch: cannot access field 'field' of number at line 5, column 1",
+        "Type mismatch: cannot index map with number at line 6, column 1",
+    ]);
+}
+
+#[test]
+fn test_null() {
+    assert!(check(r#"
+        let user = {name: "Ada"};
+        let age: number = user.age ?? 0;
+        let name = user?.name ?? "anonymous";
+        user.age == null;
+        let missing = null;
+        missing = 1;
+    "#).is_empty());
+
+    // a number is never null, `??` keeps its type
+    assert_eq!(check("let n = 1 ?? \"one\";\nn + \"!\";\nlet b = true;\nb?.field;"), vec![
+        "Type mismatch: cannot apply '+' to number and string at line 2, column 1",
+        "Type mismatch: cannot access field 'field' of bool at line 4, column 1",
+    ]);
+}
+
+#[test]
+fn test_try_catch() {
+    assert!(check(r#"
+        func parse(s) -> number {
+            try { 1; } catch (e) { 0; }
+        }
+        let n: number = parse("1");
+        try { throw "x"; } catch (e) { e.message; }
+    "#).is_empty());
+
+    assert_eq!(check("try { 1; } catch (e) { e + 1; }\nfunc f() -> number { try { 1; } catch (e) { \"none\"; } }"), vec![
+        "Type mismatch: cannot apply '+' to map and number at line 1, column 24",
+    ]);
+}
+
+#[test]
+fn test_match() {
+    assert!(check(r#"
+        func sign(n: number) -> string {
+            match n { 0 => "zero", m if m > 0 => "positive", _ => "negative" }
+        }
+        let rest: list = match [1, 2] { [_, ..tail] => tail, _ => [] };
+    "#).is_empty());
+
+    assert_eq!(check("let n = 1;
+match n { m if m + 1 => m, _ => 0 };
+func f() -> number { match 1 { 1 => \"one\", _ => \"many\" } }"), vec![
+        "Type mismatch: condition must be bool, found number at line 2, column 16",
+        "Type mismatch: expected number, found string at line 3, column 22",
+    ]);
+}
+
+#[test]
+fn test_lambdas() {
+    assert!(check(r#"
+        let double = fn(x: number) -> number => x * 2;
+        let n: number = double(2) + 1;
+        map([1, 2], fn(x) { x + 1; });
+    "#).is_empty());
+
+    assert_eq!(check("let f = fn(x: number) -> string => x * 2;\nlet g = fn(s: string) { -s; };"), vec![
+        "Type mismatch: expected string, found number at line 1, column 36",
+        "Type mismatch: cannot apply '-' to string at line 2, column 25",
+    ]);
+}
@@ -0,0 +1,551 @@
+// Differential tests: the bytecode VM must behave exactly like the tree-walker
+
+use misty_db::interpreter::compiler::Compiler;
+use misty_db::interpreter::evaluator::Evaluator;
+use misty_db::interpreter::parser::Parser;
+use misty_db::interpreter::tokenizer::Tokenizer;
+use misty_db::interpreter::vm::Vm;
+
+mod test_support;
+
+use test_support::SharedBuffer;
+
+// Output and error message of a run
+type Outcome = (String, Option<String>);
+
+fn run_tree_walker(source: &str) -> Outcome {
+    let ast = Parser::new(Tokenizer::tokenize(source.to_string()).unwrap()).parse().unwrap();
+    let buffer = SharedBuffer::default();
+    let result = Evaluator::with_output(Box::new(buffer.clone())).evaluate(ast);
+    (buffer.contents(), result.err().map(|e| e.to_string()))
+}
+
+fn run_vm(source: &str) -> Outcome {
+    let ast = Parser::new(Tokenizer::tokenize(source.to_string()).unwrap()).parse().unwrap();
+    let program = Compiler::compile(&ast).unwrap();
+    let buffer = SharedBuffer::default();
+    let result = Vm::with_output(Box::new(buffer.clone())).run(program);
+    (buffer.contents(), result.err().map(|e| e.to_string()))
+}
+
+fn assert_same(source: &str) -> Outcome {
+    let expected = run_tree_walker(source);
+    let actual = run_vm(source);
+    assert_eq!(expected, actual, "backends differ on:\n{}", source);
+    actual
+}
+
+#[test]
+fn test_literals_and_arithmetic() {
+    let (output, error) = assert_same(r#"
+        let pi = 3.14159;
+        let name = "MistyDB";
+        let is_active = true;
+        let result = (10 + 5) * 2 - 8 / 4;
+        let complex = pi * 2 + 1.0;
+        -result;
+        "Hello, " + name;
+    "#);
+
+    assert!(error.is_none());
+    assert!(output.contains("Let result = 28"));
+}
+
+#[test]
+fn test_comparisons_and_logic() {
+    assert_same(r#"
+        let a = 5;
+        a > 3 && a < 10;
+        a == 5 || false;
+        a != 5;
+        a >= 5;
+        a <= 4;
+        true == false;
+    "#);
+}
+
+#[test]
+fn test_functions_and_locals() {
+    let (output, _) = assert_same(r#"
+        func add(a, b) {
+            a + b;
+        }
+        func calc(x, y) {
+            let sum = add(x, y);
+            let sum = sum * 2;
+            sum - 1;
+        }
+        calc(4, 7);
+        func empty() {
+        }
+        empty();
+    "#);
+
+    assert!(output.contains("21\n"));
+    assert!(output.contains("()\n"));
+}
+
+#[test]
+fn test_functions_read_globals() {
+    assert_same(r#"
+        let factor = 3;
+        func scale(x) {
+            x * factor;
+        }
+        scale(2);
+        let factor = 10;
+        scale(2);
+    "#);
+}
+
+#[test]
+fn test_dynamic_scoping_of_caller_locals() {
+    let (output, error) = assert_same(r#"
+        let x = 1;
+        func show() {
+            x;
+        }
+        func wrap(x) {
+            show();
+        }
+        wrap(42);
+        show();
+    "#);
+
+    assert!(error.is_none());
+    assert!(output.contains("42\n"));
+}
+
+#[test]
+fn test_local_declared_after_use() {
+    assert_same(r#"
+        let v = "global";
+        func f() {
+            v;
+            let v = "local";
+            v;
+        }
+        f();
+        v;
+    "#);
+}
+
+#[test]
+fn test_redefined_function() {
+    assert_same(r#"
+        func f() {
+            1;
+        }
+        f();
+        func f() {
+            2;
+        }
+        f();
+    "#);
+}
+
+#[test]
+fn test_not_implemented_statements() {
+    assert_same(r#"
+        where age > 18 && status == "active";
+        let after = 1;
+    "#);
+}
+
+#[test]
+fn test_runtime_errors() {
+    for source in [
+        "let a = 1 / 0;",
+        "let a = missing;",
+        "undefined_fn(1);",
+        "func f(a) { a; }\nf(1, 2);",
+        "let a = 1 + true;",
+        "let a = \"x\" - 1;",
+        "func f() { 1; }\nmissing(f());",
+    ] {
+        let (_, error) = assert_same(source);
+        assert!(error.is_some(), "expected an error for: {}", source);
+    }
+}
+
+#[test]
+fn test_tracebacks() {
+    let (_, error) = assert_same(r#"
+func area(w, h) {
+    w * h / zero;
+}
+func report(w) {
+    let a = area(w, 2);
+    a;
+}
+report(3);
+"#);
+
+    assert!(error.unwrap().contains("Traceback (most recent call last):\n  in report() called at line 9, column 1"));
+}
+
+#[test]
+fn test_sample_program() {
+    let source = std::fs::read_to_string("sample_program.mu").unwrap();
+    assert_same(&source);
+}
+
+#[test]
+fn test_lists_and_builtins() {
+    let (output, error) = assert_same(r#"
+        let items = [1, "two", [3, 4]];
+        items[2][1];
+        len(items);
+        len("four");
+        func first(list) {
+            list[0];
+        }
+        first(items) + 1;
+        [];
+    "#);
+
+    assert!(error.is_none());
+    assert!(output.contains("Let items = [1, \"two\", [3, 4]]\n4\n3\n4\n"), "{}", output);
+    assert!(output.ends_with("2\n[]\n"), "{}", output);
+
+    for source in [
+        "[1, 2][2];",
+        "[1, 2][0.5];",
+        "let n = 1; n[0];",
+        "len(1);",
+        "len();",
+        "exit(\"no\");",
+        "exit(99999999999);",
+        "exit(-1);",
+        "exit(1.5);",
+    ] {
+        let (_, error) = assert_same(source);
+        assert!(error.is_some(), "expected an error for: {}", source);
+    }
+}
+
+#[test]
+fn test_assignment_and_maps() {
+    let (output, error) = assert_same(r#"
+        let count = 0;
+        count = count + 1;
+        count += 10;
+        count *= 2;
+        count -= 1;
+        count /= 3;
+        let grid = [[1, 2], [3, 4]];
+        grid[1][0] += 5;
+        let user = {name: "Ada", "home town": "London", age: 36};
+        user.age = 4;
+        user.email = "ada@example.com";
+        user["home town"];
+        func birthday(years) {
+            user.age += years;
+            count = count + 1;
+            let local = 1;
+            local = 2;
+            years = 0;
+        }
+        birthday(2);
+        user.age;
+        count;
+        len(user);
+    "#);
+
+    assert!(error.is_none(), "{:?}", error);
+    assert!(output.contains("count = 1\ncount = 11\ncount = 22\ncount = 21\ncount = 7\n"), "{}", output);
+    assert!(output.contains("grid[1][0] = 8\n"), "{}", output);
+    assert!(output.contains("Let user = {age: 36, \"home town\": \"London\", name: \"Ada\"}\n"), "{}", output);
+    assert!(output.contains("user.email = ada@example.com\nLondon\n"), "{}", output);
+    // the function changed the globals, not its own locals
+    assert!(output.ends_with("local = 2\nyears = 0\n0\n6\n8\n4\n"), "{}", output);
+
+    for source in [
+        "missing = 1;",
+        "let n = 1; n[0] = 2;",
+        "let items = [1]; items[1] = 2;",
+        "let user = {}; user.age += 1;",
+        "let user = {}; user.address.city = \"Paris\";",
+        "let n = 1; n.age;",
+        "let s = \"a\"; s -= 1;",
+    ] {
+        let (_, error) = assert_same(source);
+        assert!(error.is_some(), "expected an error for: {}", source);
+    }
+}
+
+#[test]
+fn test_null() {
+    let (output, error) = assert_same(r#"
+        let user = {name: "Ada", address: {city: "London"}};
+        let nobody = null;
+        user.age;
+        user.age == null;
+        user.name != null;
+        null == null;
+        1 == null;
+        user.age ?? 36;
+        user.name ?? "anonymous";
+        nobody ?? user.age ?? "unknown";
+        user?.address?.city;
+        nobody?.address?.city;
+        user.phone?.number ?? "no phone";
+        func loud() { "evaluated"; 0; }
+        user.name ?? loud();
+        nobody ?? loud();
+    "#);
+
+    assert!(error.is_none(), "{:?}", error);
+    assert!(output.starts_with("Let user = {address: {city: \"London\"}, name: \"Ada\"}\nLet nobody = null\n"), "{}", output);
+    assert!(output.contains("null\ntrue\ntrue\ntrue\nfalse\n36\nAda\nunknown\nLondon\nnull\nno phone\n"), "{}", output);
+    // the right side of `??` only runs when the left one is null
+    assert!(output.ends_with("Ada\nevaluated\n0\n0\n"), "{}", output);
+
+    for source in [
+        "null + 1;",
+        "null.name;",
+        "let n = 1; n?.name;",
+        "let user = {}; user.address.city;",
+    ] {
+        let (_, error) = assert_same(source);
+        assert!(error.is_some(), "expected an error for: {}", source);
+    }
+}
+
+#[test]
+fn test_constants() {
+    let (output, error) = assert_same(r#"
+        const LIMIT = 100;
+        func shadow_param(LIMIT) {
+            LIMIT += 1;
+        }
+        func shadow_let() {
+            let LIMIT = 5;
+            LIMIT = LIMIT * 2;
+        }
+        shadow_param(1);
+        shadow_let();
+        LIMIT;
+    "#);
+
+    assert!(error.is_none(), "{:?}", error);
+    assert!(output.starts_with("Const LIMIT = 100\n"), "{}", output);
+    assert!(output.ends_with("LIMIT = 2\n2\nLet LIMIT = 5\nLIMIT = 10\n10\n100\n"), "{}", output);
+
+    for (source, message) in [
+        ("const A = 1; A = 2;", "Cannot assign to constant: A"),
+        ("const A = [1]; A[0] += 1;", "Cannot assign to constant: A"),
+        ("const A = 1; func f() { A = 2; } f();", "Cannot assign to constant: A"),
+        ("const A = 1; let A = 2;", "Cannot redeclare constant: A"),
+        ("let A = 1; const A = 2; const A = 3;", "Cannot redeclare constant: A"),
+        ("func f() { const A = 1; const A = 2; } f();", "Cannot redeclare constant: A"),
+    ] {
+        let (_, error) = assert_same(source);
+        assert!(error.as_deref().is_some_and(|e| e.starts_with(message)), "{}: {:?}", source, error);
+    }
+}
+
+#[test]
+fn test_try_catch() {
+    let (output, error) = assert_same(r#"
+        func safe_div(a, b) {
+            try {
+                a / b;
+            } catch (e) {
+                e.kind;
+                0;
+            }
+        }
+        let q = safe_div(1, 0);
+        func find(name) {
+            throw {kind: "NotFound", message: "no user " + name};
+        }
+        func lookup(name) {
+            let found = false;
+            try {
+                find(name);
+                found = true;
+            } catch (err) {
+                err.message;
+            } finally {
+                "cleanup";
+            }
+        }
+        lookup("ada");
+        try { throw "boom"; } catch (e) { e; }
+        try { missing; } catch (e) { e.kind; }
+        try {
+            try { [1][5]; } finally { "inner finally"; }
+        } catch (e) {
+            e.kind;
+            try { throw e; } catch (again) { again.message; }
+        }
+        try { 1; } catch (e) { 2; } finally { 3; }
+        "after";
+    "#);
+
+    assert!(error.is_none(), "{:?}", error);
+    assert!(output.starts_with("Defined function: safe_div\nDivisionByZero\n0\nLet q = 0\n"), "{}", output);
+    assert!(output.contains("Let found = false\nno user ada\ncleanup\nno user ada\n"), "{}", output);
+    assert!(output.contains("{kind: \"Error\", message: \"boom\", value: \"boom\"}\nUndefinedVariable\n"), "{}", output);
+    // the message of a caught error does not repeat where it happened
+    assert!(output.contains("inner finally\nIndexError\nList index out of bounds: 5 for a list of length 1\n"), "{}", output);
+    assert!(output.ends_with("1\n3\nafter\n"), "{}", output);
+
+    // what is not caught keeps its message and traceback, finally blocks run on the way out
+    let (output, error) = assert_same(r#"
+        func fail() { 1 / 0; }
+        try { fail(); } finally { "finally"; }
+    "#);
+    assert!(output.ends_with("finally\n"), "{}", output);
+    assert_eq!(error.as_deref(), Some("Division by zero at line 2, column 23\nTraceback (most recent call last):\n  in fail() called at line 3, column 15"));
+
+    let (_, error) = assert_same("try { 1; } catch (e) { throw {kind: \"Custom\", message: \"from catch\"}; } finally { 2; }\nthrow 5;");
+    assert_eq!(error.as_deref(), Some("Uncaught error: 5 at line 2, column 1"));
+
+    let (output, error) = assert_same("try { throw {message: \"bad\"}; } catch (e) { throw e; } finally { \"finally\"; }");
+    assert_eq!(output, "finally\n");
+    assert_eq!(error.as_deref(), Some("Uncaught error: bad at line 1, column 45"));
+}
+
+#[test]
+fn test_match() {
+    let (output, error) = assert_same(r#"
+        func describe(value) {
+            match value {
+                0 => "zero",
+                -1 => "minus one",
+                [] => "empty list",
+                [first, ..rest] => {head: first, tail: rest},
+                { name, age } if age > 18 => name + " is an adult",
+                { name } => name,
+                true => "yes",
+                null => "nothing",
+                _ => "something else",
+            }
+        }
+        describe(0);
+        describe(-1);
+        describe([]);
+        describe(["a", "b", "c"]);
+        describe({name: "Ada", age: 36});
+        describe({name: "Bo", age: 3});
+        describe(true);
+        describe(null);
+        describe("text");
+        let x = 5;
+        let doubled = match x { x if x > 3 => x * 2, _ => 0 };
+        x;
+        const C = 3;
+        match 1 { C => C + 1 };
+        C;
+        match [1, [2, 3]] { [a, [b, c]] => a + b + c, _ => 0 };
+        match {point: {x: 1, y: 2}} { {point: {x: 1, y}} => y, _ => 0 };
+    "#);
+
+    assert!(error.is_none(), "{:?}", error);
+    assert!(output.contains("zero\nzero\nminus one\nminus one\nempty list\nempty list\n"), "{}", output);
+    assert!(output.contains("{head: \"a\", tail: [\"b\", \"c\"]}\n"), "{}", output);
+    assert!(output.contains("Ada is an adult\nAda is an adult\nBo\nBo\nyes\nyes\nnothing\nnothing\nsomething else\n"), "{}", output);
+    // the bindings only live in their arm
+    assert!(output.contains("Let doubled = 10\n5\nConst C = 3\n2\n3\n6\n2\n"), "{}", output);
+
+    // no arm matches, or a guard that is not a bool
+    let (output, error) = assert_same(r#"
+        func f(n) { match n { 1 => "one" } }
+        try { f(2); } catch (e) { e.kind; }
+        match 1 { n if n => n, _ => 0 };
+    "#);
+    assert!(output.ends_with("MatchError\n"), "{}", output);
+    assert_eq!(error.as_deref(), Some("Match guard must be a bool, got 1 at line 4, column 24"));
+
+    // an error in an arm puts the bindings back before the catch runs
+    let (output, error) = assert_same(r#"
+        let n = "outer";
+        func g() {
+            let m = "local";
+            try { match 0 { m => 1 / m } } catch (e) { m; }
+        }
+        g();
+        try { match 0 { n => 1 / n } } catch (e) { n; }
+    "#);
+    assert!(error.is_none(), "{:?}", error);
+    assert!(output.ends_with("local\nlocal\nouter\n"), "{}", output);
+}
+
+#[test]
+fn test_lambdas() {
+    let (output, error) = assert_same(r#"
+        func make_adder(n) {
+            fn(x) => x + n;
+        }
+        make_adder(1)(2);
+        let add_ten = make_adder(10);
+        add_ten(5);
+        let double = fn(x) => x * 2;
+        map([1, 2, 3], double);
+        filter([1, 2, 3, 4], fn(x) { let big = x > 2; big; });
+        func inc(x) { x + 1; }
+        map([1, 2], inc);
+        let f = inc;
+        f(10);
+        (fn(a, b) => a - b)(5, 3);
+        map([[1], [2, 3]], len);
+        func apply_all(items) {
+            match items { [first, ..] => fn() => first, _ => fn() => null };
+        }
+        apply_all([7])();
+    "#);
+
+    assert!(error.is_none(), "{:?}", error);
+    assert!(output.contains("<fn>\n3\n<fn>\nLet add_ten = <fn>\n15\nLet double = <fn>\n[2, 4, 6]\n"), "{}", output);
+    assert!(output.contains("Let big = true\ntrue\n[3, 4]\n"), "{}", output);
+    assert!(output.contains("[2, 3]\nLet f = <fn inc>\n11\n11\n2\n[1, 2]\n"), "{}", output);
+    assert!(output.ends_with("<fn>\n7\n"), "{}", output);
+
+    // a `try` in the function given to map catches its own errors, the others reach the caller
+    let (output, error) = assert_same(r#"
+        try { 5(1); } catch (e) { e.kind; }
+        try { map([1], fn(a, b) => a); } catch (e) { e.message; }
+        map([1, 2], fn(x) { try { throw x; } catch (e) { e.value * 10; } });
+        let g = fn(x) => x;
+        try { g(1, 2); } catch (e) { e.message; }
+        func outer() { map([1], fn(x) => x / 0); }
+        outer();
+    "#);
+    assert!(output.contains("TypeError\nFunction 'fn' expects 2 arguments, got 1\n"), "{}", output);
+    assert!(output.contains("[10, 20]\nLet g = <fn>\nFunction 'g' expects 1 arguments, got 2\n"), "{}", output);
+    assert!(error.as_deref().is_some_and(|e| e.starts_with("Division by zero at line 7, column 42")), "{:?}", error);
+    assert!(error.as_deref().is_some_and(|e| e.ends_with("in fn() called at line 7, column 24")), "{:?}", error);
+
+    // a function stored in a map field is called through the field
+    let (output, error) = assert_same("let m = {f: fn(x) => x * 2, inner: {g: len}};\nm.f(3);\nm.inner.g([1, 2]);");
+    assert!(error.is_none(), "{:?}", error);
+    assert!(output.ends_with("\n6\n2\n"), "{}", output);
+
+    // a builtin called back by map fails at the span of the map call, given once
+    let (_, error) = assert_same("map([1], len);");
+    assert_eq!(error.as_deref(), Some("len() expects a list, a string or a map, got Number(1.0) at line 1, column 1"));
+}
+
+#[test]
+fn test_exit_stops_both_backends() {
+    let (output, error) = assert_same(r#"
+        let a = 1;
+        func stop(code) {
+            exit(code);
+            let unreachable = 1;
+        }
+        stop(7);
+        let b = 2;
+    "#);
+
+    assert_eq!(output, "Let a = 1\nDefined function: stop\n");
+    // no traceback, exit is not an error
+    assert_eq!(error.as_deref(), Some("exit(7)"));
+
+    // try cannot catch it, finally blocks do not run
+    let (output, error) = assert_same("try { exit(3); } catch (e) { \"caught\"; } finally { \"finally\"; }");
+    assert_eq!(output, "");
+    assert_eq!(error.as_deref(), Some("exit(3)"));
+}